@@ -180,7 +180,6 @@ fn generate_tests() -> std::io::Result<()> {
             module_name: "parser_success",
             directory: "parser/success/",
             variant: "ParserSuccess",
-            too_slow_path: Rc::new(|path: &str| path == "largeExpression"),
             exclude_path: Rc::new(|path: &str| {
                 false
                     // Pretty sure the test is incorrect
@@ -200,7 +199,6 @@ fn generate_tests() -> std::io::Result<()> {
             module_name: "printer",
             directory: "parser/success/",
             variant: "Printer",
-            too_slow_path: Rc::new(|path: &str| path == "largeExpression"),
             output_type: Some(FileType::UI),
             ..default_feature.clone()
         },
@@ -208,7 +206,6 @@ fn generate_tests() -> std::io::Result<()> {
             module_name: "binary_encoding",
             directory: "parser/success/",
             variant: "BinaryEncoding",
-            too_slow_path: Rc::new(|path: &str| path == "largeExpression"),
             exclude_path: Rc::new(|path: &str| {
                 false
                     // Pretty sure the test is incorrect
@@ -250,14 +247,8 @@ fn generate_tests() -> std::io::Result<()> {
             variant: "ImportSuccess",
             exclude_path: Rc::new(|path: &str| {
                 false
-                    // TODO: import hash
-                    || path == "hashFromCache"
                     // TODO: the standard does not respect https://tools.ietf.org/html/rfc3986#section-5.2
                     || path == "unit/asLocation/RemoteCanonicalize4"
-                    // TODO: import headers
-                    || path == "customHeaders"
-                    || path == "headerForwarding"
-                    || path == "noHeaderForwarding"
             }),
             output_type: Some(FileType::Text),
             ..default_feature.clone()
@@ -266,11 +257,6 @@ fn generate_tests() -> std::io::Result<()> {
             module_name: "import_failure",
             directory: "import/failure/",
             variant: "ImportFailure",
-            exclude_path: Rc::new(|path: &str| {
-                false
-                    // TODO: import headers
-                    || path == "customHeadersUsingBoundVariable"
-            }),
             output_type: Some(FileType::UI),
             ..default_feature.clone()
         },
@@ -330,11 +316,6 @@ fn generate_tests() -> std::io::Result<()> {
             module_name: "type_inference_failure",
             directory: "type-inference/failure/",
             variant: "TypeInferenceFailure",
-            exclude_path: Rc::new(|path: &str| {
-                false
-                    // TODO: enable free variable checking
-                    || path == "unit/MergeHandlerFreeVar"
-            }),
             output_type: Some(FileType::UI),
             ..default_feature
         },
@@ -408,6 +389,29 @@ fn convert_abnf_to_pest() -> std::io::Result<()> {
     }}"
     )?;
 
+    // The grammar tries `a -> b` before `a : t`, so an expression without an arrow gets its
+    // operator expression parsed twice; nested expressions make that exponential. Parse it once
+    // and choose what follows instead. The alternatives that used to come between the two can't
+    // succeed where `a -> b` does, so moving them first accepts the same language.
+    rules.remove("expression");
+    rules.remove("annotated_expression");
+    writeln!(
+        &mut file,
+        r##"
+        expression = {{
+              lambda ~ whsp ~ "(" ~ whsp ~ nonreserved_label ~ whsp ~ ":" ~ whsp1 ~ expression ~ whsp ~ ")" ~ whsp ~ arrow ~ whsp ~ expression
+            | if_ ~ whsp1 ~ expression ~ whsp ~ then ~ whsp1 ~ expression ~ whsp ~ else_ ~ whsp1 ~ expression
+            | let_binding+ ~ in_ ~ whsp1 ~ expression
+            | forall ~ whsp ~ "(" ~ whsp ~ nonreserved_label ~ whsp ~ ":" ~ whsp1 ~ expression ~ whsp ~ ")" ~ whsp ~ arrow ~ whsp ~ expression
+            | merge ~ whsp1 ~ import_expression ~ whsp1 ~ import_expression ~ whsp ~ ":" ~ whsp1 ~ application_expression
+            | empty_list_literal
+            | toMap ~ whsp1 ~ import_expression ~ whsp ~ ":" ~ whsp1 ~ application_expression
+            | assert ~ whsp ~ ":" ~ whsp1 ~ expression
+            | operator_expression ~ (whsp ~ arrow ~ whsp ~ expression | whsp ~ ":" ~ whsp1 ~ expression)?
+        }}
+    "##
+    )?;
+
     // Setup grammar for precedence climbing
     rules.remove("operator_expression");
     writeln!(
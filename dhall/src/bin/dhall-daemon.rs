@@ -0,0 +1,43 @@
+//! Runs the evaluation server of `dhall::daemon`.
+//!
+//! Usage: `dhall-daemon` answers requests on standard input and output, and
+//! `dhall-daemon --socket <path>` listens on a unix socket instead.
+use std::process::exit;
+
+use dhall::daemon::Daemon;
+
+const USAGE: &str = "usage: dhall-daemon [--socket <path>]";
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut daemon = Daemon::new();
+    let result = match args.as_slice() {
+        [] => daemon.serve_stdio(),
+        [flag, path] if flag == "--socket" => serve_socket(&mut daemon, path),
+        [flag] if flag == "--help" || flag == "-h" => {
+            println!("{}", USAGE);
+            return;
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            exit(2);
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("dhall-daemon: {}", e);
+        exit(1);
+    }
+}
+
+#[cfg(unix)]
+fn serve_socket(daemon: &mut Daemon, path: &str) -> std::io::Result<()> {
+    daemon.serve_unix(std::path::Path::new(path))
+}
+
+#[cfg(not(unix))]
+fn serve_socket(_daemon: &mut Daemon, _path: &str) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Other,
+        "unix sockets are not supported on this platform",
+    ))
+}
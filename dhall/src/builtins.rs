@@ -1,9 +1,10 @@
-use std::collections::{BTreeMap, HashMap};
+use std::collections::BTreeMap;
 use std::convert::TryInto;
 
 use crate::operations::{BinOp, OpKind};
 use crate::semantics::{
-    skip_resolve_expr, typecheck, Hir, HirKind, Nir, NirKind, NzEnv, VarEnv,
+    check_fuel, skip_resolve_expr, typecheck, Hir, HirKind, Nir, NirKind,
+    NzEnv, Thunk, VarEnv,
 };
 use crate::syntax::Const::Type;
 use crate::syntax::{
@@ -106,6 +107,12 @@ impl BuiltinClosure {
         let args = self.args.iter().cloned().chain(once(a)).collect();
         apply_builtin(self.b, args, self.env.clone())
     }
+    pub fn builtin(&self) -> Builtin {
+        self.b
+    }
+    pub fn args(&self) -> &[Nir] {
+        &self.args
+    }
     pub fn to_hirkind(&self, venv: VarEnv) -> HirKind {
         HirKind::Expr(self.args.iter().fold(
             ExprKind::Builtin(self.b),
@@ -288,6 +295,12 @@ macro_rules! make_closure {
     (Natural) => {
         rc(ExprKind::Builtin(Builtin::Natural))
     };
+    (Bool) => {
+        rc(ExprKind::Builtin(Builtin::Bool))
+    };
+    (True) => {
+        rc(ExprKind::Num(NumKind::Bool(true)))
+    };
     (List $($ty:tt)*) => {{
         let ty = make_closure!($($ty)*);
         rc(ExprKind::Op(OpKind::App(
@@ -318,6 +331,69 @@ macro_rules! make_closure {
     }};
 }
 
+/// If `v` hasn't been evaluated yet and would evaluate to `build` applied to some function, returns
+/// that function. A `fold` can then use it directly instead of going through the intermediate
+/// value, since e.g. `List/fold A (List/build A f)` is `f`. Only the head of the expression gets
+/// evaluated along the way; the arguments stay lazy.
+fn as_unevaluated_build(v: &Nir, build: Builtin) -> Option<Nir> {
+    // Number of arguments `build` takes before the function.
+    let nargs = match build {
+        Builtin::NaturalBuild => 0,
+        _ => 1,
+    };
+    // What the head of an unevaluated expression reduces to.
+    enum Step {
+        Value(Nir),
+        App(Nir, Nir),
+    }
+    let step = |thunk: &Thunk| match thunk {
+        Thunk::Thunk { env, body } => {
+            let mut body = body;
+            while let HirKind::Import(hir, _) = body.kind() {
+                body = hir;
+            }
+            Some(match body.kind() {
+                HirKind::Var(var) => Step::Value(env.lookup_nir(*var)?),
+                HirKind::Expr(ExprKind::Annot(x, _)) => {
+                    Step::Value(x.eval(env))
+                }
+                HirKind::Expr(ExprKind::Let(_, _, val, body)) => {
+                    Step::Value(body.eval(env.insert_value(val.eval(env), ())))
+                }
+                HirKind::Expr(ExprKind::Op(OpKind::App(f, a))) => {
+                    Step::App(f.eval(env), a.eval(env))
+                }
+                _ => return None,
+            })
+        }
+        Thunk::PartialExpr {
+            expr: ExprKind::Op(OpKind::App(f, a)),
+            ..
+        } => Some(Step::App(f.clone(), a.clone())),
+        Thunk::PartialExpr { .. } => None,
+    };
+
+    let mut v = v.clone();
+    loop {
+        let (f, a) = match v.with_thunk(step)?? {
+            Step::Value(x) => {
+                v = x;
+                continue;
+            }
+            Step::App(f, a) => (f, a),
+        };
+        match f.kind() {
+            NirKind::AppliedBuiltin(closure)
+                if closure.b == build && closure.args.len() == nargs =>
+            {
+                return Some(a)
+            }
+            NirKind::LamClosure { closure, .. } => v = closure.apply(a),
+            _ => return None,
+        }
+    }
+}
+
 #[allow(clippy::cognitive_complexity)]
 fn apply_builtin(b: Builtin, args: Vec<Nir>, env: NzEnv) -> NirKind {
     use NirKind::*;
@@ -334,6 +410,17 @@ fn apply_builtin(b: Builtin, args: Vec<Nir>, env: NzEnv) -> NirKind {
             .unwrap()
             .eval(env.clone())
     };
+    // Whether `f`, given to a `build`, makes it reduce to a literal. `f` can only build its result
+    // out of the functions it is given, so it does exactly when `f Bool probe True` reduces to
+    // `True`, where `probe` ignores the elements and passes the accumulator on. Only then does a
+    // fold fused with the `build` give the same normal form as a fold over the literal.
+    let builds_literal = |f: &Nir, probe: Nir| {
+        let result = f
+            .app(Nir::from_builtin(Builtin::Bool))
+            .app(probe)
+            .app_to_kind(Num(Bool(true)).into_nir());
+        matches!(result, Num(Bool(true)))
+    };
 
     let ret = match (b, args.as_slice()) {
         (Builtin::Bool, [])
@@ -428,30 +515,24 @@ fn apply_builtin(b: Builtin, args: Vec<Nir>, env: NzEnv) -> NirKind {
         },
         (Builtin::ListHead, [_, l]) => match &*l.kind() {
             EmptyListLit(n) => Ret::NirKind(EmptyOptionalLit(n.clone())),
-            NEListLit(xs) => {
-                Ret::NirKind(NEOptionalLit(xs.iter().next().unwrap().clone()))
-            }
+            NEListLit(xs) => Ret::NirKind(NEOptionalLit(xs.head().clone())),
             _ => Ret::DoneAsIs,
         },
         (Builtin::ListLast, [_, l]) => match &*l.kind() {
             EmptyListLit(n) => Ret::NirKind(EmptyOptionalLit(n.clone())),
-            NEListLit(xs) => Ret::NirKind(NEOptionalLit(
-                xs.iter().rev().next().unwrap().clone(),
-            )),
+            NEListLit(xs) => Ret::NirKind(NEOptionalLit(xs.last().clone())),
             _ => Ret::DoneAsIs,
         },
         (Builtin::ListReverse, [_, l]) => match &*l.kind() {
             EmptyListLit(n) => Ret::NirKind(EmptyListLit(n.clone())),
-            NEListLit(xs) => {
-                Ret::NirKind(NEListLit(xs.iter().rev().cloned().collect()))
-            }
+            NEListLit(xs) => Ret::NirKind(NEListLit(xs.reverse())),
             _ => Ret::DoneAsIs,
         },
         (Builtin::ListIndexed, [t, l]) => {
             match l.kind() {
                 EmptyListLit(_) | NEListLit(_) => {
                     // Construct the returned record type: { index: Natural, value: t }
-                    let mut kts = HashMap::new();
+                    let mut kts = BTreeMap::new();
                     kts.insert(
                         "index".into(),
                         Nir::from_builtin(Builtin::Natural),
@@ -466,7 +547,7 @@ fn apply_builtin(b: Builtin, args: Vec<Nir>, env: NzEnv) -> NirKind {
                             xs.iter()
                                 .enumerate()
                                 .map(|(i, e)| {
-                                    let mut kvs = HashMap::new();
+                                    let mut kvs = BTreeMap::new();
                                     kvs.insert(
                                         "index".into(),
                                         Nir::from_kind(Num(Natural(i))),
@@ -499,17 +580,37 @@ fn apply_builtin(b: Builtin, args: Vec<Nir>, env: NzEnv) -> NirKind {
                     .app(EmptyListLit(t.clone()).into_nir()),
             )
         }
-        (Builtin::ListFold, [_, l, _, cons, nil]) => match &*l.kind() {
-            EmptyListLit(_) => Ret::Nir(nil.clone()),
-            NEListLit(xs) => {
-                let mut v = nil.clone();
-                for x in xs.iter().cloned().rev() {
-                    v = cons.app(x).app(v);
+        (Builtin::ListFold, [a, l, t, cons, nil]) => {
+            let probe = || {
+                make_closure(make_closure!(
+                    λ(T : Type) ->
+                    λ(x : var(T)) ->
+                    λ(acc : Bool) ->
+                    var(acc)
+                ))
+                .app(a.clone())
+            };
+            if let Some(f) = as_unevaluated_build(l, Builtin::ListBuild)
+                .filter(|f| builds_literal(f, probe()))
+            {
+                return f
+                    .app(t.clone())
+                    .app(cons.clone())
+                    .app_to_kind(nil.clone());
+            }
+            match &*l.kind() {
+                EmptyListLit(_) => Ret::Nir(nil.clone()),
+                NEListLit(xs) => {
+                    // The accumulator is lazy, so that a `cons` that ignores it stops the fold.
+                    let mut v = nil.clone();
+                    for x in xs.iter().cloned().rev() {
+                        v = cons.app(x).app_lazy(v);
+                    }
+                    Ret::Nir(v)
                 }
-                Ret::Nir(v)
+                _ => Ret::DoneAsIs,
             }
-            _ => Ret::DoneAsIs,
-        },
+        }
         (Builtin::OptionalBuild, [t, f]) => {
             let optional_t =
                 Nir::from_builtin(Builtin::Optional).app(t.clone());
@@ -526,11 +627,29 @@ fn apply_builtin(b: Builtin, args: Vec<Nir>, env: NzEnv) -> NirKind {
                     .app(EmptyOptionalLit(t.clone()).into_nir()),
             )
         }
-        (Builtin::OptionalFold, [_, v, _, just, nothing]) => match &*v.kind() {
-            EmptyOptionalLit(_) => Ret::Nir(nothing.clone()),
-            NEOptionalLit(x) => Ret::Nir(just.app(x.clone())),
-            _ => Ret::DoneAsIs,
-        },
+        (Builtin::OptionalFold, [a, v, t, just, nothing]) => {
+            let probe = || {
+                make_closure(make_closure!(
+                    λ(T : Type) ->
+                    λ(x : var(T)) ->
+                    True
+                ))
+                .app(a.clone())
+            };
+            if let Some(f) = as_unevaluated_build(v, Builtin::OptionalBuild)
+                .filter(|f| builds_literal(f, probe()))
+            {
+                return f
+                    .app(t.clone())
+                    .app(just.clone())
+                    .app_to_kind(nothing.clone());
+            }
+            match &*v.kind() {
+                EmptyOptionalLit(_) => Ret::Nir(nothing.clone()),
+                NEOptionalLit(x) => Ret::Nir(just.app(x.clone())),
+                _ => Ret::DoneAsIs,
+            }
+        }
         (Builtin::NaturalBuild, [f]) => Ret::Nir(
             f.app(Nir::from_builtin(Builtin::Natural))
                 .app(make_closure(make_closure!(
@@ -540,18 +659,42 @@ fn apply_builtin(b: Builtin, args: Vec<Nir>, env: NzEnv) -> NirKind {
                 .app(Num(Natural(0)).into_nir()),
         ),
 
-        (Builtin::NaturalFold, [n, t, succ, zero]) => match &*n.kind() {
-            Num(Natural(0)) => Ret::Nir(zero.clone()),
-            Num(Natural(n)) => {
-                let fold = Nir::from_builtin(Builtin::NaturalFold)
-                    .app(Num(Natural(n - 1)).into_nir())
+        (Builtin::NaturalFold, [n, t, succ, zero]) => {
+            let probe = || {
+                make_closure(make_closure!(
+                    λ(acc : Bool) ->
+                    var(acc)
+                ))
+            };
+            if let Some(f) = as_unevaluated_build(n, Builtin::NaturalBuild)
+                .filter(|f| builds_literal(f, probe()))
+            {
+                return f
                     .app(t.clone())
                     .app(succ.clone())
-                    .app(zero.clone());
-                Ret::Nir(succ.app(fold))
+                    .app_to_kind(zero.clone());
             }
-            _ => Ret::DoneAsIs,
-        },
+            match &*n.kind() {
+                Num(Natural(n)) => {
+                    // Apply `succ` from the inside out, and stop as soon as the accumulator
+                    // stops changing: the remaining iterations would leave it as is. In
+                    // particular this stops right away when `succ` ignores its argument.
+                    let mut v = zero.clone();
+                    for _ in 0..*n {
+                        if check_fuel().is_err() {
+                            return Aborted;
+                        }
+                        let next = succ.app(v.clone());
+                        if next == v {
+                            break;
+                        }
+                        v = next;
+                    }
+                    Ret::Nir(v)
+                }
+                _ => Ret::DoneAsIs,
+            }
+        }
         _ => Ret::DoneAsIs,
     };
     match ret {
@@ -0,0 +1,196 @@
+//! Typechecking and evaluation of expressions that refer to variables provided by the host
+//! application.
+use crate::error::{Error, ErrorBuilder};
+use crate::semantics::resolve::resolve_with_names;
+use crate::semantics::{
+    mkerr, ForeignBuiltin, ForeignClosure, Hir, HirKind, ImportEnv, Nir, TyEnv,
+};
+use crate::shared::Rc;
+use crate::syntax::{Expr, ExprKind, Label, Span};
+use crate::{Normalized, Parsed, Resolved};
+
+/// A typing context: variables that an expression may refer to without binding them, along with
+/// their types or values. This allows checking a fragment that uses values provided at runtime,
+/// or injecting those values, without splicing them into its source.
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> Result<(), dhall::error::Error> {
+/// use dhall::context::Context;
+/// use dhall::Parsed;
+///
+/// let ty = |src| -> Result<_, dhall::error::Error> {
+///     Ok(Parsed::parse_str(src)?.resolve()?.typecheck()?.normalize())
+/// };
+/// let ctx = Context::new()
+///     .insert("host", ty("Text")?)
+///     .insert("port", ty("Natural")?);
+///
+/// let fragment = Parsed::parse_str(r#"{ url = "${host}:${Natural/show port}" }"#)?;
+/// assert_eq!(ctx.typecheck(fragment)?.to_string(), "{ url : Text }");
+///
+/// let fragment = Parsed::parse_str(r#"port ++ "/""#)?;
+/// assert!(ctx.typecheck(fragment).is_err());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Context {
+    bindings: Vec<(Label, Binding)>,
+}
+
+#[derive(Debug, Clone)]
+enum Binding {
+    /// A variable of which only the type is known.
+    Type(Nir),
+    /// A variable bound to a value.
+    Value(Nir),
+    /// A variable bound to a function implemented by the host application.
+    Foreign(ForeignBuiltin),
+}
+
+impl Context {
+    pub fn new() -> Self {
+        Context::default()
+    }
+
+    /// Adds a variable of the given type, which must be a closed type like `Natural` or
+    /// `List { name : Text }`. Later variables shadow earlier ones with the same name, like
+    /// nested `let`s; the shadowed ones can be accessed with `x@1`.
+    pub fn insert(mut self, name: &str, ty: Normalized) -> Self {
+        self.bindings.push((name.into(), Binding::Type(ty.0)));
+        self
+    }
+
+    /// Binds a variable to the given value, e.g. a parameter like the deployment region chosen by
+    /// the application. Expressions that only use variables with values can be evaluated with
+    /// `resolve`.
+    pub fn define(mut self, name: &str, value: Normalized) -> Self {
+        self.bindings.push((name.into(), Binding::Value(value.0)));
+        self
+    }
+
+    /// Binds a variable to a function implemented in Rust, e.g. to look up secrets or query the
+    /// environment without going through imports. The function gets called during normalization
+    /// once all the arguments given by `ty` are there and have no free variables. If it returns
+    /// `None`, or can't be called yet, the application is left as is and printed using `name`.
+    /// The function is trusted to return values of the declared type.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), dhall::error::Error> {
+    /// use dhall::context::Context;
+    /// use dhall::Parsed;
+    ///
+    /// let eval = |src: &str| -> Result<_, dhall::error::Error> {
+    ///     Ok(Parsed::parse_str(src)?.resolve()?.typecheck()?.normalize())
+    /// };
+    /// let ctx = Context::new().builtin("lookupSecret", eval("Text → Text")?, move |args| {
+    ///     match args[0].to_string().as_str() {
+    ///         r#""db""# => eval(r#""hunter2""#).ok(),
+    ///         _ => None,
+    ///     }
+    /// })?;
+    ///
+    /// let config = Parsed::parse_str(r#"{ password = lookupSecret "db" }"#)?;
+    /// assert_eq!(ctx.typecheck(config)?.to_string(), "{ password : Text }");
+    /// let config = Parsed::parse_str(r#"{ password = lookupSecret "db" }"#)?;
+    /// let config = ctx.resolve(config)?.typecheck()?.normalize();
+    /// assert_eq!(config.to_string(), r#"{ password = "hunter2" }"#);
+    ///
+    /// let f = Parsed::parse_str(r#"λ(x : Text) → lookupSecret x"#)?;
+    /// let f = ctx.resolve(f)?.typecheck()?.normalize();
+    /// assert_eq!(f.to_string(), "λ(x : Text) → lookupSecret x");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn builtin<F>(
+        mut self,
+        name: &str,
+        ty: Normalized,
+        f: F,
+    ) -> Result<Self, Error>
+    where
+        F: Fn(&[Normalized]) -> Option<Normalized> + Send + Sync + 'static,
+    {
+        let env = TyEnv::new();
+        let ty = ty.0.to_hir_noenv().typecheck(&env)?.eval_to_type(&env)?;
+        let foreign = ForeignBuiltin::new(name.into(), ty, Rc::new(f));
+        self.bindings.push((name.into(), Binding::Foreign(foreign)));
+        Ok(self)
+    }
+
+    /// Resolves the imports of `expr` and typechecks it with the variables of this context in
+    /// scope. Returns the inferred type, which may itself refer to the variables.
+    pub fn typecheck(&self, expr: Parsed) -> Result<Expr, Error> {
+        let mut env = TyEnv::new();
+        for (name, binding) in &self.bindings {
+            env = match binding {
+                Binding::Type(ty) => {
+                    let ty_hir = ty.to_hir_noenv();
+                    let ty = ty_hir.typecheck(&env)?.eval_to_type(&env)?;
+                    env.insert_type(name, ty)
+                }
+                Binding::Value(val) => {
+                    let val_hir = val.to_hir_noenv();
+                    let ty = val_hir.typecheck(&env)?.ty().clone();
+                    env.insert_value(name, val.clone(), ty)
+                }
+                Binding::Foreign(f) => {
+                    let val = Nir::from_kind(ForeignClosure::new(f.clone()));
+                    env.insert_value(name, val, f.ty().clone())
+                }
+            };
+        }
+        let resolved = self.resolve_open(expr)?;
+        let tir = resolved.0.typecheck(&env)?;
+        Ok(tir.ty().to_expr_tyenv(&env))
+    }
+
+    /// Resolves the imports of `expr` and substitutes the variables of this context with their
+    /// values. The result is a closed expression, which can be typechecked and normalized as
+    /// usual. Fails if the context has variables without a value.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), dhall::error::Error> {
+    /// use dhall::context::Context;
+    /// use dhall::Parsed;
+    ///
+    /// let region = Parsed::parse_str(r#""eu-west-1""#)?.resolve()?.typecheck()?.normalize();
+    /// let ctx = Context::new().define("region", region);
+    /// let config = Parsed::parse_str(r#"{ bucket = "logs-${region}" }"#)?;
+    /// let config = ctx.resolve(config)?.typecheck()?.normalize();
+    /// assert_eq!(config.to_string(), r#"{ bucket = "logs-eu-west-1" }"#);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn resolve(&self, expr: Parsed) -> Result<Resolved, Error> {
+        let mut hir = self.resolve_open(expr)?.0;
+        for (name, binding) in self.bindings.iter().rev() {
+            let val = match binding {
+                Binding::Value(val) => val.to_hir_noenv(),
+                Binding::Foreign(f) => {
+                    Hir::new(HirKind::Foreign(f.clone()), Span::Artificial)
+                }
+                Binding::Type(_) => mkerr(
+                    ErrorBuilder::new(format!(
+                        "the variable `{}` has no value",
+                        name
+                    ))
+                    .help("use `Context::define` to give it one")
+                    .format(),
+                )?,
+            };
+            let kind = ExprKind::Let(name.clone(), None, val, hir);
+            hir = Hir::new(HirKind::Expr(kind), Span::Artificial);
+        }
+        Ok(Resolved(hir))
+    }
+
+    /// Resolves `expr` with the variables of this context in scope, leaving them free.
+    fn resolve_open(&self, expr: Parsed) -> Result<Resolved, Error> {
+        let names: Vec<Label> =
+            self.bindings.iter().map(|(name, _)| name.clone()).collect();
+        resolve_with_names(&mut ImportEnv::new(), expr, &names)
+    }
+}
@@ -0,0 +1,101 @@
+use crate::builtins::Builtin;
+use crate::convert::{flatten_record, FlattenOptions};
+use crate::operations::OpKind;
+use crate::syntax::{Expr, ExprKind, InterpolatedTextContents, NumKind};
+
+/// Renders a record of scalars as `KEY=value` lines that can be sourced by a shell or read by
+/// dotenv-style tools.
+///
+/// Nested records are flattened, joining field names with `_`. Fields must hold a `Bool`,
+/// `Natural`, `Integer`, `Double` or `Text`, or an `Optional` of one of those; absent optional
+/// values are omitted. Text is single-quoted when it contains characters that the shell would
+/// interpret. This expects a normalized expression, and returns a description of the problem if
+/// the expression cannot be rendered, e.g. because two fields like `a_b` and `a.b` would set the
+/// same variable.
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> Result<(), dhall::error::Error> {
+/// use dhall::convert::to_env;
+///
+/// let expr = dhall::Parsed::parse_str(
+///     r#"{ HOME = "/home/me", db = { port = 5432, name = "my db" } }"#,
+/// )?
+/// .resolve()?
+/// .typecheck()?
+/// .normalize()
+/// .to_expr();
+/// assert_eq!(
+///     to_env(&expr).unwrap(),
+///     "HOME=/home/me\ndb_name='my db'\ndb_port=5432\n"
+/// );
+/// # Ok(())
+/// # }
+/// ```
+pub fn to_env(expr: &Expr) -> Result<String, String> {
+    let fields = flatten_record(expr, &FlattenOptions::new().separator("_"))?;
+    let mut out = String::new();
+    for (key, value) in fields {
+        if !is_valid_name(&key) {
+            return Err(format!("`{}` is not a valid variable name", key));
+        }
+        let rendered = match value.kind() {
+            ExprKind::SomeLit(x) => Some(scalar_to_string(x)),
+            ExprKind::Op(OpKind::App(f, _))
+                if f.kind() == &ExprKind::Builtin(Builtin::OptionalNone) =>
+            {
+                None
+            }
+            _ => Some(scalar_to_string(&value)),
+        };
+        if let Some(rendered) = rendered {
+            let rendered = rendered.ok_or_else(|| {
+                format!(
+                    "field `{}` is not a scalar and cannot be exported: `{}`",
+                    key, value
+                )
+            })?;
+            out.push_str(&key);
+            out.push('=');
+            out.push_str(&shell_quote(&rendered));
+            out.push('\n');
+        }
+    }
+    Ok(out)
+}
+
+fn is_valid_name(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn scalar_to_string(expr: &Expr) -> Option<String> {
+    Some(match expr.kind() {
+        ExprKind::Num(NumKind::Bool(b)) => b.to_string(),
+        ExprKind::Num(NumKind::Natural(n)) => n.to_string(),
+        ExprKind::Num(NumKind::Integer(n)) => n.to_string(),
+        ExprKind::Num(NumKind::Double(n)) => n.to_string(),
+        ExprKind::TextLit(t) => match t.iter().collect::<Vec<_>>().as_slice() {
+            [] => String::new(),
+            [InterpolatedTextContents::Text(s)] => s.clone(),
+            _ => return None,
+        },
+        _ => return None,
+    })
+}
+
+/// Quotes a string for a POSIX shell, leaving it as-is when that's safe.
+fn shell_quote(s: &str) -> String {
+    let is_safe =
+        |c: char| c.is_ascii_alphanumeric() || "_-./:@%+,=".contains(c);
+    if !s.is_empty() && s.chars().all(is_safe) {
+        s.to_owned()
+    } else {
+        format!("'{}'", s.replace('\'', r"'\''"))
+    }
+}
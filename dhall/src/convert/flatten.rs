@@ -0,0 +1,254 @@
+use std::collections::BTreeMap;
+
+use crate::syntax::{Expr, ExprKind, Label, Span};
+
+/// Controls how [`flatten_record`] and [`unflatten_record`] map between nested records and flat
+/// keys.
+///
+/// [`flatten_record`]: fn.flatten_record.html
+/// [`unflatten_record`]: fn.unflatten_record.html
+#[derive(Debug, Clone)]
+pub struct FlattenOptions {
+    separator: String,
+    index_lists: bool,
+}
+
+impl Default for FlattenOptions {
+    fn default() -> Self {
+        FlattenOptions {
+            separator: ".".to_owned(),
+            index_lists: false,
+        }
+    }
+}
+
+impl FlattenOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the string that joins field names into keys. Defaults to `"."`.
+    pub fn separator(mut self, separator: &str) -> Self {
+        self.separator = separator.to_owned();
+        self
+    }
+
+    /// Whether to flatten the elements of non-empty lists too, with their index as the last part
+    /// of the key, e.g. `{ a = [ 1, 2 ] }` becomes `{ "a.0": 1, "a.1": 2 }`. Defaults to false,
+    /// which keeps lists as values.
+    pub fn index_lists(mut self, index_lists: bool) -> Self {
+        self.index_lists = index_lists;
+        self
+    }
+
+    fn join(&self, prefix: &str, component: &str) -> String {
+        if prefix.is_empty() {
+            component.to_owned()
+        } else {
+            format!("{}{}{}", prefix, self.separator, component)
+        }
+    }
+}
+
+/// Describes the path to a field for error messages, e.g. `a.b` or `` `a.b` ``.
+fn describe_path(path: &[String]) -> String {
+    let is_simple = |s: &str| {
+        !s.is_empty()
+            && s.chars()
+                .all(|c| c.is_ascii_alphanumeric() || "_-/".contains(c))
+    };
+    path.iter()
+        .map(|s| {
+            if is_simple(s) {
+                s.clone()
+            } else {
+                format!("`{}`", s)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Flattens nested record literals into a map from paths to leaf values, e.g. with `"."` as a
+/// separator, `{ a = { b = 1, c = 2 }, d = 3 }` becomes `{ "a.b": 1, "a.c": 2, "d": 3 }`.
+///
+/// Values other than record literals are kept as-is, except for lists when
+/// [`FlattenOptions::index_lists`] is set. Empty records and lists are kept as leaves so that the
+/// result can be turned back into the original record with [`unflatten_record`]. This expects a
+/// normalized expression, and fails if the expression is not a record literal, or if two fields
+/// end up with the same key, like `` `a.b` `` and `a.b` with `"."` as a separator.
+///
+/// [`FlattenOptions::index_lists`]: struct.FlattenOptions.html#method.index_lists
+/// [`unflatten_record`]: fn.unflatten_record.html
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> Result<(), dhall::error::Error> {
+/// use dhall::convert::{flatten_record, unflatten_record, FlattenOptions};
+///
+/// let expr = dhall::Parsed::parse_str("{ a = { b = 1, c = [ True ] } }")?
+///     .resolve()?
+///     .typecheck()?
+///     .normalize()
+///     .to_expr();
+/// let options = FlattenOptions::new().separator("_").index_lists(true);
+/// let flat = flatten_record(&expr, &options).unwrap();
+/// let keys: Vec<_> = flat.keys().cloned().collect();
+/// assert_eq!(keys, vec!["a_b", "a_c_0"]);
+/// assert_eq!(unflatten_record(&flat, &options), Ok(expr));
+/// # Ok(())
+/// # }
+/// ```
+pub fn flatten_record(
+    expr: &Expr,
+    options: &FlattenOptions,
+) -> Result<BTreeMap<String, Expr>, String> {
+    struct Flattener<'a> {
+        options: &'a FlattenOptions,
+        out: BTreeMap<String, Expr>,
+        // Where each key came from, to report collisions.
+        sources: BTreeMap<String, Vec<String>>,
+    }
+
+    impl Flattener<'_> {
+        fn go(
+            &mut self,
+            key: &str,
+            path: &mut Vec<String>,
+            expr: &Expr,
+        ) -> Result<(), String> {
+            let mut child = |this: &mut Self, component: String, v: &Expr| {
+                let key = this.options.join(key, &component);
+                path.push(component);
+                let result = this.go(&key, path, v);
+                path.pop();
+                result
+            };
+            match expr.kind() {
+                ExprKind::RecordLit(kvs) if !kvs.is_empty() => {
+                    for (k, v) in kvs {
+                        child(self, String::from(k), v)?;
+                    }
+                }
+                ExprKind::NEListLit(xs) if self.options.index_lists => {
+                    for (i, x) in xs.iter().enumerate() {
+                        child(self, i.to_string(), x)?;
+                    }
+                }
+                _ => {
+                    if let Some(other) = self.sources.get(key) {
+                        return Err(format!(
+                            "fields {} and {} both flatten to the key `{}`",
+                            describe_path(other),
+                            describe_path(path),
+                            key
+                        ));
+                    }
+                    self.sources.insert(key.to_owned(), path.clone());
+                    self.out.insert(key.to_owned(), expr.clone());
+                }
+            }
+            Ok(())
+        }
+    }
+
+    match expr.kind() {
+        ExprKind::RecordLit(kvs) => {
+            let mut flattener = Flattener {
+                options,
+                out: BTreeMap::new(),
+                sources: BTreeMap::new(),
+            };
+            if !kvs.is_empty() {
+                flattener.go("", &mut Vec::new(), expr)?;
+            }
+            Ok(flattener.out)
+        }
+        _ => Err(format!("expected a record, found `{}`", expr)),
+    }
+}
+
+/// Rebuilds nested record literals from a map of paths to values, as produced by
+/// [`flatten_record`] with the same options. With [`FlattenOptions::index_lists`], records whose
+/// fields are exactly `0` to `n - 1` are rebuilt as lists.
+///
+/// If a key is both a value and the prefix of another key (e.g. `a` and `a.b`), this returns that
+/// key as an error.
+///
+/// [`flatten_record`]: fn.flatten_record.html
+/// [`FlattenOptions::index_lists`]: struct.FlattenOptions.html#method.index_lists
+pub fn unflatten_record(
+    map: &BTreeMap<String, Expr>,
+    options: &FlattenOptions,
+) -> Result<Expr, String> {
+    enum Node {
+        Leaf(Expr),
+        Record(BTreeMap<Label, Node>),
+    }
+
+    // The top-level record stays a record, whatever its fields.
+    fn into_expr(node: Node, index_lists: bool, top_level: bool) -> Expr {
+        let kvs = match node {
+            Node::Leaf(e) => return e,
+            Node::Record(kvs) => kvs,
+        };
+        let is_list = index_lists
+            && !top_level
+            && kvs
+                .keys()
+                .map(|k| {
+                    let k = String::from(k);
+                    k.parse::<usize>().ok().filter(|i| i.to_string() == k)
+                })
+                .collect::<Option<Vec<_>>>()
+                .is_some_and(|mut indices| {
+                    indices.sort_unstable();
+                    indices.into_iter().eq(0..kvs.len())
+                });
+        let kind = if is_list {
+            let mut elements: Vec<_> = kvs
+                .into_iter()
+                .map(|(k, v)| (String::from(&k).parse::<usize>().unwrap(), v))
+                .collect();
+            elements.sort_by_key(|(i, _)| *i);
+            ExprKind::NEListLit(
+                elements
+                    .into_iter()
+                    .map(|(_, v)| into_expr(v, index_lists, false))
+                    .collect(),
+            )
+        } else {
+            ExprKind::RecordLit(
+                kvs.into_iter()
+                    .map(|(k, v)| (k, into_expr(v, index_lists, false)))
+                    .collect(),
+            )
+        };
+        Expr::new(kind, Span::Artificial)
+    }
+
+    let mut root = BTreeMap::new();
+    for (key, value) in map {
+        let mut components = key.split(&options.separator).peekable();
+        let mut node = &mut root;
+        while let Some(component) = components.next() {
+            let label = Label::from(component);
+            if components.peek().is_none() {
+                if node.contains_key(&label) {
+                    return Err(key.clone());
+                }
+                node.insert(label, Node::Leaf(value.clone()));
+                break;
+            }
+            let child = node
+                .entry(label)
+                .or_insert_with(|| Node::Record(BTreeMap::new()));
+            node = match child {
+                Node::Record(kvs) => kvs,
+                Node::Leaf(_) => return Err(key.clone()),
+            };
+        }
+    }
+    Ok(into_expr(Node::Record(root), options.index_lists, true))
+}
@@ -0,0 +1,5 @@
+//! Conversions between Dhall values and other configuration formats.
+mod env;
+mod flatten;
+pub use env::to_env;
+pub use flatten::{flatten_record, unflatten_record, FlattenOptions};
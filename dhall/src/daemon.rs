@@ -0,0 +1,157 @@
+//! A long-running evaluation server, that keeps resolved imports in memory between requests to
+//! avoid paying the startup and import resolution costs on each invocation.
+//!
+//! The protocol is line-based: each request is a JSON object on its own line, and gets a single
+//! line of JSON in response. A request has the form
+//! `{"id": 1, "command": "eval", "expr": "1 + 1"}`, where `command` is one of `eval`, `type`,
+//! `typecheck` or `reset`, and the expression is given either as source text (`expr`) or as a
+//! file to read (`file`). The `id` is optional and is echoed back in the response, which is either
+//! `{"id": 1, "ok": true, "result": "2"}` or `{"id": 1, "ok": false, "error": "..."}`.
+//!
+//! Only remote imports stay in memory between requests. Local files and environment variables
+//! are read again by each request, so that edits are picked up; imports protected by a hash are
+//! then found in the on-disk cache instead. Remote files are fetched once, until a `reset`
+//! request empties the cache.
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+use serde_json::{json, Map, Value};
+
+use crate::semantics::ImportEnv;
+use crate::{Parsed, Typed};
+
+/// Evaluation server state: the remote imports resolved so far.
+#[derive(Debug)]
+pub struct Daemon {
+    env: ImportEnv,
+}
+
+impl Default for Daemon {
+    fn default() -> Self {
+        Daemon::new()
+    }
+}
+
+impl Daemon {
+    pub fn new() -> Self {
+        Daemon {
+            env: ImportEnv::new(),
+        }
+    }
+
+    fn typecheck(
+        &mut self,
+        request: &Map<String, Value>,
+    ) -> Result<Typed, String> {
+        let parsed = match (request.get("expr"), request.get("file")) {
+            (Some(Value::String(expr)), None) => Parsed::parse_str(expr),
+            (None, Some(Value::String(file))) => {
+                Parsed::parse_file(Path::new(file))
+            }
+            _ => {
+                return Err("expected exactly one of `expr` or `file` \
+                            as a string"
+                    .to_owned())
+            }
+        };
+        let resolved = match parsed {
+            Ok(parsed) => parsed.resolve_with(&mut self.env),
+            Err(e) => Err(e),
+        };
+        self.env.clear_local_imports();
+        let resolved = resolved.map_err(|e| e.to_string())?;
+        resolved.typecheck().map_err(|e| e.to_string())
+    }
+
+    fn run(&mut self, request: &Map<String, Value>) -> Result<Value, String> {
+        match request.get("command").and_then(Value::as_str) {
+            Some("eval") => {
+                let typed = self.typecheck(request)?;
+                Ok(Value::String(typed.normalize().to_string()))
+            }
+            Some("type") => {
+                let typed = self.typecheck(request)?;
+                let ty = typed.get_type().map_err(|e| e.to_string())?;
+                Ok(Value::String(ty.to_string()))
+            }
+            Some("typecheck") => {
+                self.typecheck(request)?;
+                Ok(Value::Null)
+            }
+            Some("reset") => {
+                self.env.clear_cache();
+                Ok(Value::Null)
+            }
+            Some(cmd) => Err(format!("unknown command `{}`", cmd)),
+            None => Err("missing `command`".to_owned()),
+        }
+    }
+
+    /// Answers a single request, given as a line of JSON.
+    ///
+    /// ```
+    /// let mut daemon = dhall::daemon::Daemon::new();
+    /// let response = daemon.handle(r#"{"id": 1, "command": "eval", "expr": "1 + 1"}"#);
+    /// assert_eq!(response, r#"{"id":1,"ok":true,"result":"2"}"#);
+    /// ```
+    pub fn handle(&mut self, line: &str) -> String {
+        let (id, result) = match serde_json::from_str(line) {
+            Ok(Value::Object(request)) => {
+                let id = request.get("id").cloned().unwrap_or(Value::Null);
+                (id, self.run(&request))
+            }
+            Ok(_) => (Value::Null, Err("expected a JSON object".to_owned())),
+            Err(e) => (Value::Null, Err(format!("invalid JSON: {}", e))),
+        };
+        let response = match result {
+            Ok(result) => json!({ "id": id, "ok": true, "result": result }),
+            Err(error) => json!({ "id": id, "ok": false, "error": error }),
+        };
+        response.to_string()
+    }
+
+    /// Answers requests read line by line from `input` until it is exhausted. Empty lines are
+    /// ignored.
+    pub fn serve(
+        &mut self,
+        input: impl BufRead,
+        mut output: impl Write,
+    ) -> io::Result<()> {
+        for line in input.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            writeln!(output, "{}", self.handle(&line))?;
+            output.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Answers requests on standard input.
+    pub fn serve_stdio(&mut self) -> io::Result<()> {
+        let stdin = io::stdin();
+        let stdout = io::stdout();
+        self.serve(stdin.lock(), stdout.lock())
+    }
+
+    /// Listens on a unix socket at the given path, answering the requests of each connection in
+    /// turn. The cache is shared across connections. Failing to accept a connection stops the
+    /// server; errors while talking to a client are reported on standard error, and the server
+    /// moves on to the next client.
+    #[cfg(unix)]
+    pub fn serve_unix(&mut self, path: &Path) -> io::Result<()> {
+        use std::os::unix::net::UnixListener;
+        let listener = UnixListener::bind(path)?;
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let result = stream.try_clone().and_then(|reader| {
+                self.serve(io::BufReader::new(reader), stream)
+            });
+            if let Err(e) = result {
+                eprintln!("dhall daemon: connection error: {}", e);
+            }
+        }
+        Ok(())
+    }
+}
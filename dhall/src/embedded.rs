@@ -0,0 +1,160 @@
+//! Checking of Dhall code embedded in other files, e.g. the examples in a Markdown document.
+use std::fmt;
+use std::path::Path;
+
+use crate::error::{annotations, Error, SourcePosition};
+use crate::semantics::ImportLocation;
+use crate::syntax::parse_expr;
+use crate::Parsed;
+
+/// How to find the Dhall code in a file.
+#[derive(Debug, Clone, Copy)]
+pub enum Delimiters<'a> {
+    /// Markdown code blocks fenced with ```` ```dhall ```` or `~~~dhall`.
+    Markdown,
+    /// Blocks starting after a line consisting of `open` and ending before a line consisting of
+    /// `close`, ignoring surrounding whitespace.
+    Lines { open: &'a str, close: &'a str },
+}
+
+/// A block of Dhall code found in a host file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub source: String,
+    /// The line of the host file where the code starts, 1-based.
+    pub line: usize,
+    /// The number of columns of indentation that were removed from each line.
+    pub indent: usize,
+}
+
+/// An error in an embedded block, with its position in the host file.
+#[derive(Debug)]
+pub struct EmbeddedError {
+    /// The position of the error, or of the start of the block if the error has no position.
+    pub position: SourcePosition,
+    pub error: Error,
+}
+
+impl fmt::Display for EmbeddedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (title, _) = annotations::summarize(&self.error);
+        write!(
+            f,
+            "{}:{}: {}",
+            self.position.line, self.position.column, title
+        )
+    }
+}
+
+/// Finds the blocks of Dhall code in the given text.
+pub fn extract_blocks(text: &str, delimiters: Delimiters) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    // The closing delimiter and indentation of the block we're in, if any.
+    let mut current: Option<(String, usize, Block)> = None;
+    for (i, line) in text.lines().enumerate() {
+        let trimmed = line.trim();
+        match &mut current {
+            Some((close, _, _)) if trimmed == close => {
+                let (_, _, block) = current.take().unwrap();
+                blocks.push(block);
+            }
+            Some((_, indent, block)) => {
+                let strip = line
+                    .char_indices()
+                    .take_while(|(i, c)| *i < *indent && *c == ' ')
+                    .count();
+                block.source.push_str(&line[strip..]);
+                block.source.push('\n');
+            }
+            None => {
+                let close = match delimiters {
+                    Delimiters::Markdown => {
+                        let fence = if trimmed.starts_with("```") {
+                            "```"
+                        } else if trimmed.starts_with("~~~") {
+                            "~~~"
+                        } else {
+                            continue;
+                        };
+                        let info = trimmed.trim_start_matches(&fence[..1]);
+                        if info.split_whitespace().next() != Some("dhall") {
+                            continue;
+                        }
+                        let len = trimmed.len() - info.len();
+                        fence[..1].repeat(len)
+                    }
+                    Delimiters::Lines { open, close } => {
+                        if trimmed != open.trim() {
+                            continue;
+                        }
+                        close.trim().to_owned()
+                    }
+                };
+                let indent = line.len() - line.trim_start_matches(' ').len();
+                let block = Block {
+                    source: String::new(),
+                    line: i + 2,
+                    indent,
+                };
+                current = Some((close, indent, block));
+            }
+        }
+    }
+    // An unterminated block extends to the end of the file.
+    if let Some((_, _, block)) = current {
+        blocks.push(block);
+    }
+    blocks
+}
+
+fn check_block(block: &Block, location: &ImportLocation) -> Result<(), Error> {
+    let expr = parse_expr(&block.source)?;
+    Parsed(expr, location.clone()).resolve()?.typecheck()?;
+    Ok(())
+}
+
+/// Parses, resolves and typechecks each block of Dhall code in the given text, and returns the
+/// errors found. Imports are resolved relative to `path`, the location of the host file.
+///
+/// # Example
+///
+/// ```
+/// use dhall::embedded::{check_embedded, Delimiters};
+///
+/// let doc = "# Example\n\n```dhall\n1 + 1\n```\n\n```dhall\n1 + True\n```\n";
+/// let errors = check_embedded(doc, Delimiters::Markdown, None);
+/// assert_eq!(errors.len(), 1);
+/// assert_eq!(errors[0].position.line, 8);
+/// ```
+pub fn check_embedded(
+    text: &str,
+    delimiters: Delimiters,
+    path: Option<&Path>,
+) -> Vec<EmbeddedError> {
+    let location = match path {
+        Some(path) => ImportLocation::Local(path.to_owned()),
+        None => ImportLocation::Missing,
+    };
+    extract_blocks(text, delimiters)
+        .iter()
+        .filter_map(|block| {
+            let error = check_block(block, &location).err()?;
+            let (_, position) = annotations::summarize(&error);
+            let position = match position {
+                Some(pos) => SourcePosition {
+                    line: pos.line + block.line - 1,
+                    column: pos.column + block.indent,
+                    end_line: pos.end_line + block.line - 1,
+                    end_column: pos.end_column + block.indent,
+                },
+                None => SourcePosition {
+                    line: block.line,
+                    column: 1,
+                    end_line: block.line,
+                    end_column: 1,
+                },
+            };
+            Some(EmbeddedError { position, error })
+        })
+        .collect()
+}
@@ -0,0 +1,88 @@
+//! Rendering of errors in formats understood by CI systems, so that they can be displayed inline
+//! on the offending source.
+use serde_json::{json, Value};
+
+use crate::error::{Error, ErrorKind, EvalError, SourcePosition};
+
+/// Extracts a one-line summary of the error and its location in the source, if known.
+pub(crate) fn summarize(err: &Error) -> (String, Option<SourcePosition>) {
+    match err.kind() {
+        ErrorKind::Typecheck(err) => {
+            let diagnostic = err.diagnostic();
+            (diagnostic.title().to_owned(), diagnostic.position())
+        }
+        ErrorKind::Eval(EvalError::BudgetExceeded(err)) => {
+            let diagnostic = err.diagnostic();
+            (diagnostic.title().to_owned(), diagnostic.position())
+        }
+        ErrorKind::Parse(err) => {
+            let diagnostic = err.diagnostic();
+            (
+                format!("parse error: {}", diagnostic.title()),
+                diagnostic.position(),
+            )
+        }
+        _ => {
+            let formatted = err.to_string();
+            let title = formatted.lines().next().unwrap_or_default();
+            (title.to_owned(), None)
+        }
+    }
+}
+
+/// Formats an error as a GitHub Actions workflow command, e.g.
+/// `::error file=config.dhall,line=3,col=5,endLine=3,endColumn=6::unbound variable`.
+pub fn github_annotation(err: &Error, path: &str) -> String {
+    fn escape_data(s: &str) -> String {
+        s.replace('%', "%25")
+            .replace('\r', "%0D")
+            .replace('\n', "%0A")
+    }
+    fn escape_property(s: &str) -> String {
+        escape_data(s).replace(':', "%3A").replace(',', "%2C")
+    }
+
+    let (title, position) = summarize(err);
+    let mut properties = format!("file={}", escape_property(path));
+    if let Some(pos) = position {
+        properties += &format!(
+            ",line={},col={},endLine={},endColumn={}",
+            pos.line, pos.column, pos.end_line, pos.end_column
+        );
+    }
+    format!("::error {}::{}", properties, escape_data(&title))
+}
+
+/// Formats errors as a GitLab code quality report, i.e. a JSON array of issues. Each error is
+/// paired with the path of the file it comes from.
+///
+/// ```
+/// use dhall::error::annotations::gitlab_code_quality;
+///
+/// let err = dhall::Parsed::parse_str("{ a = }").unwrap_err();
+/// let report = gitlab_code_quality(&[("configs/\u{1}a.dhall", &err)]);
+/// let issues: serde_json::Value = serde_json::from_str(&report).unwrap();
+/// assert_eq!(issues[0]["location"]["path"], "configs/\u{1}a.dhall");
+/// assert_eq!(issues[0]["location"]["lines"]["begin"], 1);
+/// ```
+pub fn gitlab_code_quality(errors: &[(&str, &Error)]) -> String {
+    let issues: Vec<Value> = errors
+        .iter()
+        .map(|(path, err)| {
+            let (title, position) = summarize(err);
+            let line = position.map(|pos| pos.line).unwrap_or(1);
+            let fingerprint = {
+                let key = format!("{}:{}:{}", path, line, title);
+                hex::encode(crate::hash::sha256(key.as_bytes()))
+            };
+            json!({
+                "description": title,
+                "check_name": "dhall",
+                "fingerprint": fingerprint,
+                "severity": "major",
+                "location": { "path": path, "lines": { "begin": line } },
+            })
+        })
+        .collect();
+    Value::Array(issues).to_string()
+}
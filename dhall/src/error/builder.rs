@@ -3,11 +3,60 @@ use annotate_snippets::{
     snippet::{Annotation, AnnotationType, Slice, Snippet, SourceAnnotation},
 };
 
+use super::TypeErrorKind;
 use crate::syntax::{ParsedSpan, Span};
 
+/// A range in the source text, as 1-based lines and columns. The end is exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourcePosition {
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+/// A formatted error message, along with the information needed to report it in other formats.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    title: String,
+    kind: Option<TypeErrorKind>,
+    position: Option<SourcePosition>,
+    // Boxed slices keep errors small.
+    labels: Box<[DiagnosticLabel]>,
+    notes: Box<[DiagnosticNote]>,
+    rendered: String,
+}
+
+/// The kind of a message attached to a diagnostic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+    Note,
+    Help,
+}
+
+/// A message pointing at a location in the source text, e.g. the offending subexpression or the
+/// annotation that explains why it is wrong.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiagnosticLabel {
+    pub severity: Severity,
+    pub position: SourcePosition,
+    pub message: String,
+}
+
+/// A message without location, shown after the source snippet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiagnosticNote {
+    pub severity: Severity,
+    pub message: String,
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct ErrorBuilder {
     title: FreeAnnotation,
+    kind: Option<TypeErrorKind>,
     annotations: Vec<SpannedAnnotation>,
     footer: Vec<FreeAnnotation>,
     /// Inducate that the current builder has already been consumed and consuming it again should
@@ -28,6 +77,18 @@ struct FreeAnnotation {
     annotation_type: AnnotationType,
 }
 
+impl From<AnnotationType> for Severity {
+    fn from(annotation_type: AnnotationType) -> Self {
+        match annotation_type {
+            AnnotationType::Error => Severity::Error,
+            AnnotationType::Warning => Severity::Warning,
+            AnnotationType::Info => Severity::Info,
+            AnnotationType::Note => Severity::Note,
+            AnnotationType::Help => Severity::Help,
+        }
+    }
+}
+
 impl SpannedAnnotation {
     fn into_annotation(self) -> SourceAnnotation {
         SourceAnnotation {
@@ -57,12 +118,19 @@ impl ErrorBuilder {
                 message: message.to_string(),
                 annotation_type: AnnotationType::Error,
             },
+            kind: None,
             annotations: Vec::new(),
             footer: Vec::new(),
             consumed: false,
         }
     }
 
+    /// Records which kind of type error this is, which gives it a code and an explanation.
+    pub fn kind(&mut self, kind: TypeErrorKind) -> &mut Self {
+        self.kind = Some(kind);
+        self
+    }
+
     pub fn span_annot(
         &mut self,
         span: Span,
@@ -116,7 +184,7 @@ impl ErrorBuilder {
 
     // TODO: handle multiple files
     #[allow(clippy::drop_ref)]
-    pub fn format(&mut self) -> String {
+    pub fn format(&mut self) -> Diagnostic {
         if self.consumed {
             panic!("tried to format the same ErrorBuilder twice")
         }
@@ -124,13 +192,42 @@ impl ErrorBuilder {
         self.consumed = true;
         drop(self); // Get rid of the self reference so we don't use it by mistake.
 
+        let title = this.title.message.clone();
+        // The position of the error is that of the first error annotation, if any.
+        let position = this
+            .annotations
+            .iter()
+            .find(|annot| annot.annotation_type == AnnotationType::Error)
+            .or_else(|| this.annotations.first())
+            .map(|annot| annot.span.position());
+        let labels = this
+            .annotations
+            .iter()
+            .map(|annot| DiagnosticLabel {
+                severity: annot.annotation_type.into(),
+                position: annot.span.position(),
+                message: annot.message.clone(),
+            })
+            .collect();
+        let notes = this
+            .footer
+            .iter()
+            .map(|annot| DiagnosticNote {
+                severity: annot.annotation_type.into(),
+                message: annot.message.clone(),
+            })
+            .collect();
+
         let slices = if this.annotations.is_empty() {
             Vec::new()
         } else {
-            let input = this.annotations[0].span.to_input();
+            let first = this.annotations[0].span.clone();
+            let input = first.to_input();
+            // Annotations pointing into other files can't be shown in this snippet.
             let annotations = this
                 .annotations
                 .into_iter()
+                .filter(|annot| annot.span.same_input(&first))
                 .map(|annot| annot.into_annotation())
                 .collect();
             vec![Slice {
@@ -147,13 +244,76 @@ impl ErrorBuilder {
             .map(|annot| annot.into_annotation())
             .collect();
 
+        let mut title_annot = this.title.into_annotation();
+        title_annot.id = this
+            .kind
+            .map(|kind| super::explain::lookup(kind).0.to_string());
         let snippet = Snippet {
-            title: Some(this.title.into_annotation()),
+            title: Some(title_annot),
             slices,
             footer,
             opt: Default::default(),
         };
-        DisplayList::from(snippet).to_string()
+        Diagnostic {
+            title,
+            kind: this.kind,
+            position,
+            labels,
+            notes,
+            rendered: DisplayList::from(snippet).to_string(),
+        }
+    }
+}
+
+impl Diagnostic {
+    /// A diagnostic without source location.
+    pub fn new(message: impl ToString) -> Self {
+        let message = message.to_string();
+        Diagnostic {
+            title: message.clone(),
+            kind: None,
+            position: None,
+            labels: Box::new([]),
+            notes: Box::new([]),
+            rendered: message,
+        }
+    }
+    /// The one-line summary of the error.
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+    /// The kind of type error reported, if it has one.
+    pub fn kind(&self) -> Option<TypeErrorKind> {
+        self.kind
+    }
+    /// The location in the source text the error points to, if any.
+    pub fn position(&self) -> Option<SourcePosition> {
+        self.position
+    }
+    /// The messages pointing at locations in the source text.
+    pub fn labels(&self) -> &[DiagnosticLabel] {
+        &self.labels
+    }
+    /// The messages shown after the source snippet, e.g. hints on how to fix the error.
+    pub fn notes(&self) -> &[DiagnosticNote] {
+        &self.notes
+    }
+}
+
+impl From<String> for Diagnostic {
+    fn from(message: String) -> Self {
+        Diagnostic::new(message)
+    }
+}
+impl From<&str> for Diagnostic {
+    fn from(message: &str) -> Self {
+        Diagnostic::new(message)
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(&self.rendered)
     }
 }
 
@@ -0,0 +1,732 @@
+//! Stable codes and long-form explanations of type errors, in the spirit of the `--explain` flag
+//! of the Haskell implementation. Each explanation says what went wrong, why Dhall rejects it,
+//! and how the expression can usually be fixed.
+
+use super::TypeErrorKind;
+
+/// Finds the stable code and the explanation of a type error from its kind. Codes are never
+/// reused or renumbered: new errors get new codes.
+pub(crate) fn lookup(kind: TypeErrorKind) -> (&'static str, &'static str) {
+    use TypeErrorKind::*;
+    match kind {
+        UnboundVariable => ("DH-TYPE-001", UNBOUND_VARIABLE),
+        AnnotMismatch => ("DH-TYPE-002", ANNOT_MISMATCH),
+        NotAType => ("DH-TYPE-003", EXPECTED_A_TYPE),
+        NotAFunction => ("DH-TYPE-004", NOT_A_FUNCTION),
+        TypeMismatch => ("DH-TYPE-005", TYPE_MISMATCH),
+        InvalidTextInterpolation => ("DH-TYPE-006", INVALID_TEXT_INTERPOLATION),
+        InvalidOptionalType => ("DH-TYPE-007", INVALID_OPTIONAL_TYPE),
+        InvalidListType => ("DH-TYPE-008", INVALID_LIST_TYPE),
+        InvalidListElement => ("DH-TYPE-009", INVALID_LIST_ELEMENT),
+        InvalidFieldType => ("DH-TYPE-010", INVALID_FIELD_TYPE),
+        InvalidVariantType => ("DH-TYPE-011", INVALID_VARIANT_TYPE),
+        AssertMismatch => ("DH-TYPE-012", ASSERT_MISMATCH),
+        AssertMustTakeEquivalence => {
+            ("DH-TYPE-013", ASSERT_MUST_TAKE_EQUIVALENCE)
+        }
+        Untyped => ("DH-TYPE-014", UNTYPED),
+        InvalidOutputType => ("DH-TYPE-015", INVALID_OUTPUT_TYPE),
+        InvalidInputType => ("DH-TYPE-047", SORT_ARGUMENT),
+        RecordTypeMergeRequiresRecordType => {
+            ("DH-TYPE-016", COMBINE_TYPES_REQUIRES_RECORD)
+        }
+        MustCombineRecord => ("DH-TYPE-017", MUST_COMBINE_RECORD),
+        BinOpTypeMismatch => ("DH-TYPE-018", BIN_OP_TYPE_MISMATCH),
+        EquivalenceTypeMismatch => ("DH-TYPE-019", EQUIVALENCE_TYPE_MISMATCH),
+        EquivalenceArgumentsMustBeTerms => {
+            ("DH-TYPE-020", EQUIVALENCE_ARGUMENTS_MUST_BE_TERMS)
+        }
+        Merge1ArgMustBeRecord => ("DH-TYPE-021", MERGE_HANDLERS_MUST_BE_RECORD),
+        Merge2ArgMustBeUnionOrOptional => {
+            ("DH-TYPE-022", MERGE_MUST_TAKE_UNION)
+        }
+        MergeHandlerInputType => ("DH-TYPE-023", MERGE_HANDLER_INPUT_TYPE),
+        MergeHandlerNotFunction => ("DH-TYPE-024", MERGE_HANDLER_NOT_FUNCTION),
+        MergeReturnTypeIsDependent => {
+            ("DH-TYPE-025", MERGE_RETURN_TYPE_IS_DEPENDENT)
+        }
+        MergeHandlerMissingVariant => ("DH-TYPE-026", MERGE_UNUSED_HANDLER),
+        MergeHandlerTypeMismatch => {
+            ("DH-TYPE-027", MERGE_HANDLER_TYPE_MISMATCH)
+        }
+        MergeVariantMissingHandler => ("DH-TYPE-028", MERGE_MISSING_HANDLER),
+        MergeAnnotMismatch => ("DH-TYPE-029", MERGE_ANNOT_MISMATCH),
+        MergeEmptyNeedsAnnotation => {
+            ("DH-TYPE-030", MERGE_EMPTY_NEEDS_ANNOTATION)
+        }
+        InvalidPredicate => ("DH-TYPE-031", INVALID_PREDICATE),
+        IfBranchMustBeTerm => ("DH-TYPE-032", IF_BRANCH_MUST_BE_TERM),
+        IfBranchMismatch => ("DH-TYPE-033", IF_BRANCH_MISMATCH),
+        InvalidToMapRecordKind => ("DH-TYPE-034", TO_MAP_ARGUMENT),
+        ToMapNotARecord => ("DH-TYPE-035", TO_MAP_ARGUMENT),
+        HeterogenousRecordToMap => ("DH-TYPE-036", TO_MAP_ARGUMENT),
+        MissingToMapType => ("DH-TYPE-037", TO_MAP_ANNOTATION),
+        InvalidToMapType => ("DH-TYPE-038", TO_MAP_ANNOTATION),
+        MapTypeMismatch => ("DH-TYPE-039", TO_MAP_ANNOTATION),
+        MissingRecordField => ("DH-TYPE-040", MISSING_FIELD),
+        MissingUnionField => ("DH-TYPE-041", MISSING_CONSTRUCTOR),
+        NotARecord => ("DH-TYPE-042", NOT_A_RECORD),
+        ProjectionMustBeRecord => ("DH-TYPE-043", CANT_PROJECT),
+        ProjectionMissingEntry => ("DH-TYPE-044", MISSING_FIELD),
+        ProjectionByExprTakesRecordType => {
+            ("DH-TYPE-045", CANT_PROJECT_BY_EXPRESSION)
+        }
+        ProjectionWrongType => ("DH-TYPE-046", PROJECTION_TYPE_MISMATCH),
+        DuplicateFieldCannotBeMerged => ("DH-TYPE-048", DUPLICATE_FIELD),
+        WithMustBeRecord => ("DH-TYPE-049", WITH_MUST_BE_RECORD),
+    }
+}
+
+const UNBOUND_VARIABLE: &str = "\
+Expressions can only reference previously introduced (i.e. \"bound\") variables
+that are still \"in scope\". Variables are introduced by `let`, by function
+arguments `λ(x : T) → ...` and by function types `∀(x : T) → ...`.
+
+For example, this is fine:
+
+    λ(x : Bool) → x
+
+... but this is not, because `x` is referenced outside of the function:
+
+    (λ(x : Bool) → x) && x
+
+Check the spelling of the variable: names are case-sensitive. If you meant to
+reference a variable shadowed by another one with the same name, use `x@1` to
+refer to the enclosing one.";
+
+const ANNOT_MISMATCH: &str = "\
+Every expression has a type, and a type annotation `e : T` checks that the
+inferred type of `e` is `T`. This annotation does not match the type that was
+inferred for the expression.
+
+For example, this is fine:
+
+    1 : Natural
+
+... but this is not, because `1` is a `Natural` and not an `Integer`:
+
+    1 : Integer
+
+Either fix the expression so that it has the annotated type, or change the
+annotation to the inferred type shown above. Note that types are compared
+after normalization, so `List Natural` and `(λ(x : Type) → List x) Natural`
+are the same type.";
+
+const EXPECTED_A_TYPE: &str = "\
+This position requires a type (like `Natural` or `List Bool`) or a kind (like
+`Type`), but a term was given instead. This happens for the type annotation
+of a function argument, of a `let` binding, or on the right of `:`.
+
+For example, this is fine:
+
+    λ(x : Natural) → x
+
+... but this is not, because `1` is a value, not a type:
+
+    λ(x : 1) → x
+
+Replace the expression with the type you meant, e.g. the type of the value
+that was written.";
+
+const NOT_A_FUNCTION: &str = "\
+Only functions can be applied to arguments. The expression on the left of
+this application does not have a function type (`∀(x : A) → B`).
+
+For example, this is fine:
+
+    Natural/even 2
+
+... but this is not, because `True` is a `Bool`, not a function:
+
+    True 2
+
+A common cause is a missing operator between two expressions, like `x y`
+instead of `x + y`, or a missing comma in a list or record, which makes two
+neighbouring elements look like a function application.";
+
+const TYPE_MISMATCH: &str = "\
+Every function declares the type of its argument, and can only be applied to
+arguments of that type.
+
+For example, this is fine:
+
+    (λ(x : Bool) → x) True
+
+... but this is not, because `1` is a `Natural`, not a `Bool`:
+
+    (λ(x : Bool) → x) 1
+
+Either pass an argument of the expected type, or convert it first, e.g. with
+`Natural/toInteger` or `Natural/show`. Note that Dhall never converts values
+implicitly.";
+
+const INVALID_TEXT_INTERPOLATION: &str = "\
+Only `Text` values can be interpolated into a `Text` literal with `${...}`.
+
+For example, this is fine:
+
+    let name = \"world\" in \"Hello, ${name}!\"
+
+... but this is not, because `1` is a `Natural`:
+
+    \"Hello, ${1}!\"
+
+Convert the value to `Text` first, e.g. with `Natural/show`, `Integer/show`
+or `Double/show`.";
+
+const INVALID_OPTIONAL_TYPE: &str = "\
+`Some` can only wrap terms, i.e. values whose type has type `Type`.
+
+For example, this is fine:
+
+    Some 1
+
+... but this is not, because `Natural` is a type, not a value:
+
+    Some Natural
+
+If you wanted the type of optional naturals, write `Optional Natural`.";
+
+const INVALID_LIST_TYPE: &str = "\
+An empty list must be annotated with a type of the form `List T`, and list
+elements must be terms, i.e. values whose type has type `Type`.
+
+For example, these are fine:
+
+    [] : List Natural
+
+    [ 1, 2, 3 ]
+
+... but these are not:
+
+    [] : Natural
+
+    [ Natural, Bool ]
+
+The first one has an annotation that is not a `List` type; write
+`[] : List Natural` instead. The second one contains types rather than values.";
+
+const INVALID_LIST_ELEMENT: &str = "\
+All the elements of a list must have the same type.
+
+For example, this is fine:
+
+    [ 1, 2, 3 ]
+
+... but this is not, because `True` is a `Bool` and the other elements are
+`Natural`s:
+
+    [ 1, True, 3 ]
+
+If you need to store values of different types, wrap them in a union type,
+e.g. `< N : Natural | B : Bool >`, and use its constructors for every
+element.";
+
+const INVALID_FIELD_TYPE: &str = "\
+The fields of a record must be terms, types or kinds, and the fields of a
+record type must be types or kinds. This field has a value that can't be
+stored in a record, e.g. a record type field whose \"type\" is actually a
+value.
+
+For example, this is fine:
+
+    { x : Natural }
+
+... but this is not, because `1` is a value, not a type:
+
+    { x : 1 }
+
+Did you mean to write a record value `{ x = 1 }` instead of a record type?";
+
+const INVALID_VARIANT_TYPE: &str = "\
+The alternatives of a union type must be types, and all of them must live in
+the same universe: either all are types like `Natural`, or all are kinds like
+`Type`.
+
+For example, this is fine:
+
+    < Left : Natural | Right : Bool >
+
+... but these are not:
+
+    < Left : 1 >
+
+    < Left : Natural | Right : Type >
+
+The first one uses a value instead of a type. The second one mixes a type
+(`Natural`) with a kind (`Type`).";
+
+const ASSERT_MISMATCH: &str = "\
+An `assert` checks that both sides of an equivalence `x ≡ y` are the same
+after normalization. Here the two sides normalize to different expressions,
+so the assertion fails.
+
+For example, this is fine:
+
+    assert : 2 + 2 ≡ 4
+
+... but this is not:
+
+    assert : 2 + 2 ≡ 5
+
+Compare the normal forms of both sides to find where they differ. Note that
+Dhall only compares normal forms: two functions that always return the same
+result can still fail the check if their bodies normalize differently.";
+
+const ASSERT_MUST_TAKE_EQUIVALENCE: &str = "\
+The annotation of an `assert` must be an equivalence of the form `x ≡ y`.
+
+For example, this is fine:
+
+    assert : Natural/even 2 ≡ True
+
+... but this is not, because the annotation is a `Bool` rather than an
+equivalence:
+
+    assert : Natural/even 2
+
+Write the expected value on the right of `≡` (or `===`).";
+
+const UNTYPED: &str = "\
+Dhall has a hierarchy of universes: terms like `1` have types like `Natural`,
+types have kinds like `Type`, and kinds have type `Sort`:
+
+    1 : Natural : Type : Kind : Sort
+
+`Sort` is at the top of the hierarchy and has no type itself, so it can only
+appear as a type annotation. Using it anywhere else would require giving it a
+type.
+
+For example, this is fine:
+
+    Kind : Sort
+
+... but these are not:
+
+    Sort
+
+    [ Sort ]
+
+Most of the time `Sort` shows up by mistake one level too high: `Kind` or
+`Type` was probably meant.";
+
+const INVALID_OUTPUT_TYPE: &str = "\
+The body of a function must be a term, a type or a kind like `Type` or
+`Type → Type`. It can't be `Kind`, or anything else of type `Sort`, since
+the type of such a function would have to live above `Sort`.
+
+For example, this is fine:
+
+    λ(x : Type) → List x
+
+... but this is not, because `Kind` has type `Sort`:
+
+    λ(x : Bool) → Kind
+
+Functions that compute kinds aren't supported; write the kind out directly
+instead.";
+
+const SORT_ARGUMENT: &str = "\
+A function can take terms, types or kinds as arguments, so the type of its
+argument can be a type, a kind like `Type`, or `Kind`. It can't be `Sort`,
+because `Sort` has no type: the function itself couldn't be typed.
+
+For example, this is fine:
+
+    λ(k : Kind) → k
+
+... but this is not:
+
+    λ(k : Sort) → k
+
+Functions over kinds themselves (`Kind`, `Kind → Kind`, ...) aren't
+supported.";
+
+const COMBINE_TYPES_REQUIRES_RECORD: &str = "\
+The recursive record merge operators `∧` (`/\\`) and `⩓` (`//\\\\`) can only
+combine records (respectively record types). When two records share a field,
+its values are merged recursively, so that field must also be a record.
+
+For example, this is fine:
+
+    { a = { x = 1 } } ∧ { a = { y = True } }
+
+... but this is not, because `a` is a `Natural` on both sides and naturals
+can't be merged:
+
+    { a = 1 } ∧ { a = 2 }
+
+If you want the right side to override the left one, use `⫽` (`//`)
+instead. Otherwise rename one of the conflicting fields.";
+
+const DUPLICATE_FIELD: &str = "\
+A record literal may define the same field several times. The values are then
+combined with `∧` (`/\\`), so this:
+
+    { a = { x = 1 }, a = { y = True } }
+
+... means the same as `{ a = { x = 1, y = True } }`. This only works if every
+value is a record and the fields they have in common can be combined the same
+way. This is not allowed, because naturals can't be combined:
+
+    { a = 1, a = 2 }
+
+Remove or rename one of the definitions. Record types and union types can't
+have duplicate fields at all.";
+
+const MUST_COMBINE_RECORD: &str = "\
+The `⫽` (`//`) operator only works on records: it returns the fields of both
+records, taking the value from the right one when a field is on both sides.
+
+For example, this is fine:
+
+    { x = 1, y = 2 } ⫽ { y = 3 }
+
+... but this is not, because `1` is not a record:
+
+    { x = 1 } ⫽ 1
+
+Did you mean to update a single field? Then wrap it in a record, as in
+`r ⫽ { x = 1 }`, or use `r with x = 1`.";
+
+const BIN_OP_TYPE_MISMATCH: &str = "\
+Each operator only works on arguments of specific types:
+
+    `&&`, `||`, `==`, `!=` take `Bool`s
+    `+`, `*` take `Natural`s
+    `++` takes `Text`
+    `#` takes two `List`s with the same element type
+
+For example, this is fine:
+
+    1 + 2
+
+... but this is not, because `-1` is an `Integer`, not a `Natural`:
+
+    1 + -1
+
+There is no implicit conversion between numeric types; convert explicitly,
+e.g. with `Natural/toInteger`. There is no `+` on `Integer`s: use
+`Integer/add` instead. To concatenate a `Text` with a number, use
+`Natural/show` or an interpolation.";
+
+const EQUIVALENCE_TYPE_MISMATCH: &str = "\
+Both sides of an equivalence `x ≡ y` must have the same type, since they can
+only be equal if they have the same type.
+
+For example, this is fine:
+
+    2 + 2 ≡ 4
+
+... but this is not, because `+4` is an `Integer`:
+
+    2 + 2 ≡ +4";
+
+const EQUIVALENCE_ARGUMENTS_MUST_BE_TERMS: &str = "\
+An equivalence `x ≡ y` can only compare terms, i.e. values whose type has
+type `Type`. Comparing types is not supported.
+
+For example, this is fine:
+
+    1 ≡ 1
+
+... but this is not, because `Natural` is a type:
+
+    Natural ≡ Natural";
+
+const MERGE_HANDLERS_MUST_BE_RECORD: &str = "\
+The first argument of `merge` must be a record of handlers, with one function
+for each alternative of the union (or `Some`/`None` for an `Optional`).
+
+For example, this is fine:
+
+    merge { Left = Natural/even, Right = λ(b : Bool) → b } (< Left : Natural | Right : Bool >.Left 2)
+
+... but this is not, because `True` is not a record:
+
+    merge True (< Left : Natural | Right : Bool >.Left 2)";
+
+const MERGE_MUST_TAKE_UNION: &str = "\
+The second argument of `merge` must be a union value (or an `Optional`),
+since `merge` picks the handler for the alternative that value holds.
+
+For example, this is fine:
+
+    merge { None = 0, Some = λ(n : Natural) → n } (Some 1)
+
+... but this is not, because `1` is neither a union nor an `Optional`:
+
+    merge { None = 0, Some = λ(n : Natural) → n } 1";
+
+const MERGE_HANDLER_INPUT_TYPE: &str = "\
+Each handler of a `merge` receives the value stored in its alternative, so
+the argument type of the handler must be the type of that alternative.
+
+For example, this is fine:
+
+    merge { Left = Natural/even } (< Left : Natural >.Left 2)
+
+... but this is not, because the handler expects a `Bool` where the union
+holds a `Natural`:
+
+    merge { Left = λ(b : Bool) → b } (< Left : Natural >.Left 2)
+
+Fix either the type of the handler's argument or the union type.";
+
+const MERGE_HANDLER_NOT_FUNCTION: &str = "\
+Handlers for alternatives that store a value must be functions that take
+that value. Only handlers for empty alternatives (like `< Empty >`) are plain
+values.
+
+For example, this is fine:
+
+    merge { Some = λ(n : Natural) → n, None = 0 } (Some 1)
+
+... but this is not, because the handler for `Some` is not a function:
+
+    merge { Some = 1, None = 0 } (Some 1)
+
+If you don't need the stored value, ignore it with `λ(_ : Natural) → 1`.";
+
+const MERGE_RETURN_TYPE_IS_DEPENDENT: &str = "\
+The output type of a `merge` handler can't depend on the value the handler
+receives: all handlers must return values of a single type that is known
+before the union is inspected.
+
+For example, this is not allowed, because the output type `x` depends on the
+input:
+
+    merge { Left = λ(x : Type) → x } (< Left : Type >.Left Bool)";
+
+const MERGE_UNUSED_HANDLER: &str = "\
+The record of handlers passed to `merge` has a handler for an alternative
+that does not exist in the union type. Each handler must correspond to one of
+the alternatives.
+
+For example, this is not allowed, because the union has no `Right`
+alternative:
+
+    merge { Left = Natural/even, Right = λ(b : Bool) → b } (< Left : Natural >.Left 2)
+
+Remove the extra handler, or check for a typo in its name.";
+
+const MERGE_HANDLER_TYPE_MISMATCH: &str = "\
+All the handlers of a `merge` must return values of the same type, which is
+the type of the whole `merge` expression.
+
+For example, this is fine:
+
+    merge { Left = Natural/even, Right = λ(b : Bool) → b } u
+
+... but this is not, because one handler returns a `Bool` and the other a
+`Natural`:
+
+    merge { Left = Natural/even, Right = λ(b : Bool) → 1 } u
+
+Make the handlers agree on their output type, e.g. by converting their
+results with `Natural/show`.";
+
+const MERGE_MISSING_HANDLER: &str = "\
+`merge` requires a handler for every alternative of the union, so that the
+result is defined whatever alternative the value holds.
+
+For example, this is not allowed, because there is no handler for `Right`:
+
+    merge { Left = Natural/even } (< Left : Natural | Right : Bool >.Left 2)
+
+Add the missing handler. Dhall has no wildcard handler: every alternative
+must be handled explicitly.";
+
+const MERGE_ANNOT_MISMATCH: &str = "\
+The type annotation on this `merge` does not match the type returned by its
+handlers.
+
+For example, this is fine:
+
+    merge { None = 0, Some = λ(n : Natural) → n } (Some 1) : Natural
+
+... but this is not, because the handlers return a `Natural`:
+
+    merge { None = 0, Some = λ(n : Natural) → n } (Some 1) : Bool";
+
+const MERGE_EMPTY_NEEDS_ANNOTATION: &str = "\
+A `merge` on an empty union type has no handlers, so the type of its result
+can't be inferred and must be given with an annotation.
+
+For example:
+
+    λ(x : <>) → merge {=} x : Natural";
+
+const INVALID_PREDICATE: &str = "\
+The condition of an `if` must be a `Bool`.
+
+For example, this is fine:
+
+    if Natural/isZero n then 0 else 1
+
+... but this is not, because `n` is a `Natural`:
+
+    if n then 0 else 1
+
+Dhall has no notion of \"truthiness\": compare the value explicitly, e.g.
+with `Natural/isZero`.";
+
+const IF_BRANCH_MUST_BE_TERM: &str = "\
+The branches of an `if` must be terms, i.e. values whose type has type
+`Type`. An `if` can't choose between types.
+
+For example, this is not allowed:
+
+    if True then Natural else Bool
+
+To select a type based on a condition, use a union type or a function that
+takes the type as an argument.";
+
+const IF_BRANCH_MISMATCH: &str = "\
+Both branches of an `if` must have the same type, since the type of the
+result can't depend on the condition.
+
+For example, this is fine:
+
+    if b then 1 else 0
+
+... but this is not, because one branch is a `Natural` and the other a
+`Text`:
+
+    if b then 1 else \"zero\"
+
+Convert one of the branches, or wrap the results in a union type.";
+
+const TO_MAP_ARGUMENT: &str = "\
+`toMap` turns a record of terms into a list of `{ mapKey : Text, mapValue : T }`
+entries, so its argument must be a record value (not a record type) and all
+its fields must have the same type `T`.
+
+For example, this is fine:
+
+    toMap { a = 1, b = 2 }
+
+... but these are not:
+
+    toMap { a = 1, b = True }
+
+    toMap { a : Natural }
+
+The first one mixes field types; wrap the values in a union type to store
+them in one map. The second one is a record type rather than a record value.";
+
+const TO_MAP_ANNOTATION: &str = "\
+The type annotation of `toMap` must be of the form
+`List { mapKey : Text, mapValue : T }`, matching the fields of the record.
+It is required when the record is empty, since the type of the values can't
+be inferred from zero fields.
+
+For example, these are fine:
+
+    toMap {=} : List { mapKey : Text, mapValue : Natural }
+
+    toMap { a = 1 } : List { mapKey : Text, mapValue : Natural }
+
+... but this is not, because the values are `Natural`s:
+
+    toMap { a = 1 } : List { mapKey : Text, mapValue : Bool }";
+
+const MISSING_FIELD: &str = "\
+This accesses or selects a field that does not exist in the record.
+
+For example, this is fine:
+
+    { x = 1, y = 2 }.x
+
+    { x = 1, y = 2 }.{ x }
+
+... but these are not, because there is no `z` field:
+
+    { x = 1, y = 2 }.z
+
+    { x = 1, y = 2 }.{ x, z }
+
+Check the spelling of the field: field names are case-sensitive. If the
+field is optional, give it an `Optional` type in the record instead of
+leaving it out.";
+
+const MISSING_CONSTRUCTOR: &str = "\
+This accesses a constructor that does not exist in the union type.
+
+For example, this is fine:
+
+    < Left : Natural | Right : Bool >.Left 1
+
+... but this is not, because there is no `Middle` alternative:
+
+    < Left : Natural | Right : Bool >.Middle 1
+
+Check the spelling of the alternative, or add it to the union type.";
+
+const NOT_A_RECORD: &str = "\
+The `.` operator accesses a field of a record, or a constructor of a union
+type. The expression on its left is neither.
+
+For example, this is fine:
+
+    { x = 1 }.x
+
+    < A | B >.A
+
+... but this is not, because `1` is a `Natural`:
+
+    1.x
+
+If the value is a union value rather than a union type, use `merge` to
+inspect it.";
+
+const CANT_PROJECT: &str = "\
+A projection `e.{ x, y }` selects some fields of a record, so `e` must be a
+record.
+
+For example, this is fine:
+
+    { x = 1, y = 2, z = 3 }.{ x, y }
+
+... but this is not, because `1` is not a record:
+
+    1.{ x }";
+
+const CANT_PROJECT_BY_EXPRESSION: &str = "\
+A projection by type `e.(T)` selects the fields of the record `e` listed in
+the record type `T`. The expression in parentheses must be a record type.
+
+For example, this is fine:
+
+    { x = 1, y = True }.({ x : Natural })
+
+... but this is not, because `Natural` is not a record type:
+
+    { x = 1, y = True }.(Natural)";
+
+const PROJECTION_TYPE_MISMATCH: &str = "\
+In a projection by type `e.(T)`, the fields of `T` must have the same types
+as the corresponding fields of the record `e`.
+
+For example, this is fine:
+
+    { x = 1, y = True }.({ x : Natural })
+
+... but this is not, because `x` is a `Natural`, not a `Bool`:
+
+    { x = 1, y = True }.({ x : Bool })";
+
+const WITH_MUST_BE_RECORD: &str = "\
+`e with a.b = v` updates the field `a.b` of the record `e`. Both `e` and every
+field along the path that already exists must be records; missing fields are
+created as needed.
+
+For example, this is fine:
+
+    { a = { b = 1 } } with a.c = True
+
+... but this is not, because `a` is a `Natural`, not a record:
+
+    { a = 1 } with a.b = 2
+
+Did you mean to replace `a` entirely? Then write `{ a = 1 } with a = { b = 2 }`.";
@@ -1,16 +1,23 @@
 use std::io::Error as IOError;
+use std::ops::Range;
 
 use crate::semantics::resolve::{ImportLocation, ImportStack};
-use crate::syntax::{Import, ParseError};
+use crate::semantics::TyEnv;
+use crate::syntax::{Expr, Import, ParsedSpan, Span};
 
+pub mod annotations;
 mod builder;
+mod explain;
+#[cfg(feature = "serialize-errors")]
+mod serialize;
 pub use builder::*;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Debug)]
 pub struct Error {
-    kind: ErrorKind,
+    // Boxed to keep `Result`s small.
+    kind: Box<ErrorKind>,
 }
 
 #[derive(Debug)]
@@ -22,18 +29,104 @@ pub enum ErrorKind {
     Encode(EncodeError),
     Resolve(ImportError),
     Typecheck(TypeError),
+    Eval(EvalError),
 }
 
 #[derive(Debug)]
 pub enum ImportError {
     Missing,
     MissingEnvVar,
-    SanityCheck,
+    ReferentialSanity(Box<ReferentialSanityError>),
+    /// A remote import from another origin was not allowed by the server.
+    Cors(Box<CorsError>),
+    /// A remote import was encountered while network access is disabled.
+    Offline(url::Url),
+    /// A remote import could not be downloaded.
+    Fetch(Box<FetchError>),
+    /// Resolving the imports went over one of the configured limits.
+    LimitExceeded(ImportLimit),
     UnexpectedImport(Import<()>),
     ImportCycle(ImportStack, ImportLocation),
+    /// All the alternatives of a `?` failed to resolve.
+    ImportAlternatives(Vec<Error>),
     Url(url::ParseError),
 }
 
+/// A remote import tried to import a local file or an environment variable.
+#[derive(Debug)]
+pub struct ReferentialSanityError {
+    /// The remote import that contains the offending import.
+    pub parent: url::Url,
+    pub target: ImportLocation,
+}
+
+/// A remote import was imported from another origin without the server allowing it.
+#[derive(Debug)]
+pub struct CorsError {
+    /// The origin of the importing file.
+    pub origin: String,
+    pub url: url::Url,
+    /// The `Access-Control-Allow-Origin` header sent by the server, if any.
+    pub allow_origin: Option<String>,
+}
+
+/// A remote import could not be downloaded.
+#[derive(Debug)]
+pub struct FetchError {
+    pub url: url::Url,
+    pub kind: FetchErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FetchErrorKind {
+    /// The server didn't answer in time.
+    Timeout,
+    /// The server answered with an error status, e.g. 404.
+    Status(u16),
+    /// The connection failed or was interrupted.
+    Connection(String),
+}
+
+impl FetchError {
+    /// Whether trying again may succeed: timeouts, connection failures and server errors are
+    /// transient, while client errors like 404 are not.
+    pub fn is_transient(&self) -> bool {
+        match self.kind {
+            FetchErrorKind::Timeout | FetchErrorKind::Connection(_) => true,
+            FetchErrorKind::Status(status) => status >= 500,
+        }
+    }
+}
+
+/// A limit on import resolution, with its configured value. See `ImportLimits`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportLimit {
+    /// The maximum number of nested imports.
+    Depth(usize),
+    /// The maximum number of distinct imports.
+    Imports(usize),
+    /// The maximum number of bytes read.
+    Bytes(usize),
+}
+
+/// Normalization was aborted.
+#[derive(Debug, Clone)]
+pub enum EvalError {
+    /// Normalization took more steps than allowed by `Typed::normalize_with_budget`.
+    BudgetExceeded(Box<BudgetExceededError>),
+}
+
+/// Normalization ran out of budget.
+#[derive(Debug, Clone)]
+pub struct BudgetExceededError {
+    /// The budget, in evaluation steps.
+    pub steps: usize,
+    diagnostic: Diagnostic,
+}
+
+/// How many of the calls leading to the point where the budget ran out get shown.
+const MAX_SHOWN_CALLS: usize = 10;
+
 #[derive(Debug)]
 pub enum DecodeError {
     CBORError(serde_cbor::error::Error),
@@ -45,6 +138,18 @@ pub enum EncodeError {
     CBORError(serde_cbor::error::Error),
 }
 
+/// A syntax error, with the location where parsing failed.
+#[derive(Debug)]
+pub struct ParseError {
+    expected: Vec<String>,
+    // Boxed to keep errors small.
+    diagnostic: Box<Diagnostic>,
+    byte_span: Range<usize>,
+    line: usize,
+    column: usize,
+    snippet: String,
+}
+
 /// A structured type error
 #[derive(Debug)]
 pub struct TypeError {
@@ -54,22 +159,253 @@ pub struct TypeError {
 /// The specific type error
 #[derive(Debug)]
 pub enum TypeMessage {
-    Custom(String),
+    Custom(Box<Diagnostic>),
+}
+
+/// The kinds of type errors that have a stable code and a long-form explanation. Errors record
+/// their kind when they are raised, see `ErrorBuilder::kind`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum TypeErrorKind {
+    UnboundVariable,
+    AnnotMismatch,
+    NotAType,
+    NotAFunction,
+    TypeMismatch,
+    InvalidTextInterpolation,
+    InvalidOptionalType,
+    InvalidListType,
+    InvalidListElement,
+    InvalidFieldType,
+    InvalidVariantType,
+    AssertMismatch,
+    AssertMustTakeEquivalence,
+    Untyped,
+    InvalidOutputType,
+    InvalidInputType,
+    RecordTypeMergeRequiresRecordType,
+    MustCombineRecord,
+    BinOpTypeMismatch,
+    EquivalenceTypeMismatch,
+    EquivalenceArgumentsMustBeTerms,
+    Merge1ArgMustBeRecord,
+    Merge2ArgMustBeUnionOrOptional,
+    MergeHandlerInputType,
+    MergeHandlerNotFunction,
+    MergeReturnTypeIsDependent,
+    MergeHandlerMissingVariant,
+    MergeHandlerTypeMismatch,
+    MergeVariantMissingHandler,
+    MergeAnnotMismatch,
+    MergeEmptyNeedsAnnotation,
+    InvalidPredicate,
+    IfBranchMustBeTerm,
+    IfBranchMismatch,
+    InvalidToMapRecordKind,
+    ToMapNotARecord,
+    HeterogenousRecordToMap,
+    MissingToMapType,
+    InvalidToMapType,
+    MapTypeMismatch,
+    MissingRecordField,
+    MissingUnionField,
+    NotARecord,
+    ProjectionMustBeRecord,
+    ProjectionMissingEntry,
+    ProjectionByExprTakesRecordType,
+    ProjectionWrongType,
+    DuplicateFieldCannotBeMerged,
+    WithMustBeRecord,
+}
+
+/// How much of the types mentioned in type errors gets printed. Types nested deeper than `depth`,
+/// and the entries of records, unions and lists beyond the first `width`, are replaced with `…`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TypeDisplayLimits {
+    pub depth: usize,
+    pub width: usize,
+}
+
+impl TypeDisplayLimits {
+    /// Prints types in full, e.g. when the elided part is what matters.
+    pub const FULL: TypeDisplayLimits = TypeDisplayLimits {
+        depth: usize::MAX,
+        width: usize::MAX,
+    };
+}
+
+impl Default for TypeDisplayLimits {
+    fn default() -> Self {
+        TypeDisplayLimits { depth: 6, width: 8 }
+    }
+}
+
+/// Prints a type for an error message, within the `TypeDisplayLimits` of the typechecking
+/// environment.
+pub(crate) fn elide_type(env: &TyEnv, ty: &Expr) -> String {
+    let limits = env.display_limits();
+    ty.elide(limits.depth, limits.width).to_string()
 }
 
 impl Error {
     pub fn new(kind: ErrorKind) -> Self {
-        Error { kind }
+        Error {
+            kind: Box::new(kind),
+        }
     }
     pub fn kind(&self) -> &ErrorKind {
         &self.kind
     }
+    pub fn into_kind(self) -> ErrorKind {
+        *self.kind
+    }
+}
+
+impl ParseError {
+    pub(crate) fn new(
+        expected: Vec<String>,
+        diagnostic: Diagnostic,
+        span: &ParsedSpan,
+    ) -> Self {
+        let (start, end) = span.byte_range();
+        let position = span.position();
+        ParseError {
+            expected,
+            diagnostic: Box::new(diagnostic),
+            byte_span: start..end,
+            line: position.line,
+            column: position.column,
+            snippet: span.as_str().to_owned(),
+        }
+    }
+    /// What the parser would have accepted where it failed, e.g. "an operator" or "`)`". Empty
+    /// for errors about input that parsed but is invalid, like an out-of-range number.
+    ///
+    /// ```
+    /// use dhall::error::ErrorKind;
+    ///
+    /// let err = dhall::Parsed::parse_str("λ(x : ) → x").unwrap_err();
+    /// let err = match err.kind() {
+    ///     ErrorKind::Parse(err) => err,
+    ///     _ => unreachable!(),
+    /// };
+    /// assert_eq!(err.expected(), ["an expression"]);
+    /// let position = err.diagnostic().position().unwrap();
+    /// assert_eq!((position.line, position.column), (1, 7));
+    /// assert!(err.to_string().contains("^ unexpected `)`"));
+    /// ```
+    pub fn expected(&self) -> &[String] {
+        &self.expected
+    }
+    /// The title, location and rendering of the error.
+    pub fn diagnostic(&self) -> &Diagnostic {
+        &self.diagnostic
+    }
+    /// The 1-based line where the error is.
+    ///
+    /// ```
+    /// use dhall::error::ErrorKind;
+    ///
+    /// let err = dhall::Parsed::parse_str("{ a = 1,\n  b = 2 3 ] }").unwrap_err();
+    /// let err = match err.kind() {
+    ///     ErrorKind::Parse(err) => err,
+    ///     _ => unreachable!(),
+    /// };
+    /// assert_eq!((err.line(), err.column()), (2, 11));
+    /// assert_eq!(err.byte_span(), 19..20);
+    /// assert_eq!(err.snippet(), "]");
+    /// ```
+    pub fn line(&self) -> usize {
+        self.line
+    }
+    /// The 1-based column where the error is, counted in characters.
+    pub fn column(&self) -> usize {
+        self.column
+    }
+    /// The byte offsets of the offending input in the source text.
+    pub fn byte_span(&self) -> Range<usize> {
+        self.byte_span.clone()
+    }
+    /// The offending input. Empty when the input ended unexpectedly.
+    pub fn snippet(&self) -> &str {
+        &self.snippet
+    }
 }
 
 impl TypeError {
     pub fn new(message: TypeMessage) -> Self {
         TypeError { message }
     }
+    /// The structured contents of the error: its title, the labelled source locations and the
+    /// notes. With the `serialize-errors` feature, the whole error can also be serialized with
+    /// `serde`, e.g. to JSON for a language server.
+    ///
+    /// ```
+    /// use dhall::error::Severity;
+    ///
+    /// let err = dhall::Parsed::parse_str("[ 1, True ]")
+    ///     .unwrap()
+    ///     .resolve()
+    ///     .unwrap()
+    ///     .typecheck()
+    ///     .unwrap_err();
+    /// let label = &err.diagnostic().labels()[0];
+    /// assert_eq!(label.severity, Severity::Error);
+    /// assert_eq!((label.position.line, label.position.column), (1, 6));
+    /// assert_eq!(label.message, "this has type: `Bool`");
+    /// ```
+    pub fn diagnostic(&self) -> &Diagnostic {
+        match &self.message {
+            TypeMessage::Custom(d) => d,
+        }
+    }
+    /// The kind of the error, if it is one with a code and an explanation.
+    ///
+    /// ```
+    /// use dhall::error::TypeErrorKind;
+    ///
+    /// let err = dhall::Parsed::parse_str("if 1 then 2 else 3")
+    ///     .unwrap()
+    ///     .resolve()
+    ///     .unwrap()
+    ///     .typecheck()
+    ///     .unwrap_err();
+    /// assert_eq!(err.kind(), Some(TypeErrorKind::InvalidPredicate));
+    /// ```
+    pub fn kind(&self) -> Option<TypeErrorKind> {
+        self.diagnostic().kind()
+    }
+    /// A long-form explanation of the error: what went wrong, why, and examples of how to fix
+    /// it. This is what the Haskell implementation prints with `--explain`.
+    ///
+    /// ```
+    /// let err = dhall::Parsed::parse_str("[ 1, True ]")
+    ///     .unwrap()
+    ///     .resolve()
+    ///     .unwrap()
+    ///     .typecheck()
+    ///     .unwrap_err();
+    /// assert!(err.explain().unwrap().contains("must have the same type"));
+    /// ```
+    pub fn explain(&self) -> Option<&'static str> {
+        self.kind().map(|kind| explain::lookup(kind).1)
+    }
+    /// A stable code identifying the kind of error, like `DH-TYPE-018`, for tools and
+    /// documentation to refer to. Codes are also shown in the rendered diagnostic.
+    ///
+    /// ```
+    /// let err = dhall::Parsed::parse_str("1 + True")
+    ///     .unwrap()
+    ///     .resolve()
+    ///     .unwrap()
+    ///     .typecheck()
+    ///     .unwrap_err();
+    /// assert_eq!(err.code(), Some("DH-TYPE-018"));
+    /// assert!(err.to_string().contains("error[DH-TYPE-018]"));
+    /// ```
+    pub fn code(&self) -> Option<&'static str> {
+        self.kind().map(|kind| explain::lookup(kind).0)
+    }
 }
 
 impl std::fmt::Display for TypeError {
@@ -84,15 +420,197 @@ impl std::fmt::Display for TypeError {
 
 impl std::error::Error for TypeError {}
 
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.diagnostic)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match &self.kind {
+        match self.kind() {
             ErrorKind::IO(err) => write!(f, "{}", err),
             ErrorKind::Parse(err) => write!(f, "{}", err),
             ErrorKind::Decode(err) => write!(f, "{:?}", err),
             ErrorKind::Encode(err) => write!(f, "{:?}", err),
-            ErrorKind::Resolve(err) => write!(f, "{:?}", err),
+            ErrorKind::Resolve(err) => write!(f, "{}", err),
             ErrorKind::Typecheck(err) => write!(f, "{}", err),
+            ErrorKind::Eval(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl BudgetExceededError {
+    /// `call_stack` lists the function applications that were being evaluated when the budget
+    /// ran out, outermost first. This is where to look for a runaway recursion.
+    pub(crate) fn new(steps: usize, call_stack: &[ParsedSpan]) -> Self {
+        let mut builder = ErrorBuilder::new(format!(
+            "Evaluation budget exceeded: normalization took more than {} steps",
+            steps
+        ));
+        let mut calls = call_stack.iter().rev();
+        if let Some(innermost) = calls.next() {
+            builder.span_err(
+                Span::Parsed(innermost.clone()),
+                "the budget ran out while evaluating this application",
+            );
+        }
+        let mut callers: Vec<&ParsedSpan> = Vec::new();
+        for span in calls {
+            // Recursion through a fold goes through the same application many times.
+            let repeated = callers.last().is_some_and(|last| {
+                last.same_input(span) && last.byte_range() == span.byte_range()
+            });
+            if !repeated {
+                callers.push(span);
+            }
+        }
+        for span in callers.iter().take(MAX_SHOWN_CALLS) {
+            let text = span.as_str().lines().next().unwrap_or_default();
+            let text = match text.char_indices().nth(40) {
+                Some((i, _)) => format!("{}…", &text[..i]),
+                None => text.to_owned(),
+            };
+            let position = span.position();
+            builder.note(format!(
+                "called from `{}` at line {}, column {}",
+                text, position.line, position.column
+            ));
+        }
+        if callers.len() > MAX_SHOWN_CALLS {
+            builder.note(format!(
+                "and {} more calls",
+                callers.len() - MAX_SHOWN_CALLS
+            ));
+        }
+        BudgetExceededError {
+            steps,
+            diagnostic: builder.format(),
+        }
+    }
+    /// The error, pointing at the innermost application being evaluated when the budget ran
+    /// out, with the applications it was called from as notes.
+    ///
+    /// ```
+    /// use dhall::error::{ErrorKind, EvalError};
+    ///
+    /// let src = "let f = λ(n : Natural) → Natural/fold n Natural (λ(x : Natural) → x + 1) 0\n\
+    ///            in  { a = f 1000000 }";
+    /// let err = dhall::Parsed::parse_str(src)
+    ///     .unwrap()
+    ///     .resolve()
+    ///     .unwrap()
+    ///     .typecheck()
+    ///     .unwrap()
+    ///     .normalize_with_budget(1000)
+    ///     .unwrap_err();
+    /// let diagnostic = match err.kind() {
+    ///     ErrorKind::Eval(EvalError::BudgetExceeded(err)) => err.diagnostic(),
+    ///     _ => unreachable!(),
+    /// };
+    /// let position = diagnostic.position().unwrap();
+    /// assert_eq!((position.line, position.column), (1, 26));
+    /// assert_eq!(
+    ///     diagnostic.notes()[0].message,
+    ///     "called from `f 1000000` at line 2, column 11"
+    /// );
+    /// ```
+    pub fn diagnostic(&self) -> &Diagnostic {
+        &self.diagnostic
+    }
+}
+
+impl std::fmt::Display for EvalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            EvalError::BudgetExceeded(err) => {
+                write!(f, "{}", err.diagnostic())
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ImportError::ImportAlternatives(errs) => {
+                writeln!(
+                    f,
+                    "Failed to resolve any of the import alternatives:"
+                )?;
+                for (i, err) in errs.iter().enumerate() {
+                    let err = err.to_string().replace('\n', "\n   ");
+                    write!(f, "{}. {}", i + 1, err)?;
+                    if i + 1 < errs.len() {
+                        writeln!(f)?;
+                    }
+                }
+                Ok(())
+            }
+            ImportError::ImportCycle(stack, location) => {
+                writeln!(f, "Import cycle detected:")?;
+                // Only show the part of the stack that is part of the cycle.
+                let start =
+                    stack.iter().position(|loc| loc == location).unwrap_or(0);
+                for (i, loc) in stack[start..].iter().enumerate() {
+                    let arrow = if i == 0 { " " } else { "→" };
+                    writeln!(f, "  {} {}", arrow, loc)?;
+                }
+                write!(f, "  → {}", location)
+            }
+            ImportError::ReferentialSanity(err) => write!(
+                f,
+                "Referential sanity check failed: the remote import `{}` \
+                 cannot import `{}`",
+                err.parent, err.target
+            ),
+            ImportError::Cors(err) => {
+                write!(
+                    f,
+                    "CORS check failed: `{}` does not allow imports from `{}`",
+                    err.url, err.origin
+                )?;
+                match &err.allow_origin {
+                    Some(allowed) => {
+                        write!(f, " (Access-Control-Allow-Origin: {})", allowed)
+                    }
+                    None => {
+                        write!(f, " (no Access-Control-Allow-Origin header)")
+                    }
+                }
+            }
+            ImportError::Offline(url) => {
+                write!(f, "Cannot fetch `{}`: remote imports are disabled", url)
+            }
+            ImportError::Fetch(err) => {
+                write!(f, "Cannot fetch `{}`: ", err.url)?;
+                match &err.kind {
+                    FetchErrorKind::Timeout => {
+                        write!(f, "the request timed out")
+                    }
+                    FetchErrorKind::Status(status) => {
+                        write!(f, "the server responded with status {}", status)
+                    }
+                    FetchErrorKind::Connection(msg) => write!(f, "{}", msg),
+                }
+            }
+            ImportError::LimitExceeded(limit) => {
+                write!(f, "Import limit exceeded: ")?;
+                match limit {
+                    ImportLimit::Depth(n) => {
+                        write!(f, "imports are nested more than {} deep", n)
+                    }
+                    ImportLimit::Imports(n) => {
+                        write!(f, "more than {} imports", n)
+                    }
+                    ImportLimit::Bytes(n) => {
+                        write!(f, "more than {} bytes were read", n)
+                    }
+                }
+            }
+            err => write!(f, "{:?}", err),
         }
     }
 }
@@ -138,3 +656,8 @@ impl From<TypeError> for Error {
         ErrorKind::Typecheck(err).into()
     }
 }
+impl From<EvalError> for Error {
+    fn from(err: EvalError) -> Error {
+        ErrorKind::Eval(err).into()
+    }
+}
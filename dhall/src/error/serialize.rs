@@ -0,0 +1,78 @@
+//! `serde` serialization of parse and type errors, for language servers and other tools that consume
+//! errors programmatically. Enabled by the `serialize-errors` feature.
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+use crate::error::{
+    DiagnosticLabel, DiagnosticNote, ParseError, Severity, SourcePosition,
+    TypeError,
+};
+
+impl Serialize for ParseError {
+    fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        let diagnostic = self.diagnostic();
+        let mut s = ser.serialize_struct("ParseError", 5)?;
+        s.serialize_field("title", diagnostic.title())?;
+        s.serialize_field("position", &diagnostic.position())?;
+        s.serialize_field("expected", self.expected())?;
+        s.serialize_field("labels", diagnostic.labels())?;
+        s.serialize_field("rendered", &diagnostic.to_string())?;
+        s.end()
+    }
+}
+
+impl Serialize for TypeError {
+    fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        let diagnostic = self.diagnostic();
+        let mut s = ser.serialize_struct("TypeError", 7)?;
+        s.serialize_field("code", &self.code())?;
+        s.serialize_field("title", diagnostic.title())?;
+        s.serialize_field("position", &diagnostic.position())?;
+        s.serialize_field("labels", diagnostic.labels())?;
+        s.serialize_field("notes", diagnostic.notes())?;
+        s.serialize_field("explanation", &self.explain())?;
+        s.serialize_field("rendered", &diagnostic.to_string())?;
+        s.end()
+    }
+}
+
+impl Serialize for SourcePosition {
+    fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        let mut s = ser.serialize_struct("SourcePosition", 4)?;
+        s.serialize_field("line", &self.line)?;
+        s.serialize_field("column", &self.column)?;
+        s.serialize_field("end_line", &self.end_line)?;
+        s.serialize_field("end_column", &self.end_column)?;
+        s.end()
+    }
+}
+
+impl Serialize for Severity {
+    fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        ser.serialize_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Info => "info",
+            Severity::Note => "note",
+            Severity::Help => "help",
+        })
+    }
+}
+
+impl Serialize for DiagnosticLabel {
+    fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        let mut s = ser.serialize_struct("DiagnosticLabel", 3)?;
+        s.serialize_field("severity", &self.severity)?;
+        s.serialize_field("position", &self.position)?;
+        s.serialize_field("message", &self.message)?;
+        s.end()
+    }
+}
+
+impl Serialize for DiagnosticNote {
+    fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        let mut s = ser.serialize_struct("DiagnosticNote", 2)?;
+        s.serialize_field("severity", &self.severity)?;
+        s.serialize_field("message", &self.message)?;
+        s.end()
+    }
+}
@@ -0,0 +1,60 @@
+//! The sha256 implementation used for semantic hashes, integrity checks and the import cache.
+//!
+//! By default the pure-Rust implementation from the `sha2` crate is used. Embedders that must
+//! route hashing through a specific provider (e.g. a certified or hardware-backed one) can
+//! install their own backend with `set_backend`, before any hash is computed.
+use once_cell::sync::OnceCell;
+
+/// A sha256 implementation.
+pub trait Sha256Backend: Send + Sync {
+    /// Computes the sha256 digest of `data`.
+    fn digest(&self, data: &[u8]) -> [u8; 32];
+}
+
+/// The default backend, using the `sha2` crate.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultBackend;
+
+impl Sha256Backend for DefaultBackend {
+    fn digest(&self, data: &[u8]) -> [u8; 32] {
+        use sha2::Digest;
+        let mut out = [0; 32];
+        out.copy_from_slice(&sha2::Sha256::digest(data));
+        out
+    }
+}
+
+static BACKEND: OnceCell<Box<dyn Sha256Backend>> = OnceCell::new();
+
+/// Installs the backend to use for all hashing done by this crate. This can only be done once,
+/// and must happen before the first hash is computed: afterwards the backend is fixed and this
+/// returns the given backend back as an error.
+///
+/// # Example
+///
+/// ```
+/// use dhall::hash::{set_backend, DefaultBackend, Sha256Backend};
+///
+/// struct Provider;
+/// impl Sha256Backend for Provider {
+///     fn digest(&self, data: &[u8]) -> [u8; 32] {
+///         // Call into the certified provider here.
+///         DefaultBackend.digest(data)
+///     }
+/// }
+///
+/// assert!(set_backend(Box::new(Provider)).is_ok());
+/// assert!(set_backend(Box::new(Provider)).is_err());
+/// ```
+pub fn set_backend(
+    backend: Box<dyn Sha256Backend>,
+) -> Result<(), Box<dyn Sha256Backend>> {
+    BACKEND.set(backend)
+}
+
+/// Computes the sha256 digest of `data` with the installed backend.
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    BACKEND
+        .get_or_init(|| Box::new(DefaultBackend))
+        .digest(data)
+}
@@ -8,24 +8,40 @@
     clippy::useless_format
 )]
 
+mod shared;
 mod tests;
 
 pub mod builtins;
+pub mod context;
+pub mod convert;
+pub mod daemon;
+pub mod embedded;
 pub mod error;
+pub mod hash;
+pub mod minimize;
 pub mod operations;
+pub mod overlay;
+pub mod sample;
+pub mod schema;
 pub mod semantics;
 pub mod syntax;
+pub mod whnf;
 
 use std::fmt::Display;
+use std::io::Read;
 use std::path::Path;
 use url::Url;
 
-use crate::error::{Error, TypeError};
+use crate::error::{Error, ParseError, TypeDisplayLimits, TypeError};
 use crate::semantics::parse;
 use crate::semantics::resolve;
-use crate::semantics::resolve::ImportLocation;
-use crate::semantics::{typecheck, typecheck_with, Hir, Nir, Tir, Type};
-use crate::syntax::Expr;
+use crate::semantics::resolve::{Cache, ImportEnv, ImportLocation};
+use crate::semantics::{
+    typecheck, typecheck_all, typecheck_tree, typecheck_with,
+    typecheck_with_display_limits, with_eval_budget, with_tracer, Hir, Nir,
+    NirKind, Reduction, Tir, TyEnv, Type, TypedNode,
+};
+use crate::syntax::{Expr, Label, SyntaxTree};
 
 #[derive(Debug, Clone)]
 pub struct Parsed(Expr, ImportLocation);
@@ -56,6 +72,16 @@ pub struct ToExprOptions {
     pub alpha: bool,
 }
 
+/// Controls how expressions are encoded to the binary format.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EncodingMode {
+    /// Rename all bound variables to `_` before encoding. This is the form used for hashing and
+    /// caching.
+    AlphaNormalized,
+    /// Keep the names of bound variables, so that decoding gives back a readable expression.
+    PreserveLabels,
+}
+
 impl Parsed {
     pub fn parse_file(f: &Path) -> Result<Parsed, Error> {
         parse::parse_file(f)
@@ -66,6 +92,46 @@ impl Parsed {
     pub fn parse_str(s: &str) -> Result<Parsed, Error> {
         parse::parse_str(s)
     }
+    /// Like `parse_str`, but allows expressions to nest `max_depth` levels deep instead of
+    /// `syntax::DEFAULT_MAX_NESTING_DEPTH`. Pass `None` to remove the limit, e.g. for trusted
+    /// input; the parser then uses as much stack as the input needs.
+    pub fn parse_str_with_nesting_limit(
+        s: &str,
+        max_depth: Option<usize>,
+    ) -> Result<Parsed, Error> {
+        parse::parse_str_with_nesting_limit(s, max_depth)
+    }
+    /// Parses an expression read from `reader`. The input is read to its end before parsing
+    /// starts, and kept in memory as a whole: the parser can't work on part of it.
+    ///
+    /// ```
+    /// let input: &[u8] = b"{ a = 1 }.a";
+    /// let parsed = dhall::Parsed::parse_reader(input).unwrap();
+    /// assert_eq!(parsed.to_string(), "{ a = 1 }.a");
+    /// ```
+    pub fn parse_reader(reader: impl Read) -> Result<Parsed, Error> {
+        parse::parse_reader(reader)
+    }
+    /// Parses an expression, also returning its concrete syntax tree. The tree keeps whitespace
+    /// and comments, so that tools rewriting the source can preserve them.
+    pub fn parse_str_lossless(s: &str) -> Result<(Parsed, SyntaxTree), Error> {
+        parse::parse_str_lossless(s)
+    }
+    /// Parses an expression, carrying on after syntax errors to report as many as possible.
+    /// Erroneous list elements, record fields, union alternatives and top-level `let` bindings
+    /// are left out of the returned expression, which is `None` if nothing could be salvaged.
+    ///
+    /// ```
+    /// let (parsed, errors) =
+    ///     dhall::Parsed::parse_str_with_recovery("{ a = 1, b = , c = }");
+    /// assert_eq!(parsed.unwrap().to_string(), "{ a = 1 }");
+    /// assert_eq!(errors.len(), 2);
+    /// ```
+    pub fn parse_str_with_recovery(
+        s: &str,
+    ) -> (Option<Parsed>, Vec<ParseError>) {
+        parse::parse_str_with_recovery(s)
+    }
     pub fn parse_binary_file(f: &Path) -> Result<Parsed, Error> {
         parse::parse_binary_file(f)
     }
@@ -77,20 +143,115 @@ impl Parsed {
     pub fn resolve(self) -> Result<Resolved, Error> {
         resolve::resolve(self)
     }
+    /// Resolves imports using the given environment, which caches imports across calls.
+    pub fn resolve_with(self, env: &mut ImportEnv) -> Result<Resolved, Error> {
+        resolve::resolve_with(env, self)
+    }
     pub fn skip_resolve(self) -> Result<Resolved, Error> {
         resolve::skip_resolve(self)
     }
+    /// Rewrites unversioned Prelude imports to point to the given version (e.g. `v17.0.0`),
+    /// optionally adding integrity hashes to them.
+    pub fn pin_prelude(
+        self,
+        version: &str,
+        freeze: bool,
+    ) -> Result<Parsed, Error> {
+        resolve::pin_prelude(self, version, freeze)
+    }
+
+    /// Encodes the expression to the binary format, as written: names of bound variables and
+    /// imports are kept.
+    pub fn encode(&self) -> Result<Vec<u8>, Error> {
+        Ok(crate::syntax::binary::encode(&self.0)?)
+    }
 
     /// Converts a value back to the corresponding AST expression.
     pub fn to_expr(&self) -> Expr {
         self.0.clone()
     }
+    /// Converts a value back to the corresponding AST expression, in canonical form: variables
+    /// are alpha-normalized and import hashes are elided. Printing the result gives a rendering
+    /// that only changes when the meaning of the source does.
+    pub fn to_expr_canonical(&self) -> Expr {
+        self.0.to_canonical()
+    }
 }
 
 impl Resolved {
     pub fn typecheck(&self) -> Result<Typed, TypeError> {
         Ok(Typed::from_tir(typecheck(&self.0)?))
     }
+    /// Like `typecheck`, but prints the types mentioned in errors within the given limits
+    /// instead of the default ones. Use `TypeDisplayLimits::FULL` to print them in full, e.g.
+    /// when the elided part is what matters.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), dhall::error::Error> {
+    /// use dhall::error::TypeDisplayLimits;
+    ///
+    /// let resolved = dhall::Parsed::parse_str("{ a = 1, b = 2, c = 3 } : { a : Natural }")?
+    ///     .resolve()?;
+    /// let limits = TypeDisplayLimits { depth: 6, width: 2 };
+    /// let elided = resolved.typecheck_with_display_limits(limits).unwrap_err();
+    /// assert!(elided.to_string().contains("{ a : Natural, b : Natural, … }"));
+    /// let full = resolved
+    ///     .typecheck_with_display_limits(TypeDisplayLimits::FULL)
+    ///     .unwrap_err();
+    /// assert!(full
+    ///     .to_string()
+    ///     .contains("{ a : Natural, b : Natural, c : Natural }"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn typecheck_with_display_limits(
+        &self,
+        limits: TypeDisplayLimits,
+    ) -> Result<Typed, TypeError> {
+        Ok(Typed::from_tir(typecheck_with_display_limits(
+            &self.0, limits,
+        )?))
+    }
+    /// Like `typecheck`, but reports all the type errors that can be found instead of stopping at
+    /// the first one. Errors in independent parts of the expression, like record fields or list
+    /// elements, are all reported.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), dhall::error::Error> {
+    /// let resolved = dhall::Parsed::parse_str(
+    ///     r#"{ port = 80 + "1", hosts = [ "a", 1 ], debug = True }"#,
+    /// )?
+    /// .resolve()?;
+    /// let errors = resolved.typecheck_all().unwrap_err();
+    /// assert_eq!(errors.len(), 2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn typecheck_all(&self) -> Result<Typed, Vec<TypeError>> {
+        Ok(Typed::from_tir(typecheck_all(&self.0)?))
+    }
+    /// Like `typecheck`, but returns every subexpression along with its inferred type and span.
+    /// This answers questions like "what is the type of the expression under the cursor" without
+    /// typechecking again.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), dhall::error::Error> {
+    /// let src = r#"let wrap = \(n : Natural) -> [ n ] in wrap 1"#;
+    /// let tree = dhall::Parsed::parse_str(src)?.resolve()?.typecheck_tree()?;
+    /// assert_eq!(tree.ty().to_string(), "List Natural");
+    ///
+    /// let cursor = src.find('[').unwrap();
+    /// let node = tree.node_at(cursor).unwrap();
+    /// assert_eq!(node.ty().to_string(), "List Natural");
+    /// let cursor = src.find("wrap 1").unwrap();
+    /// let node = tree.node_at(cursor).unwrap();
+    /// assert_eq!(node.ty().to_string(), "∀(n : Natural) → List Natural");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn typecheck_tree(&self) -> Result<TypedNode, TypeError> {
+        typecheck_tree(&self.0)
+    }
     pub fn typecheck_with(self, ty: &Hir) -> Result<Typed, TypeError> {
         Ok(Typed::from_tir(typecheck_with(&self.0, ty)?))
     }
@@ -107,9 +268,130 @@ impl Typed {
             ty: tir.ty().clone(),
         }
     }
-    /// Reduce an expression to its normal form, performing beta reduction
+    /// Reduce an expression to its normal form, performing beta reduction. This is lazy: parts of
+    /// the value are only evaluated when they are inspected, see `Normalized::head`.
     pub fn normalize(&self) -> Normalized {
-        Normalized(self.hir.eval_closed_expr())
+        let nf = Normalized(self.hir.eval_closed_expr());
+        #[cfg(feature = "self-check")]
+        nf.check_type(&self.ty);
+        nf
+    }
+    /// Like `normalize`, but first looks for the normal form in the given cache, and records it
+    /// there on a miss. Entries are keyed by the input expression, imports included, so repeated
+    /// runs on an unchanged configuration skip normalization entirely. Expressions that use
+    /// builtins provided by the host (see `Context::builtin`) are always normalized, since the
+    /// host may change their implementation. Failing to write to the cache is not an error.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), dhall::error::Error> {
+    /// use dhall::semantics::Cache;
+    ///
+    /// let dir = std::env::temp_dir().join("dhall-normalize-cached-doctest");
+    /// let cache = Cache::with_dir(dir)?;
+    /// let typed = dhall::Parsed::parse_str("λ(x : Natural) → [ x, 1 + 1 ]")?
+    ///     .resolve()?
+    ///     .typecheck()?;
+    /// let first = typed.normalize_cached(&cache);
+    /// let second = typed.normalize_cached(&cache);
+    /// assert_eq!(first.to_string(), "λ(x : Natural) → [x, 2]");
+    /// assert_eq!(second.to_string(), first.to_string());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn normalize_cached(&self, cache: &Cache) -> Normalized {
+        if let Some(nf) = cache.get_normalized(&self.hir) {
+            return Normalized(nf.eval_closed_expr());
+        }
+        let nf = self.normalize();
+        let _ = cache.insert_normalized(&self.hir, &nf.to_hir());
+        nf
+    }
+    /// Like `normalize`, but gives up with `EvalError::BudgetExceeded` after `steps` evaluation
+    /// steps. A step is the evaluation of one subexpression or one function application. Use this
+    /// to evaluate untrusted input, which could otherwise keep the normalizer busy for a very long
+    /// time. Normalization is otherwise lazy; here the whole normal form is computed upfront so
+    /// that it is covered by the budget.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), dhall::error::Error> {
+    /// use dhall::error::{ErrorKind, EvalError};
+    ///
+    /// let typed = |src| -> Result<_, dhall::error::Error> {
+    ///     Ok(dhall::Parsed::parse_str(src)?.resolve()?.typecheck()?)
+    /// };
+    /// let nf = typed("Natural/fold 3 Natural (λ(x : Natural) → x + 2) 0")?
+    ///     .normalize_with_budget(1000)?;
+    /// assert_eq!(nf.to_string(), "6");
+    ///
+    /// let err = typed("Natural/fold 1000000000 Natural (λ(x : Natural) → x + 2) 0")?
+    ///     .normalize_with_budget(1000)
+    ///     .unwrap_err();
+    /// match err.kind() {
+    ///     ErrorKind::Eval(EvalError::BudgetExceeded(err)) => assert_eq!(err.steps, 1000),
+    ///     _ => panic!("unexpected error: {}", err),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn normalize_with_budget(
+        &self,
+        steps: usize,
+    ) -> Result<Normalized, Error> {
+        let nf = with_eval_budget(steps, || {
+            let nf = self.hir.eval_closed_expr();
+            nf.to_hir_noenv();
+            Normalized(nf)
+        })?;
+        #[cfg(feature = "self-check")]
+        nf.check_type(&self.ty);
+        Ok(nf)
+    }
+
+    /// Like `normalize`, but calls `tracer` on every reduction performed along the way: each
+    /// lambda applied to an argument and each builtin that computed a result. This helps finding
+    /// out why a normalization takes so long or gives an unexpected value. The whole normal form
+    /// is computed upfront so that all the reductions get traced.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), dhall::error::Error> {
+    /// use std::cell::RefCell;
+    /// use std::rc::Rc;
+    /// use dhall::semantics::ReductionKind;
+    ///
+    /// let typed = dhall::Parsed::parse_str("(λ(x : Natural) → Natural/even x) 3")?
+    ///     .resolve()?
+    ///     .typecheck()?;
+    /// let log = Rc::new(RefCell::new(Vec::new()));
+    /// let nf = typed.normalize_traced({
+    ///     let log = log.clone();
+    ///     move |r| {
+    ///         let result = r.result().map(|v| v.to_string());
+    ///         log.borrow_mut().push((r.kind().clone(), result));
+    ///     }
+    /// });
+    /// assert_eq!(nf.to_string(), "False");
+    /// assert_eq!(
+    ///     *log.borrow(),
+    ///     vec![
+    ///         (ReductionKind::Builtin(dhall::builtins::Builtin::NaturalEven), Some("False".to_string())),
+    ///         (ReductionKind::Beta, Some("False".to_string())),
+    ///     ]
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn normalize_traced(
+        &self,
+        tracer: impl FnMut(&Reduction<'_>) + 'static,
+    ) -> Normalized {
+        let nf = with_tracer(tracer, || {
+            let nf = self.hir.eval_closed_expr();
+            nf.to_hir_noenv();
+            Normalized(nf)
+        });
+        #[cfg(feature = "self-check")]
+        nf.check_type(&self.ty);
+        nf
     }
 
     /// Converts a value back to the corresponding AST expression.
@@ -120,6 +402,37 @@ impl Typed {
     pub fn ty(&self) -> &Type {
         &self.ty
     }
+    /// The inferred type, as a normalized expression. Printing it gives Dhall source that can be
+    /// saved, e.g. as `schema.dhall`, and used to check later inputs with
+    /// `Resolved::typecheck_with`.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), dhall::error::Error> {
+    /// use dhall::Parsed;
+    ///
+    /// let config = r#"{ name = "db", ports = [ 5432 ] }"#;
+    /// let schema = Parsed::parse_str(config)?
+    ///     .resolve()?
+    ///     .typecheck()?
+    ///     .type_expr()
+    ///     .to_string();
+    /// assert_eq!(schema, "{ name : Text, ports : List Natural }");
+    ///
+    /// // Check another config against the saved schema.
+    /// let schema = Parsed::parse_str(&schema)?
+    ///     .resolve()?
+    ///     .typecheck()?
+    ///     .normalize()
+    ///     .to_hir();
+    /// Parsed::parse_str(r#"{ name = "cache", ports = [] : List Natural }"#)?
+    ///     .resolve()?
+    ///     .typecheck_with(&schema)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn type_expr(&self) -> Expr {
+        self.ty.to_expr_tyenv(&TyEnv::new())
+    }
     pub fn get_type(&self) -> Result<Normalized, TypeError> {
         Ok(Normalized(self.ty.clone().into_nir()))
     }
@@ -137,10 +450,88 @@ impl Normalized {
     pub fn as_nir(&self) -> &Nir {
         &self.0
     }
+    /// Selects a value nested in records by following the given field names. Evaluation is lazy,
+    /// so only the records along the path get evaluated; the other fields are left untouched.
+    /// Returns `None` if a field is missing or a value along the path isn't a record literal.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), dhall::error::Error> {
+    /// let config = dhall::Parsed::parse_str(
+    ///     "{ server = { port = 40 + 2 }, tests = List/length Natural [1, 2] }",
+    /// )?
+    /// .resolve()?
+    /// .typecheck()?
+    /// .normalize();
+    /// let port = config.get_path(&["server", "port"]).unwrap();
+    /// assert_eq!(port.to_string(), "42");
+    /// assert!(config.get_path(&["server", "host"]).is_none());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_path(&self, path: &[&str]) -> Option<Normalized> {
+        let mut nir = self.0.clone();
+        for field in path {
+            nir = match nir.kind() {
+                NirKind::RecordLit(kvs) => {
+                    kvs.get(&Label::from(*field))?.clone()
+                }
+                _ => return None,
+            };
+        }
+        Some(Normalized(nir))
+    }
     /// Converts a value back to the corresponding AST expression, alpha-normalizing in the process.
     pub fn to_expr_alpha(&self) -> Expr {
         self.0.to_expr(ToExprOptions { alpha: true })
     }
+    /// Encodes the expression to the binary format.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), dhall::error::Error> {
+    /// use dhall::{EncodingMode, Parsed};
+    ///
+    /// let expr = Parsed::parse_str(r"\(x : Natural) -> x")?
+    ///     .resolve()?
+    ///     .typecheck()?
+    ///     .normalize();
+    /// let data = expr.encode(EncodingMode::PreserveLabels)?;
+    /// assert_eq!(Parsed::parse_binary(&data)?.to_string(), "λ(x : Natural) → x");
+    /// let data = expr.encode(EncodingMode::AlphaNormalized)?;
+    /// assert_eq!(Parsed::parse_binary(&data)?.to_string(), "λ(_ : Natural) → _");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn encode(&self, mode: EncodingMode) -> Result<Vec<u8>, Error> {
+        let expr = match mode {
+            EncodingMode::AlphaNormalized => self.to_expr_alpha(),
+            EncodingMode::PreserveLabels => self.to_expr(),
+        };
+        Ok(crate::syntax::binary::encode(&expr)?)
+    }
+    /// Checks that normalization preserved the type of the expression. Failures are bugs in the
+    /// typechecker or the normalizer, so this panics.
+    #[cfg(feature = "self-check")]
+    fn check_type(&self, ty: &Type) {
+        let hir = self.to_hir();
+        let env = TyEnv::new();
+        match typecheck(&hir) {
+            Ok(tir) if tir.ty() == ty => {}
+            Ok(tir) => panic!(
+                "internal type error: normalization changed the type from `{}` to `{}`",
+                ty.to_expr_tyenv(&env),
+                tir.ty().to_expr_tyenv(&env),
+            ),
+            Err(err) => panic!(
+                "internal type error: the normal form `{}` doesn't typecheck: {}",
+                self, err
+            ),
+        }
+    }
+    /// Computes the semantic hash of the expression. This always uses the alpha-normalized
+    /// encoding, whatever mode is used to store the expression.
+    pub fn hash(&self) -> Result<Box<[u8]>, Error> {
+        self.to_expr_alpha().hash()
+    }
 }
 
 macro_rules! derive_traits_for_wrapper_struct {
@@ -0,0 +1,147 @@
+//! Shrinking of expressions that trigger a bug, to produce small reproducers for bug reports.
+use std::collections::BTreeMap;
+
+use crate::syntax::{Expr, ExprKind, InterpolatedText, Label, NumKind};
+
+/// Shrinks `expr` while `failing` keeps holding, and returns the smallest expression found. The
+/// predicate typically checks for the bug being reported, e.g. that typechecking panics or that
+/// the result of normalization differs from a reference implementation.
+///
+/// The expression is shrunk greedily, one step at a time: a step replaces a subexpression with
+/// one of its children, removes list elements or record fields, or replaces a literal with a
+/// trivial one. Larger reductions are tried first. The result is minimal in the sense that no
+/// single step keeps the predicate true.
+///
+/// # Example
+///
+/// ```
+/// use dhall::minimize::minimize;
+/// use dhall::syntax::parse_expr;
+///
+/// let typechecks = |e: &dhall::syntax::Expr| {
+///     dhall::Parsed::parse_str(&e.to_string())
+///         .and_then(|p| p.resolve())
+///         .and_then(|r| Ok(r.typecheck()?))
+///         .is_ok()
+/// };
+/// let expr = parse_expr(
+///     r#"{ name = "db", ports = [ 80, 443 ] # [ "8080" ], tls = True }"#,
+/// )
+/// .unwrap();
+/// let min = minimize(&expr, |e| !typechecks(e));
+/// assert_eq!(min.to_string(), r#"0 # """#);
+/// ```
+pub fn minimize(expr: &Expr, mut failing: impl FnMut(&Expr) -> bool) -> Expr {
+    let mut expr = expr.clone();
+    while let Some(smaller) = shrink_once(&expr, &mut failing) {
+        expr = smaller;
+    }
+    expr
+}
+
+/// Finds a single shrinking step of `expr` for which `failing` holds.
+fn shrink_once(
+    expr: &Expr,
+    failing: &mut dyn FnMut(&Expr) -> bool,
+) -> Option<Expr> {
+    if let Some(smaller) = local_shrinks(expr).into_iter().find(|e| failing(e))
+    {
+        return Some(smaller);
+    }
+    for (i, child) in children(expr).iter().enumerate() {
+        let mut failing_child = |c: &Expr| failing(&replace_child(expr, i, c));
+        if let Some(smaller) = shrink_once(child, &mut failing_child) {
+            return Some(replace_child(expr, i, &smaller));
+        }
+    }
+    None
+}
+
+fn children(expr: &Expr) -> Vec<Expr> {
+    let mut children = Vec::new();
+    expr.kind().map_ref(|c| children.push(c.clone()));
+    children
+}
+
+fn replace_child(expr: &Expr, i: usize, new: &Expr) -> Expr {
+    let mut n = 0;
+    let kind = expr.kind().map_ref(|c| {
+        n += 1;
+        if n - 1 == i {
+            new.clone()
+        } else {
+            c.clone()
+        }
+    });
+    Expr::new(kind, expr.span())
+}
+
+/// The chunks of `0..len` to try removing, from the halves down to single elements.
+fn chunks(len: usize) -> Vec<(usize, usize)> {
+    let mut chunks = Vec::new();
+    let mut size = len / 2;
+    while size > 0 {
+        let mut start = 0;
+        while start < len {
+            chunks.push((start, (start + size).min(len)));
+            start += size;
+        }
+        size /= 2;
+    }
+    chunks
+}
+
+fn remove_fields<T: Clone>(
+    map: &BTreeMap<Label, T>,
+) -> Vec<BTreeMap<Label, T>> {
+    chunks(map.len())
+        .into_iter()
+        .map(|(start, end)| {
+            map.iter()
+                .enumerate()
+                .filter(|(i, _)| *i < start || *i >= end)
+                .map(|(_, (k, v))| (k.clone(), v.clone()))
+                .collect()
+        })
+        .collect()
+}
+
+/// The shrinking steps that apply to the root of `expr`, largest first.
+fn local_shrinks(expr: &Expr) -> Vec<Expr> {
+    let mk = |kind| Expr::new(kind, expr.span());
+    let mut shrinks = children(expr);
+    match expr.kind() {
+        ExprKind::NEListLit(elts) => {
+            for (start, end) in chunks(elts.len()) {
+                let mut elts = elts.clone();
+                elts.drain(start..end);
+                shrinks.push(mk(ExprKind::NEListLit(elts)));
+            }
+        }
+        ExprKind::RecordLit(kvs) => shrinks.extend(
+            remove_fields(kvs)
+                .into_iter()
+                .map(|kvs| mk(ExprKind::RecordLit(kvs))),
+        ),
+        ExprKind::RecordType(kts) => shrinks.extend(
+            remove_fields(kts)
+                .into_iter()
+                .map(|kts| mk(ExprKind::RecordType(kts))),
+        ),
+        ExprKind::UnionType(kts) => shrinks.extend(
+            remove_fields(kts)
+                .into_iter()
+                .map(|kts| mk(ExprKind::UnionType(kts))),
+        ),
+        ExprKind::Num(NumKind::Natural(n)) if *n != 0 => {
+            shrinks.push(mk(ExprKind::Num(NumKind::Natural(0))))
+        }
+        ExprKind::Num(NumKind::Integer(n)) if *n != 0 => {
+            shrinks.push(mk(ExprKind::Num(NumKind::Integer(0))))
+        }
+        ExprKind::TextLit(txt) if !txt.is_empty() => shrinks
+            .push(mk(ExprKind::TextLit(InterpolatedText::from(String::new())))),
+        _ => {}
+    }
+    shrinks
+}
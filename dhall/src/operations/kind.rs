@@ -6,6 +6,8 @@ use crate::syntax::{trivial_result, Label};
 // pretty-printing to work correctly
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum BinOp {
+    /// `x === y`
+    Equivalence,
     /// `x ? y`
     ImportAlt,
     /// `x || y`
@@ -30,8 +32,6 @@ pub enum BinOp {
     BoolEQ,
     /// `x != y`
     BoolNE,
-    /// x === y
-    Equivalence,
 }
 
 /// Operations
@@ -55,6 +55,8 @@ pub enum OpKind<SubExpr> {
     ProjectionByExpr(SubExpr, SubExpr),
     ///  `x::y`
     Completion(SubExpr, SubExpr),
+    ///  `x with a.b.c = y`
+    With(SubExpr, Vec<Label>, SubExpr),
 }
 
 impl<SE> OpKind<SE> {
@@ -85,6 +87,7 @@ impl<SE> OpKind<SE> {
             Projection(e, ls) => Projection(expr!(e), ls.clone()),
             ProjectionByExpr(e, x) => ProjectionByExpr(expr!(e), expr!(x)),
             Completion(e, x) => Completion(expr!(e), expr!(x)),
+            With(e, ls, x) => With(expr!(e), ls.clone(), expr!(x)),
         })
     }
 
@@ -1,5 +1,4 @@
-use itertools::Itertools;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::iter::once;
 
 use crate::operations::{BinOp, OpKind};
@@ -13,6 +12,11 @@ fn normalize_binop(o: BinOp, x: &Nir, y: &Nir) -> Ret {
     use NirKind::{EmptyListLit, NEListLit, Num, RecordLit, RecordType};
     use NumKind::{Bool, Natural};
 
+    // Both sides are kept as they are, so that building an equivalence doesn't force them.
+    if o == Equivalence {
+        return ret_kind(NirKind::Equivalence(x.clone(), y.clone()));
+    }
+
     match (o, x.kind(), y.kind()) {
         (BoolAnd, Num(Bool(true)), _) => ret_ref(y),
         (BoolAnd, _, Num(Bool(true))) => ret_ref(x),
@@ -49,7 +53,7 @@ fn normalize_binop(o: BinOp, x: &Nir, y: &Nir) -> Ret {
         (ListAppend, EmptyListLit(_), _) => ret_ref(y),
         (ListAppend, _, EmptyListLit(_)) => ret_ref(x),
         (ListAppend, NEListLit(xs), NEListLit(ys)) => {
-            ret_kind(NEListLit(xs.iter().chain(ys.iter()).cloned().collect()))
+            ret_kind(NEListLit(xs.concat(ys)))
         }
 
         (TextAppend, NirKind::TextLit(x), _) if x.is_empty() => ret_ref(y),
@@ -113,10 +117,6 @@ fn normalize_binop(o: BinOp, x: &Nir, y: &Nir) -> Ret {
             ret_kind(RecordType(kts))
         }
 
-        (Equivalence, _, _) => {
-            ret_kind(NirKind::Equivalence(x.clone(), y.clone()))
-        }
-
         _ => ret_op(OpKind::BinOp(o, x.clone(), y.clone())),
     }
 }
@@ -249,9 +249,8 @@ pub fn normalize_operation(opkind: &OpKind<Nir>) -> Ret {
             }
             RecordLit(kvs) => ret_kind(NEListLit(
                 kvs.iter()
-                    .sorted_by_key(|(k, _)| *k)
                     .map(|(k, v)| {
-                        let mut rec = HashMap::new();
+                        let mut rec = BTreeMap::new();
                         rec.insert("mapKey".into(), Nir::from_text(k));
                         rec.insert("mapValue".into(), v.clone());
                         Nir::from_kind(NirKind::RecordLit(rec))
@@ -262,7 +261,7 @@ pub fn normalize_operation(opkind: &OpKind<Nir>) -> Ret {
         },
         Field(v, field) => normalize_field(v, field),
         Projection(_, ls) if ls.is_empty() => {
-            ret_kind(RecordLit(HashMap::new()))
+            ret_kind(RecordLit(BTreeMap::new()))
         }
         Projection(v, ls) => match v.kind() {
             RecordLit(kvs) => ret_kind(RecordLit(
@@ -299,8 +298,38 @@ pub fn normalize_operation(opkind: &OpKind<Nir>) -> Ret {
             )),
             _ => nothing_to_do(),
         },
-        Completion(..) => {
-            unreachable!("This case should have been handled in resolution")
+        With(e, ls, v) => match e.kind() {
+            RecordLit(kvs) => {
+                let (l, rest) = ls.split_first().unwrap();
+                let field = if rest.is_empty() {
+                    v.clone()
+                } else {
+                    let inner = kvs.get(l).cloned().unwrap_or_else(|| {
+                        Nir::from_kind(RecordLit(BTreeMap::new()))
+                    });
+                    Nir::from_partial_expr(ExprKind::Op(With(
+                        inner,
+                        rest.to_vec(),
+                        v.clone(),
+                    )))
+                };
+                let mut kvs = kvs.clone();
+                kvs.insert(l.clone(), field);
+                ret_kind(RecordLit(kvs))
+            }
+            _ => nothing_to_do(),
+        },
+        // `T::r` is `(T.default ⫽ r) : T.Type`, and annotations disappear when normalizing.
+        Completion(ty, compl) => {
+            let default = Nir::from_kind(normalize_operation(&Field(
+                ty.clone(),
+                "default".into(),
+            )));
+            normalize_operation(&BinOp(
+                RightBiasedRecordMerge,
+                default,
+                compl.clone(),
+            ))
         }
     }
 }
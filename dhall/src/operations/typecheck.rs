@@ -1,15 +1,15 @@
 use std::borrow::Cow;
 use std::cmp::max;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 use crate::builtins::Builtin;
-use crate::error::{ErrorBuilder, TypeError};
+use crate::error::{elide_type, ErrorBuilder, TypeError, TypeErrorKind};
 use crate::operations::{BinOp, OpKind};
 use crate::semantics::{
-    merge_maps, mk_span_err, mkerr, Binder, Closure, Hir, HirKind, Nir,
-    NirKind, Tir, TyEnv, Type,
+    merge_maps, mk_span_err, mk_subexpr_err, mkerr, Binder, Closure, Hir,
+    HirKind, Nir, NirKind, Tir, TyEnv, Type,
 };
-use crate::syntax::{Const, ExprKind, Span};
+use crate::syntax::{Const, ExprKind, Label, Span};
 
 fn check_rectymerge(
     span: &Span,
@@ -22,7 +22,7 @@ fn check_rectymerge(
         _ => {
             return mk_span_err(
                 span.clone(),
-                "RecordTypeMergeRequiresRecordType",
+                TypeErrorKind::RecordTypeMergeRequiresRecordType,
             )
         }
     };
@@ -31,7 +31,7 @@ fn check_rectymerge(
         _ => {
             return mk_span_err(
                 span.clone(),
-                "RecordTypeMergeRequiresRecordType",
+                TypeErrorKind::RecordTypeMergeRequiresRecordType,
             )
         }
     };
@@ -51,7 +51,9 @@ fn typecheck_binop(
     l: &Tir<'_>,
     r: &Tir<'_>,
 ) -> Result<Type, TypeError> {
-    let span_err = |msg: &str| mk_span_err(span.clone(), msg);
+    let span_err = |kind| mk_span_err(span.clone(), kind);
+    let subexpr_err =
+        |sub: &Tir<'_>, kind| mk_subexpr_err(env, span.clone(), sub, kind);
     use BinOp::*;
     use NirKind::{ListType, RecordType};
 
@@ -63,12 +65,12 @@ fn typecheck_binop(
             // Extract the LHS record type
             let kts_x = match x_type.kind() {
                 RecordType(kts) => kts,
-                _ => return span_err("MustCombineRecord"),
+                _ => return span_err(TypeErrorKind::MustCombineRecord),
             };
             // Extract the RHS record type
             let kts_y = match y_type.kind() {
                 RecordType(kts) => kts,
-                _ => return span_err("MustCombineRecord"),
+                _ => return span_err(TypeErrorKind::MustCombineRecord),
             };
 
             // Union the two records, prefering
@@ -79,7 +81,41 @@ fn typecheck_binop(
             Nir::from_kind(RecordType(kts)).to_type(u)
         }
         RecursiveRecordMerge => {
-            check_rectymerge(&span, env, l.ty().to_nir(), r.ty().to_nir())?;
+            let check =
+                check_rectymerge(&span, env, l.ty().to_nir(), r.ty().to_nir());
+            if let (Err(_), Span::DuplicateRecordFieldsSugar(field)) =
+                (&check, &span)
+            {
+                return mkerr(
+                    ErrorBuilder::new(format!(
+                        "duplicate field `{}` can't be merged",
+                        field
+                    ))
+                    .kind(TypeErrorKind::DuplicateFieldCannotBeMerged)
+                    .span_help(
+                        l.span(),
+                        format!(
+                            "`{}` is first defined here, with type: `{}`",
+                            field,
+                            elide_type(env, &l.ty().to_expr_tyenv(env))
+                        ),
+                    )
+                    .span_err(
+                        r.span(),
+                        format!(
+                            "and defined again here, with type: `{}`",
+                            elide_type(env, &r.ty().to_expr_tyenv(env))
+                        ),
+                    )
+                    .help(
+                        "the values of a field defined several times are \
+                         combined with `∧`, so they must be records whose \
+                         common fields can themselves be combined",
+                    )
+                    .format(),
+                );
+            }
+            check?;
 
             let hir = Hir::new(
                 HirKind::Expr(ExprKind::Op(OpKind::BinOp(
@@ -104,21 +140,23 @@ fn typecheck_binop(
         ListAppend => {
             match l.ty().kind() {
                 ListType(..) => {}
-                _ => return span_err("BinOpTypeMismatch"),
+                _ => return subexpr_err(l, TypeErrorKind::BinOpTypeMismatch),
             }
 
             if l.ty() != r.ty() {
-                return span_err("BinOpTypeMismatch");
+                return subexpr_err(r, TypeErrorKind::BinOpTypeMismatch);
             }
 
             l.ty().clone()
         }
         Equivalence => {
             if l.ty() != r.ty() {
-                return span_err("EquivalenceTypeMismatch");
+                return span_err(TypeErrorKind::EquivalenceTypeMismatch);
             }
             if l.ty().ty().as_const() != Some(Const::Type) {
-                return span_err("EquivalenceArgumentsMustBeTerms");
+                return span_err(
+                    TypeErrorKind::EquivalenceArgumentsMustBeTerms,
+                );
             }
 
             Type::from_const(Const::Type)
@@ -137,11 +175,11 @@ fn typecheck_binop(
             });
 
             if *l.ty() != t {
-                return span_err("BinOpTypeMismatch");
+                return subexpr_err(l, TypeErrorKind::BinOpTypeMismatch);
             }
 
             if *r.ty() != t {
-                return span_err("BinOpTypeMismatch");
+                return subexpr_err(r, TypeErrorKind::BinOpTypeMismatch);
             }
 
             t
@@ -149,6 +187,18 @@ fn typecheck_binop(
     })
 }
 
+/// The span of the union type that `scrut` is built from, when it is written in the expression,
+/// as in `< A | B >.A` or `x : < A | B >`.
+fn union_definition_span(scrut: &Hir) -> Option<Span> {
+    match scrut.kind() {
+        HirKind::Expr(ExprKind::UnionType(_)) => Some(scrut.span()),
+        HirKind::Expr(ExprKind::Op(OpKind::Field(e, _)))
+        | HirKind::Expr(ExprKind::Op(OpKind::App(e, _)))
+        | HirKind::Expr(ExprKind::Annot(_, e)) => union_definition_span(e),
+        _ => None,
+    }
+}
+
 fn typecheck_merge(
     env: &TyEnv,
     span: Span,
@@ -156,27 +206,82 @@ fn typecheck_merge(
     scrut: &Tir<'_>,
     type_annot: Option<&Tir<'_>>,
 ) -> Result<Type, TypeError> {
-    let span_err = |msg: &str| mk_span_err(span.clone(), msg);
+    let span_err = |kind| mk_span_err(span.clone(), kind);
     use NirKind::{OptionalType, PiClosure, RecordType, UnionType};
 
     let record_type = record.ty();
     let handlers = match record_type.kind() {
         RecordType(kts) => kts,
-        _ => return span_err("Merge1ArgMustBeRecord"),
+        _ => return span_err(TypeErrorKind::Merge1ArgMustBeRecord),
     };
 
     let scrut_type = scrut.ty();
     let variants = match scrut_type.kind() {
         UnionType(kts) => Cow::Borrowed(kts),
         OptionalType(ty) => {
-            let mut kts = HashMap::new();
+            let mut kts = BTreeMap::new();
             kts.insert("None".into(), None);
             kts.insert("Some".into(), Some(ty.clone()));
             Cow::Owned(kts)
         }
-        _ => return span_err("Merge2ArgMustBeUnionOrOptional"),
+        _ => return span_err(TypeErrorKind::Merge2ArgMustBeUnionOrOptional),
     };
 
+    let extra: Vec<_> = handlers
+        .keys()
+        .filter(|x| !variants.contains_key(*x))
+        .collect();
+    let missing: Vec<_> = variants
+        .keys()
+        .filter(|x| !handlers.contains_key(*x))
+        .collect();
+    if !extra.is_empty() || !missing.is_empty() {
+        let list = |xs: &[&Label]| {
+            xs.iter()
+                .map(|x| format!("`{}`", x))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let kind = if extra.is_empty() {
+            TypeErrorKind::MergeVariantMissingHandler
+        } else {
+            TypeErrorKind::MergeHandlerMissingVariant
+        };
+        let mut err = ErrorBuilder::new(format!("{:?}", kind));
+        err.kind(kind).span_err(span, "in this merge expression");
+        match record.as_hir().kind() {
+            // Point at each handler that has no alternative.
+            HirKind::Expr(ExprKind::RecordLit(kvs)) => {
+                for x in &extra {
+                    err.span_err(
+                        kvs[*x].span(),
+                        format!("there is no alternative `{}`", x),
+                    );
+                }
+            }
+            _ if !extra.is_empty() => {
+                err.span_err(
+                    record.span(),
+                    format!(
+                        "no alternative for the handlers: {}",
+                        list(&extra)
+                    ),
+                );
+            }
+            _ => {}
+        }
+        if !missing.is_empty() {
+            err.span_err(
+                scrut.span(),
+                format!("no handler for the alternatives: {}", list(&missing)),
+            );
+            if let Some(def) = union_definition_span(scrut.as_hir()) {
+                err.span_help(def, "the alternatives are defined here");
+            }
+        }
+        return mkerr(err.format());
+    }
+
     let mut inferred_type = None;
     for (x, handler_type) in handlers {
         let handler_return_type: Type = match variants.get(x) {
@@ -188,6 +293,7 @@ fn typecheck_merge(
                             ErrorBuilder::new(format!(
                                 "Wrong handler input type"
                             ))
+                            .kind(TypeErrorKind::MergeHandlerInputType)
                             .span_err(
                                 span,
                                 format!("in this merge expression",),
@@ -198,7 +304,7 @@ fn typecheck_merge(
                                     "the handler for `{}` expects a value of \
                                      type: `{}`",
                                     x,
-                                    annot.to_expr_tyenv(env)
+                                    elide_type(env, &annot.to_expr_tyenv(env))
                                 ),
                             )
                             .span_err(
@@ -206,18 +312,38 @@ fn typecheck_merge(
                                 format!(
                                     "but the corresponding variant has type: \
                                      `{}`",
-                                    variant_type.to_expr_tyenv(env)
+                                    elide_type(
+                                        env,
+                                        &variant_type.to_expr_tyenv(env)
+                                    )
                                 ),
                             )
                             .format(),
                         );
                     }
 
-                    // TODO: this actually doesn't check anything yet
                     match closure.remove_binder() {
                         Ok(v) => Type::new_infer_universe(env, v.clone())?,
-                        Err(()) => {
-                            return span_err("MergeReturnTypeIsDependent")
+                        Err(uses) => {
+                            let mut err =
+                                ErrorBuilder::new("MergeReturnTypeIsDependent");
+                            err.kind(TypeErrorKind::MergeReturnTypeIsDependent)
+                                .span_err(span, "in this merge expression")
+                                .span_err(
+                                    record.span(),
+                                    format!(
+                                        "the type returned by the handler \
+                                         for `{}` depends on its argument",
+                                        x
+                                    ),
+                                );
+                            for use_span in uses {
+                                err.span_help(
+                                    use_span,
+                                    "the argument is used here",
+                                );
+                            }
+                            return mkerr(err.format());
                         }
                     }
                 }
@@ -226,26 +352,33 @@ fn typecheck_merge(
                         ErrorBuilder::new(format!(
                             "merge handler is not a function"
                         ))
+                        .kind(TypeErrorKind::MergeHandlerNotFunction)
                         .span_err(span, format!("in this merge expression"))
                         .span_err(
                             record.span(),
                             format!(
                                 "the handler for `{}` has type: `{}`",
                                 x,
-                                handler_type.to_expr_tyenv(env)
+                                elide_type(
+                                    env,
+                                    &handler_type.to_expr_tyenv(env)
+                                )
                             ),
                         )
                         .span_help(
                             scrut.span(),
                             format!(
                                 "the corresponding variant has type: `{}`",
-                                variant_type.to_expr_tyenv(env)
+                                elide_type(
+                                    env,
+                                    &variant_type.to_expr_tyenv(env)
+                                )
                             ),
                         )
                         .help(format!(
                             "a handler for this variant must be a function \
                              that takes an input of type: `{}`",
-                            variant_type.to_expr_tyenv(env)
+                            elide_type(env, &variant_type.to_expr_tyenv(env))
                         ))
                         .format(),
                     )
@@ -253,23 +386,17 @@ fn typecheck_merge(
             },
             // Union alternative without type
             Some(None) => Type::new_infer_universe(env, handler_type.clone())?,
-            None => return span_err("MergeHandlerMissingVariant"),
+            None => unreachable!("extra handlers were reported above"),
         };
         match &inferred_type {
             None => inferred_type = Some(handler_return_type),
             Some(t) => {
                 if t != &handler_return_type {
-                    return span_err("MergeHandlerTypeMismatch");
+                    return span_err(TypeErrorKind::MergeHandlerTypeMismatch);
                 }
             }
         }
     }
-    for x in variants.keys() {
-        if !handlers.contains_key(x) {
-            return span_err("MergeVariantMissingHandler");
-        }
-    }
-
     let type_annot = type_annot
         .as_ref()
         .map(|t| t.eval_to_type(env))
@@ -277,13 +404,15 @@ fn typecheck_merge(
     Ok(match (inferred_type, type_annot) {
         (Some(t1), Some(t2)) => {
             if t1 != t2 {
-                return span_err("MergeAnnotMismatch");
+                return span_err(TypeErrorKind::MergeAnnotMismatch);
             }
             t1
         }
         (Some(t), None) => t,
         (None, Some(t)) => t,
-        (None, None) => return span_err("MergeEmptyNeedsAnnotation"),
+        (None, None) => {
+            return span_err(TypeErrorKind::MergeEmptyNeedsAnnotation)
+        }
     })
 }
 
@@ -292,7 +421,18 @@ pub fn typecheck_operation(
     span: Span,
     opkind: &OpKind<Tir<'_>>,
 ) -> Result<Type, TypeError> {
-    let span_err = |msg: &str| mk_span_err(span.clone(), msg);
+    let span_err = |kind| mk_span_err(span.clone(), kind);
+    let subexpr_err =
+        |sub: &Tir<'_>, kind| mk_subexpr_err(env, span.clone(), sub, kind);
+    // For errors whose title says more than the name of their kind.
+    let titled_err = |kind, msg: &str| {
+        mkerr(
+            ErrorBuilder::new(msg)
+                .kind(kind)
+                .span_err(span.clone(), msg)
+                .format(),
+        )
+    };
     use NirKind::{ListType, PiClosure, RecordType, UnionType};
     use OpKind::*;
 
@@ -306,24 +446,28 @@ pub fn typecheck_operation(
                             ErrorBuilder::new(format!(
                                 "wrong type of function argument"
                             ))
+                            .kind(TypeErrorKind::TypeMismatch)
                             .span_err(
                                 f.span(),
                                 format!(
                                     "this expects an argument of type: {}",
-                                    annot.to_expr_tyenv(env),
+                                    elide_type(env, &annot.to_expr_tyenv(env)),
                                 ),
                             )
                             .span_err(
                                 arg.span(),
                                 format!(
                                     "but this has type: {}",
-                                    arg.ty().to_expr_tyenv(env),
+                                    elide_type(
+                                        env,
+                                        &arg.ty().to_expr_tyenv(env)
+                                    ),
                                 ),
                             )
                             .note(format!(
                                 "expected type `{}`\n   found type `{}`",
-                                annot.to_expr_tyenv(env),
-                                arg.ty().to_expr_tyenv(env),
+                                elide_type(env, &annot.to_expr_tyenv(env)),
+                                elide_type(env, &arg.ty().to_expr_tyenv(env)),
                             ))
                             .format(),
                         );
@@ -335,8 +479,9 @@ pub fn typecheck_operation(
                 _ => return mkerr(
                     ErrorBuilder::new(format!(
                         "expected function, found `{}`",
-                        f.ty().to_expr_tyenv(env)
+                        elide_type(env, &f.ty().to_expr_tyenv(env))
                     ))
+                    .kind(TypeErrorKind::NotAFunction)
                     .span_err(
                         f.span(),
                         format!("function application requires a function",),
@@ -348,13 +493,13 @@ pub fn typecheck_operation(
         BinOp(o, l, r) => typecheck_binop(env, span, *o, l, r)?,
         BoolIf(x, y, z) => {
             if *x.ty().kind() != NirKind::from_builtin(Builtin::Bool) {
-                return span_err("InvalidPredicate");
+                return subexpr_err(x, TypeErrorKind::InvalidPredicate);
             }
             if y.ty().ty().as_const() != Some(Const::Type) {
-                return span_err("IfBranchMustBeTerm");
+                return subexpr_err(y, TypeErrorKind::IfBranchMustBeTerm);
             }
             if y.ty() != z.ty() {
-                return span_err("IfBranchMismatch");
+                return subexpr_err(z, TypeErrorKind::IfBranchMismatch);
             }
 
             y.ty().clone()
@@ -364,13 +509,19 @@ pub fn typecheck_operation(
         }
         ToMap(record, annot) => {
             if record.ty().ty().as_const() != Some(Const::Type) {
-                return span_err("`toMap` only accepts records of type `Type`");
+                return titled_err(
+                    TypeErrorKind::InvalidToMapRecordKind,
+                    "`toMap` only accepts records of type `Type`",
+                );
             }
             let record_t = record.ty();
             let kts = match record_t.kind() {
                 RecordType(kts) => kts,
                 _ => {
-                    return span_err("The argument to `toMap` must be a record")
+                    return titled_err(
+                        TypeErrorKind::ToMapNotARecord,
+                        "The argument to `toMap` must be a record",
+                    )
                 }
             };
 
@@ -378,46 +529,53 @@ pub fn typecheck_operation(
                 let annot = if let Some(annot) = annot {
                     annot
                 } else {
-                    return span_err(
+                    return titled_err(
+                        TypeErrorKind::MissingToMapType,
                         "`toMap` applied to an empty record requires a type \
                          annotation",
                     );
                 };
                 let annot_val = annot.eval_to_type(env)?;
 
-                let err_msg = "The type of `toMap x` must be of the form \
-                               `List { mapKey : Text, mapValue : T }`";
+                let err = || {
+                    titled_err(
+                        TypeErrorKind::InvalidToMapType,
+                        "The type of `toMap x` must be of the form \
+                         `List { mapKey : Text, mapValue : T }`",
+                    )
+                };
                 let arg = match annot_val.kind() {
                     ListType(t) => t,
-                    _ => return span_err(err_msg),
+                    _ => return err(),
                 };
                 let kts = match arg.kind() {
                     RecordType(kts) => kts,
-                    _ => return span_err(err_msg),
+                    _ => return err(),
                 };
                 if kts.len() != 2 {
-                    return span_err(err_msg);
+                    return err();
                 }
                 match kts.get("mapKey") {
                     Some(t) if *t == Nir::from_builtin(Builtin::Text) => {}
-                    _ => return span_err(err_msg),
+                    _ => return err(),
                 }
                 match kts.get("mapValue") {
                     Some(_) => {}
-                    None => return span_err(err_msg),
+                    None => return err(),
                 }
                 annot_val
             } else {
                 let entry_type = kts.iter().next().unwrap().1.clone();
                 for (_, t) in kts.iter() {
                     if *t != entry_type {
-                        return span_err(
+                        return titled_err(
+                            TypeErrorKind::HeterogenousRecordToMap,
                             "Every field of the record must have the same type",
                         );
                     }
                 }
 
-                let mut kts = HashMap::new();
+                let mut kts = BTreeMap::new();
                 kts.insert("mapKey".into(), Nir::from_builtin(Builtin::Text));
                 kts.insert("mapValue".into(), entry_type);
                 let output_type: Type = Nir::from_builtin(Builtin::List)
@@ -426,7 +584,10 @@ pub fn typecheck_operation(
                 if let Some(annot) = annot {
                     let annot_val = annot.eval_to_type(env)?;
                     if output_type != annot_val {
-                        return span_err("Annotation mismatch");
+                        return titled_err(
+                            TypeErrorKind::MapTypeMismatch,
+                            "Annotation mismatch",
+                        );
                     }
                 }
                 output_type
@@ -436,7 +597,7 @@ pub fn typecheck_operation(
             match scrut.ty().kind() {
                 RecordType(kts) => match kts.get(x) {
                     Some(val) => Type::new_infer_universe(env, val.clone())?,
-                    None => return span_err("MissingRecordField"),
+                    None => return span_err(TypeErrorKind::MissingRecordField),
                 },
                 NirKind::Const(_) => {
                     let scrut = scrut.eval_to_type(env)?;
@@ -450,25 +611,31 @@ pub fn typecheck_operation(
                             })
                             .to_type(scrut.ty()),
                             Some(None) => scrut,
-                            None => return span_err("MissingUnionField"),
+                            None => {
+                                return span_err(
+                                    TypeErrorKind::MissingUnionField,
+                                )
+                            }
                         },
-                        _ => return span_err("NotARecord"),
+                        _ => return span_err(TypeErrorKind::NotARecord),
                     }
                 }
-                _ => return span_err("NotARecord"),
+                _ => return span_err(TypeErrorKind::NotARecord),
             }
         }
         Projection(record, labels) => {
             let record_type = record.ty();
             let kts = match record_type.kind() {
                 RecordType(kts) => kts,
-                _ => return span_err("ProjectionMustBeRecord"),
+                _ => return span_err(TypeErrorKind::ProjectionMustBeRecord),
             };
 
-            let mut new_kts = HashMap::new();
+            let mut new_kts = BTreeMap::new();
             for l in labels {
                 match kts.get(l) {
-                    None => return span_err("ProjectionMissingEntry"),
+                    None => {
+                        return span_err(TypeErrorKind::ProjectionMissingEntry)
+                    }
                     Some(t) => {
                         new_kts.insert(l.clone(), t.clone());
                     }
@@ -481,30 +648,72 @@ pub fn typecheck_operation(
             let record_type = record.ty();
             let rec_kts = match record_type.kind() {
                 RecordType(kts) => kts,
-                _ => return span_err("ProjectionMustBeRecord"),
+                _ => return span_err(TypeErrorKind::ProjectionMustBeRecord),
             };
 
             let selection_val = selection.eval_to_type(env)?;
             let sel_kts = match selection_val.kind() {
                 RecordType(kts) => kts,
-                _ => return span_err("ProjectionByExprTakesRecordType"),
+                _ => {
+                    return span_err(
+                        TypeErrorKind::ProjectionByExprTakesRecordType,
+                    )
+                }
             };
 
             for (l, sel_ty) in sel_kts {
                 match rec_kts.get(l) {
                     Some(rec_ty) => {
                         if rec_ty != sel_ty {
-                            return span_err("ProjectionWrongType");
+                            return span_err(
+                                TypeErrorKind::ProjectionWrongType,
+                            );
                         }
                     }
-                    None => return span_err("ProjectionMissingEntry"),
+                    None => {
+                        return span_err(TypeErrorKind::ProjectionMissingEntry)
+                    }
                 }
             }
 
             selection_val
         }
+        With(record, labels, value) => {
+            let kts = match record.ty().kind() {
+                RecordType(kts) => kts,
+                _ => return span_err(TypeErrorKind::WithMustBeRecord),
+            };
+            match type_with_update(kts, labels, value.ty().to_nir()) {
+                Some(ty) => Type::new_infer_universe(env, ty)?,
+                None => return span_err(TypeErrorKind::WithMustBeRecord),
+            }
+        }
         Completion(..) => {
-            unreachable!("This case should have been handled in resolution")
+            unreachable!("This case should have been handled in type_with")
         }
     })
 }
+
+/// Computes the type of `r with ls = v` from the fields of the type of `r`. A missing
+/// intermediate field is treated as an empty record; returns `None` if a field along the path
+/// isn't a record.
+fn type_with_update(
+    kts: &BTreeMap<Label, Nir>,
+    labels: &[Label],
+    value_ty: Nir,
+) -> Option<Nir> {
+    use NirKind::RecordType;
+    let (l, rest) = labels.split_first()?;
+    let field_ty = if rest.is_empty() {
+        value_ty
+    } else {
+        match kts.get(l).map(Nir::kind) {
+            Some(RecordType(inner)) => type_with_update(inner, rest, value_ty)?,
+            Some(_) => return None,
+            None => type_with_update(&BTreeMap::new(), rest, value_ty)?,
+        }
+    };
+    let mut kts = kts.clone();
+    kts.insert(l.clone(), field_ty);
+    Some(Nir::from_kind(RecordType(kts)))
+}
@@ -0,0 +1,62 @@
+//! Layering of configuration files, e.g. a base configuration with per-environment overrides.
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::error::Error;
+use crate::operations::{BinOp, OpKind};
+use crate::semantics::{ImportEnv, ImportLocation, Nir, NirKind};
+use crate::syntax::{Expr, ExprKind, Span};
+use crate::{Normalized, Parsed};
+
+fn empty_record() -> Normalized {
+    Normalized(Nir::from_kind(NirKind::RecordLit(BTreeMap::new())))
+}
+
+/// Typechecks and evaluates `left // right`.
+fn merge(left: &Normalized, right: Expr) -> Result<Normalized, Error> {
+    let op =
+        OpKind::BinOp(BinOp::RightBiasedRecordMerge, left.to_expr(), right);
+    let expr = Expr::new(ExprKind::Op(op), Span::Artificial);
+    let typed = Parsed(expr, ImportLocation::Missing)
+        .skip_resolve()?
+        .typecheck()?;
+    Ok(typed.normalize())
+}
+
+/// Typechecks each of the given files, which must evaluate to records, and merges them in order
+/// with `//`: fields of later files take precedence over those of earlier ones. On failure,
+/// returns the file that failed to typecheck or to merge with the files before it.
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let dir = std::env::temp_dir().join("dhall-overlay-doctest");
+/// std::fs::create_dir_all(&dir)?;
+/// std::fs::write(dir.join("base.dhall"), "{ host = \"localhost\", port = 80 }")?;
+/// std::fs::write(dir.join("prod.dhall"), "{ host = \"example.com\" }")?;
+///
+/// let config =
+///     dhall::overlay::overlay(&[dir.join("base.dhall"), dir.join("prod.dhall")])
+///         .map_err(|(_, err)| err)?;
+/// assert_eq!(config.to_string(), "{ host = \"example.com\", port = 80 }");
+/// # Ok(())
+/// # }
+/// ```
+pub fn overlay(
+    paths: &[impl AsRef<Path>],
+) -> Result<Normalized, (PathBuf, Error)> {
+    let mut env = ImportEnv::new();
+    // Starting from `{=}` checks that the first file is a record too.
+    let mut result = empty_record();
+    for path in paths {
+        let path = path.as_ref();
+        let layer = Parsed::parse_file(path)
+            .and_then(|parsed| parsed.resolve_with(&mut env))
+            .and_then(|resolved| Ok(resolved.typecheck()?))
+            .and_then(|typed| merge(&result, typed.normalize().to_expr()))
+            .map_err(|err| (path.to_owned(), err))?;
+        result = layer;
+    }
+    Ok(result)
+}
@@ -0,0 +1,146 @@
+//! Generation of sample values from a type, e.g. to produce example configurations or test
+//! fixtures from a schema.
+use std::collections::BTreeMap;
+
+use crate::builtins::Builtin;
+use crate::operations::OpKind;
+use crate::syntax::{Expr, ExprKind, NumKind, Span, UnspannedExpr};
+
+const WORDS: &[&str] = &[
+    "alpha", "bravo", "charlie", "delta", "echo", "foxtrot", "golf", "hotel",
+];
+
+/// Generates pseudo-random values of a given type. The values only depend on the seed and on the
+/// sequence of types requested, so a generator can be used to produce reproducible fixtures.
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> Result<(), dhall::error::Error> {
+/// use dhall::sample::Sampler;
+///
+/// let ty = dhall::Parsed::parse_str("{ name : Text, port : Natural }")?
+///     .resolve()?
+///     .typecheck()?
+///     .normalize()
+///     .to_expr();
+/// let value = Sampler::new(42).sample(&ty).unwrap();
+/// let again = Sampler::new(42).sample(&ty).unwrap();
+/// assert_eq!(value.to_string(), again.to_string());
+///
+/// // The value has the requested type.
+/// dhall::Parsed::parse_str(&format!("{} : {}", value, ty))?
+///     .resolve()?
+///     .typecheck()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Sampler {
+    state: u64,
+    max_list_len: u64,
+}
+
+fn mkexpr(kind: UnspannedExpr) -> Expr {
+    Expr::new(kind, Span::Artificial)
+}
+
+impl Sampler {
+    pub fn new(seed: u64) -> Self {
+        Sampler {
+            state: seed,
+            max_list_len: 3,
+        }
+    }
+
+    /// Sets the maximum number of elements of the generated lists. Defaults to 3.
+    pub fn max_list_len(mut self, len: u64) -> Self {
+        self.max_list_len = len;
+        self
+    }
+
+    /// splitmix64
+    fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in `0..n`.
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    /// Produces a value of the given type. The type must be in normal form; this fails if it
+    /// contains types for which no value can be generated, like functions or type variables.
+    pub fn sample(&mut self, ty: &Expr) -> Result<Expr, String> {
+        let kind = match ty.kind() {
+            ExprKind::Builtin(Builtin::Bool) => {
+                ExprKind::Num(NumKind::Bool(self.below(2) == 1))
+            }
+            ExprKind::Builtin(Builtin::Natural) => {
+                ExprKind::Num(NumKind::Natural(self.below(100) as usize))
+            }
+            ExprKind::Builtin(Builtin::Integer) => {
+                let n = self.below(201) as isize - 100;
+                ExprKind::Num(NumKind::Integer(n))
+            }
+            ExprKind::Builtin(Builtin::Double) => {
+                let n = (self.below(801) as f64 - 400.0) / 4.0;
+                ExprKind::Num(NumKind::Double(n.into()))
+            }
+            ExprKind::Builtin(Builtin::Text) => {
+                let word = WORDS[self.below(WORDS.len() as u64) as usize];
+                ExprKind::TextLit(word.to_owned().into())
+            }
+            ExprKind::Op(OpKind::App(f, arg)) => match f.kind() {
+                ExprKind::Builtin(Builtin::List) => {
+                    let len = self.below(self.max_list_len + 1);
+                    if len == 0 {
+                        ExprKind::EmptyListLit(ty.clone())
+                    } else {
+                        let elts = (0..len)
+                            .map(|_| self.sample(arg))
+                            .collect::<Result<_, _>>()?;
+                        ExprKind::NEListLit(elts)
+                    }
+                }
+                ExprKind::Builtin(Builtin::Optional) => {
+                    if self.below(2) == 0 {
+                        let none =
+                            mkexpr(ExprKind::Builtin(Builtin::OptionalNone));
+                        ExprKind::Op(OpKind::App(none, arg.clone()))
+                    } else {
+                        ExprKind::SomeLit(self.sample(arg)?)
+                    }
+                }
+                _ => return Err(format!("cannot generate a `{}`", ty)),
+            },
+            ExprKind::RecordType(fields) => {
+                let fields = fields
+                    .iter()
+                    .map(|(k, t)| Ok((k.clone(), self.sample(t)?)))
+                    .collect::<Result<BTreeMap<_, _>, String>>()?;
+                ExprKind::RecordLit(fields)
+            }
+            ExprKind::UnionType(alts) if !alts.is_empty() => {
+                let i = self.below(alts.len() as u64) as usize;
+                let (label, alt_ty) = alts.iter().nth(i).unwrap();
+                let ctor = mkexpr(ExprKind::Op(OpKind::Field(
+                    ty.clone(),
+                    label.clone(),
+                )));
+                match alt_ty {
+                    Some(alt_ty) => {
+                        ExprKind::Op(OpKind::App(ctor, self.sample(alt_ty)?))
+                    }
+                    None => return Ok(ctor),
+                }
+            }
+            _ => return Err(format!("cannot generate a `{}`", ty)),
+        };
+        Ok(mkexpr(kind))
+    }
+}
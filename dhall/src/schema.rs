@@ -0,0 +1,195 @@
+//! Comparison of Dhall types, to review how a configuration schema evolves.
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::builtins::Builtin;
+use crate::operations::OpKind;
+use crate::syntax::{Expr, ExprKind, Label};
+
+/// A difference between two versions of a type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// A record field was added, with the given type.
+    AddedField(Expr),
+    /// A record field was removed; it had the given type.
+    RemovedField(Expr),
+    /// A union alternative was added, with the given type if it has one.
+    AddedAlternative(Option<Expr>),
+    /// A union alternative was removed; it had the given type if it had one.
+    RemovedAlternative(Option<Expr>),
+    /// The type changed from the first to the second one.
+    Retyped(Expr, Expr),
+}
+
+/// A change, located by the path of record fields and union alternatives that leads to it. Lists
+/// and optionals are looked through, so `servers.port` may refer to a field of the elements of
+/// the `servers` list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaChange {
+    pub path: Vec<Label>,
+    pub change: Change,
+}
+
+/// The list of changes between two types.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaDiff {
+    pub changes: Vec<SchemaChange>,
+}
+
+impl SchemaDiff {
+    /// Whether values of the old type can still be used where the new type is expected. Dhall
+    /// has no subtyping, so this is only the case if the types are the same: a record lacking a
+    /// new field, even an `Optional` one, doesn't typecheck against the new type, and a `merge`
+    /// that handles the old alternatives of a union fails on the new ones.
+    pub fn is_compatible(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+/// Compares two types, which must be in normal form. Records and unions are compared field by
+/// field, recursively; any other mismatch is reported as a retyping of the enclosing field.
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> Result<(), dhall::error::Error> {
+/// use dhall::schema::diff_types;
+///
+/// let ty = |s: &str| -> Result<_, dhall::error::Error> {
+///     let typed = dhall::Parsed::parse_str(s)?.resolve()?.typecheck()?;
+///     Ok(typed.normalize().to_expr())
+/// };
+/// let old = ty("{ name : Text, port : Natural }")?;
+/// let new = ty("{ name : Text, port : Text, tls : Optional Bool }")?;
+/// let diff = diff_types(&old, &new);
+/// assert!(!diff.is_compatible());
+/// assert_eq!(diff.to_string(), "~ port : Natural → Text\n+ tls : Optional Bool\n");
+/// # Ok(())
+/// # }
+/// ```
+pub fn diff_types(old: &Expr, new: &Expr) -> SchemaDiff {
+    let mut changes = Vec::new();
+    diff_at(&mut Vec::new(), old, new, &mut changes);
+    SchemaDiff { changes }
+}
+
+fn diff_at(
+    path: &mut Vec<Label>,
+    old: &Expr,
+    new: &Expr,
+    changes: &mut Vec<SchemaChange>,
+) {
+    let fields = |fields: &BTreeMap<Label, Expr>| {
+        fields
+            .iter()
+            .map(|(k, ty)| (k.clone(), Some(ty.clone())))
+            .collect::<BTreeMap<_, _>>()
+    };
+    match (old.kind(), new.kind()) {
+        _ if old.to_canonical() == new.to_canonical() => {}
+        (ExprKind::RecordType(old), ExprKind::RecordType(new)) => {
+            diff_maps(path, &fields(old), &fields(new), true, changes)
+        }
+        (ExprKind::UnionType(old), ExprKind::UnionType(new)) => {
+            diff_maps(path, old, new, false, changes)
+        }
+        (
+            ExprKind::Op(OpKind::App(f, old)),
+            ExprKind::Op(OpKind::App(g, new)),
+        ) if f == g
+            && matches!(
+                f.kind(),
+                ExprKind::Builtin(Builtin::List)
+                    | ExprKind::Builtin(Builtin::Optional)
+            ) =>
+        {
+            diff_at(path, old, new, changes)
+        }
+        _ => changes.push(SchemaChange {
+            path: path.clone(),
+            change: Change::Retyped(old.clone(), new.clone()),
+        }),
+    }
+}
+
+/// Compares the fields of two records (`is_record`) or the alternatives of two unions.
+fn diff_maps(
+    path: &mut Vec<Label>,
+    old: &BTreeMap<Label, Option<Expr>>,
+    new: &BTreeMap<Label, Option<Expr>>,
+    is_record: bool,
+    changes: &mut Vec<SchemaChange>,
+) {
+    let removed = |ty: &Option<Expr>| match ty {
+        Some(ty) if is_record => Change::RemovedField(ty.clone()),
+        _ => Change::RemovedAlternative(ty.clone()),
+    };
+    let added = |ty: &Option<Expr>| match ty {
+        Some(ty) if is_record => Change::AddedField(ty.clone()),
+        _ => Change::AddedAlternative(ty.clone()),
+    };
+    let keys: std::collections::BTreeSet<&Label> =
+        old.keys().chain(new.keys()).collect();
+    for k in keys {
+        path.push(k.clone());
+        match (old.get(k), new.get(k)) {
+            (Some(Some(old)), Some(Some(new))) => {
+                diff_at(path, old, new, changes)
+            }
+            (Some(None), Some(None)) => {}
+            (old, new) => {
+                // Either a missing key or an alternative that gained or lost its argument.
+                if let Some(ty) = old {
+                    changes.push(SchemaChange {
+                        path: path.clone(),
+                        change: removed(ty),
+                    });
+                }
+                if let Some(ty) = new {
+                    changes.push(SchemaChange {
+                        path: path.clone(),
+                        change: added(ty),
+                    });
+                }
+            }
+        }
+        path.pop();
+    }
+}
+
+impl fmt::Display for SchemaChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let path = if self.path.is_empty() {
+            "(root)".to_owned()
+        } else {
+            self.path
+                .iter()
+                .map(|l| l.to_string())
+                .collect::<Vec<_>>()
+                .join(".")
+        };
+        match &self.change {
+            Change::AddedField(ty) | Change::AddedAlternative(Some(ty)) => {
+                write!(f, "+ {} : {}", path, ty)?
+            }
+            Change::AddedAlternative(None) => write!(f, "+ {}", path)?,
+            Change::RemovedField(ty) | Change::RemovedAlternative(Some(ty)) => {
+                write!(f, "- {} : {}", path, ty)?
+            }
+            Change::RemovedAlternative(None) => write!(f, "- {}", path)?,
+            Change::Retyped(old, new) => {
+                write!(f, "~ {} : {} → {}", path, old, new)?
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for SchemaDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
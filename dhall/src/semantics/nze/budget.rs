@@ -0,0 +1,134 @@
+use std::cell::{Cell, RefCell};
+
+use crate::error::{BudgetExceededError, EvalError};
+use crate::syntax::{ParsedSpan, Span};
+
+thread_local! {
+    /// Steps left before evaluation gets aborted, if it is bounded.
+    static FUEL: Cell<Option<usize>> = const { Cell::new(None) };
+    /// Whether the current budget has been spent.
+    static OUT_OF_FUEL: Cell<bool> = const { Cell::new(false) };
+    /// The function applications being evaluated, outermost first. Only tracked under a budget.
+    static CALL_STACK: RefCell<Vec<ParsedSpan>> = const { RefCell::new(Vec::new()) };
+    /// The call stack at the time the current budget was spent.
+    static ABORTED_IN: RefCell<Vec<ParsedSpan>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Marks an evaluation that was cut short because the budget ran out. Its result must not be
+/// kept: it may contain `NirKind::Aborted` in place of the parts that didn't get evaluated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutOfFuel;
+
+/// Count one evaluation step. Once there is no budget left for it, the caller must stop
+/// evaluating and return `NirKind::Aborted` instead; the enclosing `with_eval_budget` then
+/// reports the error.
+pub fn consume_fuel() -> Result<(), OutOfFuel> {
+    FUEL.with(|fuel| match fuel.get() {
+        None => Ok(()),
+        Some(0) => {
+            if !OUT_OF_FUEL.with(|out| out.replace(true)) {
+                let stack = CALL_STACK.with(|stack| stack.borrow().clone());
+                ABORTED_IN.with(|aborted| aborted.replace(stack));
+            }
+            Err(OutOfFuel)
+        }
+        Some(n) => {
+            fuel.set(Some(n - 1));
+            Ok(())
+        }
+    })
+}
+
+/// Whether the current budget has been spent, i.e. whatever was evaluated since then will be
+/// thrown away.
+pub fn check_fuel() -> Result<(), OutOfFuel> {
+    if OUT_OF_FUEL.with(Cell::get) {
+        Err(OutOfFuel)
+    } else {
+        Ok(())
+    }
+}
+
+/// Run `f`, which evaluates the function application at `span`, recording it in the call stack
+/// reported if the budget runs out.
+pub fn in_call_frame<R>(
+    span: impl FnOnce() -> Span,
+    f: impl FnOnce() -> R,
+) -> R {
+    if FUEL.with(Cell::get).is_none() {
+        return f();
+    }
+    let span = match span() {
+        Span::Parsed(span) => span,
+        _ => return f(),
+    };
+    CALL_STACK.with(|stack| stack.borrow_mut().push(span));
+    let ret = f();
+    CALL_STACK.with(|stack| stack.borrow_mut().pop());
+    ret
+}
+
+/// Restores the enclosing budget, even if the evaluation panics.
+struct BudgetScope {
+    steps: usize,
+    outer_fuel: Option<usize>,
+    outer_out_of_fuel: bool,
+    outer_aborted_in: Vec<ParsedSpan>,
+    call_depth: usize,
+}
+
+impl BudgetScope {
+    fn enter(steps: usize) -> Self {
+        BudgetScope {
+            steps,
+            outer_fuel: FUEL.with(|fuel| fuel.replace(Some(steps))),
+            outer_out_of_fuel: OUT_OF_FUEL.with(|out| out.replace(false)),
+            outer_aborted_in: ABORTED_IN.with(|aborted| aborted.take()),
+            call_depth: CALL_STACK.with(|stack| stack.borrow().len()),
+        }
+    }
+
+    /// If the budget was spent, the call stack at that time.
+    fn aborted_in(&self) -> Option<Vec<ParsedSpan>> {
+        if OUT_OF_FUEL.with(Cell::get) {
+            Some(ABORTED_IN.with(|aborted| aborted.take()))
+        } else {
+            None
+        }
+    }
+}
+
+impl Drop for BudgetScope {
+    fn drop(&mut self) {
+        FUEL.with(|fuel| {
+            // Charge the steps taken here to the enclosing budget, if any.
+            let used = self.steps - fuel.get().unwrap_or(0);
+            fuel.set(self.outer_fuel.map(|n| n.saturating_sub(used)))
+        });
+        OUT_OF_FUEL.with(|out| out.set(self.outer_out_of_fuel));
+        ABORTED_IN.with(|aborted| {
+            aborted.replace(std::mem::take(&mut self.outer_aborted_in))
+        });
+        CALL_STACK.with(|stack| stack.borrow_mut().truncate(self.call_depth));
+    }
+}
+
+/// Run `f`, allowing it at most `steps` evaluation steps. A step is the evaluation of one
+/// subexpression or one function application.
+/// Once the budget is spent, evaluation returns placeholders instead of recursing further, and
+/// whatever `f` computed is thrown away. Thunks are only updated once their evaluation
+/// completes within the budget, so values shared with the aborted evaluation can still be used
+/// afterwards.
+pub fn with_eval_budget<R>(
+    steps: usize,
+    f: impl FnOnce() -> R,
+) -> Result<R, EvalError> {
+    let scope = BudgetScope::enter(steps);
+    let ret = f();
+    match scope.aborted_in() {
+        Some(call_stack) => Err(EvalError::BudgetExceeded(Box::new(
+            BudgetExceededError::new(steps, &call_stack),
+        ))),
+        None => Ok(ret),
+    }
+}
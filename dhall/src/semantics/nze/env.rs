@@ -79,6 +79,14 @@ impl<Type: Clone> ValEnv<Type> {
             EnvItem::Replaced(x, _) => x.kind().clone(),
         }
     }
+    /// The value of the variable, without evaluating it, if it is bound to one.
+    pub fn lookup_nir(&self, var: AlphaVar) -> Option<Nir> {
+        let idx = self.items.len() - 1 - var.idx();
+        match &self.items[idx] {
+            EnvItem::Kept(_) => None,
+            EnvItem::Replaced(x, _) => Some(x.clone()),
+        }
+    }
     pub fn lookup_ty(&self, var: AlphaVar) -> Type {
         let idx = self.items.len() - 1 - var.idx();
         match &self.items[idx] {
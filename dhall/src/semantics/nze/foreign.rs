@@ -0,0 +1,107 @@
+use crate::operations::OpKind;
+use crate::semantics::{Hir, HirKind, Nir, NirKind, Type, VarEnv};
+use crate::shared::Rc;
+use crate::syntax::{ExprKind, Label, Span};
+use crate::Normalized;
+
+/// The implementation of a builtin provided by the host application. It gets called once all the
+/// arguments are there and have no free variables. It returns `None` when it can't compute a
+/// result; the application then stays as is.
+pub type ForeignFn = dyn Fn(&[Normalized]) -> Option<Normalized> + Send + Sync;
+
+/// A builtin provided by the host application, with its declared type. See
+/// `Context::builtin`.
+#[derive(Clone)]
+pub struct ForeignBuiltin {
+    name: Label,
+    ty: Type,
+    /// Number of arguments the function expects, i.e. the number of arrows in its type.
+    arity: usize,
+    f: Rc<ForeignFn>,
+}
+
+/// A partially applied foreign builtin.
+/// Invariant: if all the arguments have been given, the function returned `None` for them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForeignClosure {
+    foreign: ForeignBuiltin,
+    /// Arguments applied to the closure so far.
+    args: Vec<Nir>,
+}
+
+impl ForeignBuiltin {
+    pub fn new(name: Label, ty: Type, f: Rc<ForeignFn>) -> Self {
+        let arity = arity(ty.as_nir());
+        ForeignBuiltin { name, ty, arity, f }
+    }
+    pub fn name(&self) -> &Label {
+        &self.name
+    }
+    pub fn ty(&self) -> &Type {
+        &self.ty
+    }
+}
+
+fn arity(ty: &Nir) -> usize {
+    match ty.kind() {
+        NirKind::PiClosure { closure, .. } => match closure.remove_binder() {
+            Ok(body) => 1 + arity(&body),
+            Err(_) => 1,
+        },
+        _ => 0,
+    }
+}
+
+impl ForeignClosure {
+    pub fn new(foreign: ForeignBuiltin) -> NirKind {
+        apply_foreign(foreign, Vec::new())
+    }
+    pub fn apply(&self, a: Nir) -> NirKind {
+        use std::iter::once;
+        let args = self.args.iter().cloned().chain(once(a)).collect();
+        apply_foreign(self.foreign.clone(), args)
+    }
+    pub fn name(&self) -> &Label {
+        self.foreign.name()
+    }
+    pub fn args(&self) -> &[Nir] {
+        &self.args
+    }
+    pub fn to_hirkind(&self, venv: VarEnv) -> HirKind {
+        let foreign = HirKind::Foreign(self.foreign.clone());
+        self.args.iter().fold(foreign, |acc, v| {
+            HirKind::Expr(ExprKind::Op(OpKind::App(
+                Hir::new(acc, Span::Artificial),
+                v.to_hir(venv),
+            )))
+        })
+    }
+}
+
+fn apply_foreign(foreign: ForeignBuiltin, args: Vec<Nir>) -> NirKind {
+    // The host function can only make sense of closed values.
+    if args.len() == foreign.arity && args.iter().all(Nir::is_closed) {
+        let normalized: Vec<_> = args.iter().cloned().map(Normalized).collect();
+        if let Some(ret) = (foreign.f)(&normalized) {
+            return ret.0.kind().clone();
+        }
+    }
+    NirKind::Foreign(ForeignClosure { foreign, args })
+}
+
+/// Two foreign builtins are equal if they are the same registered function.
+impl std::cmp::PartialEq for ForeignBuiltin {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && Rc::ptr_eq(&self.f, &other.f)
+    }
+}
+impl std::cmp::Eq for ForeignBuiltin {}
+
+impl std::fmt::Debug for ForeignBuiltin {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt.debug_struct("ForeignBuiltin")
+            .field("name", &self.name)
+            .field("ty", &self.ty)
+            .finish()
+    }
+}
@@ -0,0 +1,99 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::builtins::Builtin;
+use crate::semantics::{Nir, NirKind};
+use crate::shared::{Rc, Weak};
+use crate::syntax::{Const, Label, NumKind};
+
+/// Identifies a WHNF value up to sharing. Subvalues are identified by the address of their
+/// allocation, so two keys are equal when the values have the same head constructor and share
+/// their subvalues. Since the interned values are themselves shared, this catches most
+/// duplicates without having to compare whole values.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum InternKey {
+    Const(Const),
+    Num(NumKind),
+    BuiltinType(Builtin),
+    Text(String),
+    EmptyOptionalLit(usize),
+    NEOptionalLit(usize),
+    OptionalType(usize),
+    EmptyListLit(usize),
+    NEListLit(Vec<usize>),
+    ListType(usize),
+    RecordLit(Vec<(Label, usize)>),
+    RecordType(Vec<(Label, usize)>),
+}
+
+impl InternKey {
+    /// Returns `None` for values that aren't worth interning, like closures, stuck operations and
+    /// text that hasn't been flattened yet.
+    pub fn of(kind: &NirKind) -> Option<Self> {
+        let addr = Nir::addr;
+        let fields = |kvs: &BTreeMap<Label, Nir>| {
+            kvs.iter().map(|(k, v)| (k.clone(), addr(v))).collect()
+        };
+        Some(match kind {
+            NirKind::Const(c) => InternKey::Const(*c),
+            NirKind::Num(n) => InternKey::Num(n.clone()),
+            NirKind::BuiltinType(b) => InternKey::BuiltinType(*b),
+            NirKind::TextLit(t) => {
+                InternKey::Text(t.as_flat_text()?.to_owned())
+            }
+            NirKind::EmptyOptionalLit(t) => {
+                InternKey::EmptyOptionalLit(addr(t))
+            }
+            NirKind::NEOptionalLit(x) => InternKey::NEOptionalLit(addr(x)),
+            NirKind::OptionalType(t) => InternKey::OptionalType(addr(t)),
+            NirKind::EmptyListLit(t) => InternKey::EmptyListLit(addr(t)),
+            NirKind::NEListLit(xs) => {
+                InternKey::NEListLit(xs.iter().map(addr).collect())
+            }
+            NirKind::ListType(t) => InternKey::ListType(addr(t)),
+            NirKind::RecordLit(kvs) => InternKey::RecordLit(fields(kvs)),
+            NirKind::RecordType(kts) => InternKey::RecordType(fields(kts)),
+            _ => return None,
+        })
+    }
+}
+
+/// A table of the live values of a given kind. It only holds weak references, so interning a
+/// value doesn't keep it alive.
+/// The keys of a live entry stay valid: the value holds on to the subvalues whose addresses make
+/// up the key. Dead entries are purged when the table grows.
+#[derive(Debug)]
+pub struct Interner<T> {
+    table: HashMap<InternKey, Weak<T>>,
+    /// Size of the table after the last purge.
+    live: usize,
+}
+
+impl<T> Interner<T> {
+    pub fn new() -> Self {
+        Interner {
+            table: HashMap::new(),
+            live: 0,
+        }
+    }
+
+    /// Returns the live value for `key` if there is one, and otherwise makes one with `mk` and
+    /// remembers it.
+    pub fn intern(&mut self, key: InternKey, mk: impl FnOnce() -> T) -> Rc<T> {
+        if let Some(rc) = self.table.get(&key).and_then(Weak::upgrade) {
+            return rc;
+        }
+        let rc = Rc::new(mk());
+        self.table.insert(key, Rc::downgrade(&rc));
+        if self.table.len() > 2 * self.live.max(1024) {
+            self.table.retain(|_, w| w.strong_count() != 0);
+            self.live = self.table.len();
+        }
+        rc
+    }
+}
+
+impl<T> Default for Interner<T> {
+    fn default() -> Self {
+        Interner::new()
+    }
+}
@@ -1,17 +1,23 @@
+#[cfg(feature = "sync")]
+use once_cell::sync::OnceCell;
+#[cfg(not(feature = "sync"))]
 use once_cell::unsync::OnceCell;
-use std::cell::Cell;
+#[cfg(not(feature = "sync"))]
+use std::cell::RefCell as SrcCell;
 use std::fmt::Debug;
-use std::ops::Deref;
+#[cfg(feature = "sync")]
+use std::sync::RwLock as SrcCell;
 
 pub trait Eval<Tgt> {
-    fn eval(self) -> Tgt;
+    type Error;
+    fn eval(&self) -> Result<Tgt, Self::Error>;
 }
 
 /// A value which is initialized from a `Src` on the first access.
 pub struct Lazy<Src, Tgt> {
     /// Exactly one of `src` of `tgt` must be set at a given time.
     /// Once `src` is unset and `tgt` is set, we never go back.
-    src: Cell<Option<Src>>,
+    src: SrcCell<Option<Src>>,
     tgt: OnceCell<Tgt>,
 }
 
@@ -22,14 +28,14 @@ where
     /// Creates a new lazy value with the given initializing value.
     pub fn new(src: Src) -> Self {
         Lazy {
-            src: Cell::new(Some(src)),
+            src: SrcCell::new(Some(src)),
             tgt: OnceCell::new(),
         }
     }
     /// Creates a new lazy value with the given already-initialized value.
     pub fn new_completed(tgt: Tgt) -> Self {
         let lazy = Lazy {
-            src: Cell::new(None),
+            src: SrcCell::new(None),
             tgt: OnceCell::new(),
         };
         let _ = lazy.tgt.set(tgt);
@@ -37,15 +43,37 @@ where
     }
 }
 
-impl<Src, Tgt> Deref for Lazy<Src, Tgt>
+impl<Src, Tgt> Lazy<Src, Tgt> {
+    /// Calls `f` on the initializing value, if the value hasn't been computed yet.
+    pub fn with_src<R>(&self, f: impl FnOnce(&Src) -> R) -> Option<R> {
+        #[cfg(not(feature = "sync"))]
+        let src = self.src.borrow();
+        #[cfg(feature = "sync")]
+        let src = self.src.read().unwrap();
+        src.as_ref().map(f)
+    }
+}
+
+impl<Src, Tgt> Lazy<Src, Tgt>
 where
     Src: Eval<Tgt>,
 {
-    type Target = Tgt;
-    fn deref(&self) -> &Self::Target {
-        self.tgt.get_or_init(|| {
-            let src = self.src.take().unwrap();
-            src.eval()
+    /// Computes the value if needed. If that fails, the value is left uninitialized and can
+    /// still be evaluated later.
+    pub fn force(&self) -> Result<&Tgt, Src::Error> {
+        self.tgt.get_or_try_init(|| {
+            #[cfg(not(feature = "sync"))]
+            let src = self.src.borrow();
+            #[cfg(feature = "sync")]
+            let src = self.src.read().unwrap();
+            let tgt = src.as_ref().unwrap().eval()?;
+            drop(src);
+            // `src` is only dropped once evaluation succeeds.
+            #[cfg(not(feature = "sync"))]
+            self.src.replace(None);
+            #[cfg(feature = "sync")]
+            self.src.write().unwrap().take();
+            Ok(tgt)
         })
     }
 }
@@ -1,9 +1,17 @@
+pub mod budget;
 pub mod env;
+pub mod foreign;
+pub mod intern;
 pub mod lazy;
 pub mod nir;
 pub mod normalize;
+pub mod rope;
+pub mod trace;
 pub mod var;
+pub use budget::*;
 pub use env::*;
+pub use foreign::*;
 pub use nir::*;
 pub use normalize::*;
+pub use trace::*;
 pub use var::*;
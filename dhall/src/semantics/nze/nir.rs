@@ -1,20 +1,24 @@
-use std::collections::HashMap;
-use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
 
 use crate::builtins::{Builtin, BuiltinClosure};
 use crate::operations::{BinOp, OpKind};
+use crate::semantics::nze::intern::{InternKey, Interner};
 use crate::semantics::nze::lazy;
+use crate::semantics::nze::rope::Rope;
 use crate::semantics::{
-    apply_any, normalize_hir, normalize_one_layer, squash_textlit, Binder, Hir,
-    HirKind, NzEnv, NzVar, TyEnv, Type, Universe, VarEnv,
+    apply_any, check_fuel, normalize_hir, normalize_one_layer, squash_textlit,
+    AlphaVar, Binder, ForeignClosure, Hir, HirKind, NzEnv, NzVar, OutOfFuel,
+    TyEnv, Type, Universe, VarEnv,
 };
+use crate::shared::{ensure_stack, stack_is_low, Rc};
 use crate::syntax::{
     Const, Expr, ExprKind, InterpolatedTextContents, Label, NumKind, Span,
 };
 use crate::ToExprOptions;
 
 /// Stores a possibly unevaluated value. Gets (partially) normalized on-demand, sharing computation
-/// automatically. Uses a Rc<RefCell> to share computation.
+/// automatically. Uses a reference-counted lazy cell to share computation.
 /// If you compare for equality two `Nir`s, then equality will be up to alpha-equivalence
 /// (renaming of bound variables) and beta-equivalence (normalization). It will recursively
 /// normalize as needed.
@@ -22,6 +26,11 @@ use crate::ToExprOptions;
 #[derive(Clone)]
 pub struct Nir(Rc<NirInternal>);
 
+thread_local! {
+    /// Values in WHNF built with `Nir::from_kind`, so that identical ones share an allocation.
+    static INTERNED: RefCell<Interner<NirInternal>> = RefCell::new(Interner::new());
+}
+
 #[derive(Debug)]
 struct NirInternal {
     kind: lazy::Lazy<Thunk, NirKind>,
@@ -47,9 +56,27 @@ pub enum Closure {
 
 /// A text literal with interpolations.
 // Invariant: this must not contain interpolations that are themselves TextLits, and contiguous
-// text values must be merged.
+// text values must be merged and non-empty.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextLit(Vec<TextChunk>);
+
+/// A part of a text literal. The text is kept as a rope so that concatenating literals doesn't
+/// copy it.
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct TextLit(Vec<InterpolatedTextContents<Nir>>);
+pub enum TextChunk {
+    Text(Rope),
+    Expr(Nir),
+}
+
+/// A non-empty list literal. The elements are shared, so copying the literal or reversing it is
+/// cheap.
+// Invariant: `elts` is non-empty.
+#[derive(Clone)]
+pub struct ListLit {
+    elts: Rc<[Nir]>,
+    /// Whether the elements are stored in reverse order.
+    reversed: bool,
+}
 
 /// This represents a value in Weak Head Normal Form (WHNF). This means that the value is
 /// normalized up to the first constructor, but subexpressions may not be fully normalized.
@@ -72,6 +99,8 @@ pub enum NirKind {
         closure: Closure,
     },
     AppliedBuiltin(BuiltinClosure),
+    /// A builtin provided by the host application, possibly partially applied.
+    Foreign(ForeignClosure),
 
     Var(NzVar),
     Const(Const),
@@ -84,18 +113,21 @@ pub enum NirKind {
     OptionalType(Nir),
     // EmptyListLit(t) means `[] : List t`, not `[] : t`
     EmptyListLit(Nir),
-    NEListLit(Vec<Nir>),
+    NEListLit(ListLit),
     ListType(Nir),
-    RecordLit(HashMap<Label, Nir>),
-    RecordType(HashMap<Label, Nir>),
-    UnionConstructor(Label, HashMap<Label, Option<Nir>>),
-    UnionLit(Label, Nir, HashMap<Label, Option<Nir>>),
-    UnionType(HashMap<Label, Option<Nir>>),
+    RecordLit(BTreeMap<Label, Nir>),
+    RecordType(BTreeMap<Label, Nir>),
+    UnionConstructor(Label, BTreeMap<Label, Option<Nir>>),
+    UnionLit(Label, Nir, BTreeMap<Label, Option<Nir>>),
+    UnionType(BTreeMap<Label, Option<Nir>>),
     Equivalence(Nir, Nir),
     Assert(Nir),
     /// Invariant: evaluation must not be able to progress with `normalize_operation`.
     /// This is used when an operation couldn't proceed further, for example because of variables.
     Op(OpKind<Nir>),
+    /// Stands for a value that didn't get evaluated because the evaluation budget ran out. The
+    /// evaluation it is part of gets thrown away, so this never escapes `with_eval_budget`.
+    Aborted,
 }
 
 impl Nir {
@@ -109,9 +141,17 @@ impl Nir {
         let env = NzEnv::new();
         NirInternal::from_thunk(Thunk::from_partial_expr(env, e)).into_nir()
     }
-    /// Make a Nir from a NirKind
+    /// Make a Nir from a NirKind. Simple values are interned: if an identical value is still
+    /// alive, we return that one instead of allocating a new one.
     pub fn from_kind(v: NirKind) -> Nir {
-        NirInternal::from_whnf(v).into_nir()
+        match InternKey::of(&v) {
+            Some(key) => INTERNED.with(|interned| {
+                Nir(interned
+                    .borrow_mut()
+                    .intern(key, || NirInternal::from_whnf(v)))
+            }),
+            None => NirInternal::from_whnf(v).into_nir(),
+        }
     }
     pub fn from_const(c: Const) -> Self {
         Self::from_kind(NirKind::Const(c))
@@ -133,6 +173,17 @@ impl Nir {
         }
     }
 
+    /// The address of the underlying allocation. Identifies the value up to sharing.
+    pub(crate) fn addr(&self) -> usize {
+        Rc::as_ptr(&self.0) as usize
+    }
+
+    /// Calls `f` on the expression the value will be evaluated from, if it hasn't been evaluated
+    /// yet.
+    pub fn with_thunk<R>(&self, f: impl FnOnce(&Thunk) -> R) -> Option<R> {
+        self.0.kind.with_src(f)
+    }
+
     /// This is what you want if you want to pattern-match on the value.
     pub fn kind(&self) -> &NirKind {
         self.0.kind()
@@ -152,12 +203,16 @@ impl Nir {
     pub fn app(&self, v: Nir) -> Nir {
         Nir::from_kind(self.app_to_kind(v))
     }
+    /// Like `app`, but the application only gets evaluated when needed.
+    pub fn app_lazy(&self, v: Nir) -> Nir {
+        Nir::from_partial_expr(ExprKind::Op(OpKind::App(self.clone(), v)))
+    }
     pub fn app_to_kind(&self, v: Nir) -> NirKind {
         apply_any(self, v)
     }
 
     pub fn to_hir(&self, venv: VarEnv) -> Hir {
-        let map_uniontype = |kts: &HashMap<Label, Option<Nir>>| {
+        let map_uniontype = |kts: &BTreeMap<Label, Option<Nir>>| {
             ExprKind::UnionType(
                 kts.iter()
                     .map(|(k, v)| {
@@ -170,8 +225,11 @@ impl Nir {
         let hir = match self.kind() {
             NirKind::Var(v) => HirKind::Var(venv.lookup(*v)),
             NirKind::AppliedBuiltin(closure) => closure.to_hirkind(venv),
+            NirKind::Foreign(closure) => closure.to_hirkind(venv),
             self_kind => HirKind::Expr(match self_kind {
-                NirKind::Var(..) | NirKind::AppliedBuiltin(..) => {
+                NirKind::Var(..)
+                | NirKind::AppliedBuiltin(..)
+                | NirKind::Foreign(..) => {
                     unreachable!()
                 }
                 NirKind::LamClosure {
@@ -220,7 +278,14 @@ impl Nir {
                 ),
                 NirKind::TextLit(elts) => ExprKind::TextLit(
                     elts.iter()
-                        .map(|t| t.map_ref(|v| v.to_hir(venv)))
+                        .map(|t| match t {
+                            TextChunk::Text(s) => {
+                                InterpolatedTextContents::Text(s.to_string())
+                            }
+                            TextChunk::Expr(v) => {
+                                InterpolatedTextContents::Expr(v.to_hir(venv))
+                            }
+                        })
                         .collect(),
                 ),
                 NirKind::RecordLit(kvs) => ExprKind::RecordLit(
@@ -263,6 +328,9 @@ impl Nir {
                 )),
                 NirKind::Assert(x) => ExprKind::Assert(x.to_hir(venv)),
                 NirKind::Op(e) => ExprKind::Op(e.map_ref(|v| v.to_hir(venv))),
+                // Only seen by an evaluation that is about to be thrown away, so any
+                // placeholder does.
+                NirKind::Aborted => ExprKind::Const(Const::Sort),
             }),
         };
 
@@ -271,6 +339,73 @@ impl Nir {
     pub fn to_hir_noenv(&self) -> Hir {
         self.to_hir(VarEnv::new())
     }
+
+    /// Whether the value has no free variables, and so can be converted back to an expression on
+    /// its own. This fully normalizes the value.
+    pub fn is_closed(&self) -> bool {
+        !self.any_free_var(&|_| true, &mut Vec::new())
+    }
+    /// Whether the given variable occurs in the value. This fully normalizes the value.
+    pub fn mentions_var(&self, var: NzVar) -> bool {
+        self.any_free_var(&|v| v == var, &mut Vec::new())
+    }
+    /// Whether some free variable of the value satisfies `pred`. `bound` lists the fresh
+    /// variables standing for the binders we went under.
+    fn any_free_var(
+        &self,
+        pred: &impl Fn(NzVar) -> bool,
+        bound: &mut Vec<NzVar>,
+    ) -> bool {
+        let mut closed = |v: &Nir| !v.any_free_var(pred, bound);
+        let closed_kind = match self.kind() {
+            NirKind::LamClosure { annot, closure, .. }
+            | NirKind::PiClosure { annot, closure, .. } => {
+                if !closed(annot) {
+                    return true;
+                }
+                let var = NzVar::fresh();
+                bound.push(var);
+                let body_closed =
+                    !closure.apply_var(var).any_free_var(pred, bound);
+                bound.pop();
+                body_closed
+            }
+            NirKind::Var(v) => bound.contains(v) || !pred(*v),
+            NirKind::AppliedBuiltin(closure) => {
+                closure.args().iter().all(closed)
+            }
+            NirKind::Foreign(closure) => closure.args().iter().all(closed),
+            NirKind::Const(_)
+            | NirKind::Num(_)
+            | NirKind::BuiltinType(_)
+            | NirKind::Aborted => true,
+            NirKind::TextLit(elts) => elts.iter().all(|t| match t {
+                TextChunk::Text(_) => true,
+                TextChunk::Expr(v) => closed(v),
+            }),
+            NirKind::EmptyOptionalLit(x)
+            | NirKind::NEOptionalLit(x)
+            | NirKind::OptionalType(x)
+            | NirKind::EmptyListLit(x)
+            | NirKind::ListType(x)
+            | NirKind::Assert(x) => closed(x),
+            NirKind::NEListLit(xs) => xs.iter().all(closed),
+            NirKind::RecordLit(kvs) | NirKind::RecordType(kvs) => {
+                kvs.values().all(closed)
+            }
+            NirKind::UnionType(kts) | NirKind::UnionConstructor(_, kts) => {
+                kts.values().flatten().all(closed)
+            }
+            NirKind::UnionLit(_, v, kts) => {
+                closed(v) && kts.values().flatten().all(closed)
+            }
+            NirKind::Equivalence(x, y) => closed(x) && closed(y),
+            NirKind::Op(op) => op
+                .traverse_ref(|v| if closed(v) { Ok(()) } else { Err(()) })
+                .is_ok(),
+        };
+        !closed_kind
+    }
 }
 
 impl NirInternal {
@@ -289,7 +424,8 @@ impl NirInternal {
     }
 
     fn kind(&self) -> &NirKind {
-        &self.kind
+        const ABORTED: &NirKind = &NirKind::Aborted;
+        self.kind.force().unwrap_or(ABORTED)
     }
 }
 
@@ -313,11 +449,15 @@ impl Thunk {
     fn from_partial_expr(env: NzEnv, expr: ExprKind<Nir>) -> Self {
         Thunk::PartialExpr { env, expr }
     }
-    fn eval(self) -> NirKind {
-        match self {
-            Thunk::Thunk { env, body } => normalize_hir(&env, &body),
-            Thunk::PartialExpr { env, expr } => normalize_one_layer(expr, &env),
-        }
+    fn eval(&self) -> Result<NirKind, OutOfFuel> {
+        let kind = ensure_stack(|| match self {
+            Thunk::Thunk { env, body } => normalize_hir(env, body),
+            Thunk::PartialExpr { env, expr } => {
+                normalize_one_layer(expr.clone(), env)
+            }
+        });
+        check_fuel()?;
+        Ok(kind)
     }
 }
 
@@ -350,20 +490,31 @@ impl Closure {
         }
     }
 
+    /// The location of the body of the closure, if it comes from the source.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Closure::Closure { body, .. } => Some(body.span()),
+            Closure::ConstantClosure { .. } => None,
+        }
+    }
+
     /// Convert this closure to a Hir expression
     pub fn to_hir(&self, venv: VarEnv) -> Hir {
         self.apply_var(NzVar::new(venv.size()))
             .to_hir(venv.insert())
     }
-    /// If the closure variable is free in the closure, return Err. Otherwise, return the value
-    /// with that free variable remove.
-    pub fn remove_binder(&self) -> Result<Nir, ()> {
+    /// If the closure variable is free in the closure, return the locations where the closure
+    /// body uses it. Otherwise, return the value with that free variable removed.
+    pub fn remove_binder(&self) -> Result<Nir, Vec<Span>> {
         match self {
-            Closure::Closure { .. } => {
+            Closure::Closure { body, .. } => {
                 let v = NzVar::fresh();
-                // TODO: handle case where variable is used in closure
-                // TODO: return information about where the variable is used
-                Ok(self.apply_var(v))
+                let val = self.apply_var(v);
+                if val.mentions_var(v) {
+                    Err(body.occurrences(AlphaVar::new(0)))
+                } else {
+                    Ok(val)
+                }
             }
             Closure::ConstantClosure { body, .. } => Ok(body.clone()),
         }
@@ -377,14 +528,20 @@ impl TextLit {
         TextLit(squash_textlit(elts))
     }
     pub fn interpolate(v: Nir) -> TextLit {
-        TextLit(vec![InterpolatedTextContents::Expr(v)])
+        TextLit(vec![TextChunk::Expr(v)])
     }
     pub fn from_text(s: String) -> TextLit {
-        TextLit(vec![InterpolatedTextContents::Text(s)])
+        TextLit::new(std::iter::once(InterpolatedTextContents::Text(s)))
     }
 
+    /// Concatenating text is cheap: only the boundary chunks get merged, and the text itself
+    /// isn't copied.
     pub fn concat(&self, other: &TextLit) -> TextLit {
-        TextLit::new(self.iter().chain(other.iter()).cloned())
+        let mut chunks = self.0.clone();
+        for chunk in other.iter() {
+            push_text_chunk(&mut chunks, chunk.clone());
+        }
+        TextLit(chunks)
     }
     pub fn is_empty(&self) -> bool {
         self.0.is_empty()
@@ -392,8 +549,7 @@ impl TextLit {
     /// If the literal consists of only one interpolation and not text, return the interpolated
     /// value.
     pub fn as_single_expr(&self) -> Option<&Nir> {
-        use InterpolatedTextContents::Expr;
-        if let [Expr(v)] = self.0.as_slice() {
+        if let [TextChunk::Expr(v)] = self.0.as_slice() {
             Some(v)
         } else {
             None
@@ -401,22 +557,135 @@ impl TextLit {
     }
     /// If there are no interpolations, return the corresponding text value.
     pub fn as_text(&self) -> Option<String> {
-        use InterpolatedTextContents::Text;
         if self.is_empty() {
             Some(String::new())
-        } else if let [Text(s)] = self.0.as_slice() {
-            Some(s.clone())
+        } else if let [TextChunk::Text(s)] = self.0.as_slice() {
+            Some(s.to_string())
         } else {
             None
         }
     }
-    pub fn iter(&self) -> impl Iterator<Item = &InterpolatedTextContents<Nir>> {
+    /// Like `as_text`, but only if the text is stored in one piece, so that no copy is needed.
+    pub fn as_flat_text(&self) -> Option<&str> {
+        match self.0.as_slice() {
+            [] => Some(""),
+            [TextChunk::Text(s)] => s.as_flat(),
+            _ => None,
+        }
+    }
+    pub fn iter(&self) -> impl Iterator<Item = &TextChunk> {
         self.0.iter()
     }
 }
 
+/// Append a chunk to a list of chunks, maintaining the invariant of `TextLit`.
+pub fn push_text_chunk(chunks: &mut Vec<TextChunk>, chunk: TextChunk) {
+    match (chunks.last_mut(), chunk) {
+        (_, TextChunk::Text(s)) if s.is_empty() => {}
+        (Some(TextChunk::Text(last)), TextChunk::Text(s)) => {
+            *last = last.concat(&s)
+        }
+        (_, chunk) => chunks.push(chunk),
+    }
+}
+
+impl ListLit {
+    pub fn len(&self) -> usize {
+        self.elts.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+    pub fn head(&self) -> &Nir {
+        self.get(0).unwrap()
+    }
+    pub fn last(&self) -> &Nir {
+        self.get(self.len() - 1).unwrap()
+    }
+    pub fn get(&self, i: usize) -> Option<&Nir> {
+        if i >= self.len() {
+            None
+        } else if self.reversed {
+            self.elts.get(self.len() - 1 - i)
+        } else {
+            self.elts.get(i)
+        }
+    }
+    pub fn reverse(&self) -> ListLit {
+        ListLit {
+            elts: self.elts.clone(),
+            reversed: !self.reversed,
+        }
+    }
+    pub fn concat(&self, other: &ListLit) -> ListLit {
+        self.iter().chain(other.iter()).cloned().collect()
+    }
+    pub fn iter(&self) -> ListIter<'_> {
+        ListIter {
+            iter: self.elts.iter(),
+            reversed: self.reversed,
+        }
+    }
+}
+
+/// Iterator over the elements of a `ListLit`, in order.
+pub struct ListIter<'a> {
+    iter: std::slice::Iter<'a, Nir>,
+    reversed: bool,
+}
+
+impl<'a> Iterator for ListIter<'a> {
+    type Item = &'a Nir;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.reversed {
+            self.iter.next_back()
+        } else {
+            self.iter.next()
+        }
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+impl<'a> DoubleEndedIterator for ListIter<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.reversed {
+            self.iter.next()
+        } else {
+            self.iter.next_back()
+        }
+    }
+}
+impl<'a> ExactSizeIterator for ListIter<'a> {}
+
+/// Panics if the iterator is empty.
+impl std::iter::FromIterator<Nir> for ListLit {
+    fn from_iter<I: IntoIterator<Item = Nir>>(iter: I) -> Self {
+        let elts: Rc<[Nir]> = iter.into_iter().collect();
+        assert!(!elts.is_empty(), "list literals must be non-empty");
+        ListLit {
+            elts,
+            reversed: false,
+        }
+    }
+}
+
+impl std::cmp::PartialEq for ListLit {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+impl std::cmp::Eq for ListLit {}
+
+impl std::fmt::Debug for ListLit {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt.debug_list().entries(self.iter()).finish()
+    }
+}
+
 impl lazy::Eval<NirKind> for Thunk {
-    fn eval(self) -> NirKind {
+    type Error = OutOfFuel;
+    fn eval(&self) -> Result<NirKind, OutOfFuel> {
         self.eval()
     }
 }
@@ -429,6 +698,19 @@ impl std::cmp::PartialEq for Nir {
 }
 impl std::cmp::Eq for Nir {}
 
+/// Values can be nested arbitrarily deeply, e.g. by a fold that builds its result lazily, so the
+/// last reference to a deep value is dropped on a fresh stack segment once the current one runs
+/// low.
+impl Drop for Nir {
+    fn drop(&mut self) {
+        if stack_is_low() {
+            let placeholder = NirInternal::from_whnf(NirKind::Aborted);
+            let internal = std::mem::replace(&mut self.0, Rc::new(placeholder));
+            ensure_stack(|| drop(internal));
+        }
+    }
+}
+
 impl std::cmp::PartialEq for Thunk {
     fn eq(&self, _other: &Self) -> bool {
         unreachable!(
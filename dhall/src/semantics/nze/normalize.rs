@@ -1,70 +1,77 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 use crate::operations::{normalize_operation, OpKind};
-use crate::semantics::NzEnv;
-use crate::semantics::{Binder, Closure, Hir, HirKind, Nir, NirKind, TextLit};
+use crate::semantics::{
+    consume_fuel, in_call_frame, push_text_chunk, trace_reduction, NzEnv,
+};
+use crate::semantics::{
+    Binder, Closure, ForeignClosure, Hir, HirKind, Nir, NirKind, TextChunk,
+    TextLit,
+};
+use crate::shared::ensure_stack;
 use crate::syntax::{ExprKind, InterpolatedTextContents};
 
 pub fn apply_any(f: &Nir, a: Nir) -> NirKind {
-    match f.kind() {
-        NirKind::LamClosure { closure, .. } => closure.apply(a).kind().clone(),
-        NirKind::AppliedBuiltin(closure) => closure.apply(a),
+    if consume_fuel().is_err() {
+        return NirKind::Aborted;
+    }
+    ensure_stack(|| match f.kind() {
+        NirKind::LamClosure { closure, .. } => {
+            let ret = closure.apply(a.clone()).kind().clone();
+            trace_reduction(f, &a, &ret);
+            ret
+        }
+        NirKind::AppliedBuiltin(closure) => {
+            let ret = closure.apply(a.clone());
+            trace_reduction(f, &a, &ret);
+            ret
+        }
+        NirKind::Foreign(closure) => {
+            let ret = closure.apply(a.clone());
+            trace_reduction(f, &a, &ret);
+            ret
+        }
         NirKind::UnionConstructor(l, kts) => {
             NirKind::UnionLit(l.clone(), a, kts.clone())
         }
         _ => NirKind::Op(OpKind::App(f.clone(), a)),
-    }
+    })
 }
 
 pub fn squash_textlit(
     elts: impl Iterator<Item = InterpolatedTextContents<Nir>>,
-) -> Vec<InterpolatedTextContents<Nir>> {
-    use std::mem::replace;
+) -> Vec<TextChunk> {
     use InterpolatedTextContents::{Expr, Text};
 
-    fn inner(
-        elts: impl Iterator<Item = InterpolatedTextContents<Nir>>,
-        crnt_str: &mut String,
-        ret: &mut Vec<InterpolatedTextContents<Nir>>,
-    ) {
-        for contents in elts {
-            match contents {
-                Text(s) => crnt_str.push_str(&s),
-                Expr(e) => match e.kind() {
-                    NirKind::TextLit(elts2) => {
-                        inner(elts2.iter().cloned(), crnt_str, ret)
-                    }
-                    _ => {
-                        if !crnt_str.is_empty() {
-                            ret.push(Text(replace(crnt_str, String::new())))
-                        }
-                        ret.push(Expr(e.clone()))
+    let mut ret = Vec::new();
+    for contents in elts {
+        match contents {
+            Text(s) => push_text_chunk(&mut ret, TextChunk::Text(s.into())),
+            Expr(e) => match e.kind() {
+                // Splice in nested literals, which are already squashed.
+                NirKind::TextLit(elts2) => {
+                    for chunk in elts2.iter() {
+                        push_text_chunk(&mut ret, chunk.clone())
                     }
-                },
-            }
+                }
+                _ => push_text_chunk(&mut ret, TextChunk::Expr(e.clone())),
+            },
         }
     }
-
-    let mut crnt_str = String::new();
-    let mut ret = Vec::new();
-    inner(elts, &mut crnt_str, &mut ret);
-    if !crnt_str.is_empty() {
-        ret.push(Text(replace(&mut crnt_str, String::new())))
-    }
     ret
 }
 
 pub fn merge_maps<K, V, F>(
-    map1: &HashMap<K, V>,
-    map2: &HashMap<K, V>,
+    map1: &BTreeMap<K, V>,
+    map2: &BTreeMap<K, V>,
     mut f: F,
-) -> HashMap<K, V>
+) -> BTreeMap<K, V>
 where
     F: FnMut(&K, &V, &V) -> V,
-    K: std::hash::Hash + Eq + Clone,
+    K: Ord + Clone,
     V: Clone,
 {
-    let mut kvs = HashMap::new();
+    let mut kvs = BTreeMap::new();
     for (x, v2) in map2 {
         let newv = if let Some(v1) = map1.get(x) {
             // Collision: the key is present in both maps
@@ -128,6 +135,7 @@ pub fn normalize_one_layer(expr: ExprKind<Nir>, env: &NzEnv) -> NirKind {
         ExprKind::EmptyListLit(t) => {
             let arg = match t.kind() {
                 NirKind::ListType(t) => t.clone(),
+                NirKind::Aborted => return NirKind::Aborted,
                 _ => panic!("internal type error"),
             };
             ret_kind(NirKind::EmptyListLit(arg))
@@ -155,9 +163,23 @@ pub fn normalize_one_layer(expr: ExprKind<Nir>, env: &NzEnv) -> NirKind {
 
 /// Normalize Hir into WHNF
 pub fn normalize_hir(env: &NzEnv, hir: &Hir) -> NirKind {
+    if consume_fuel().is_err() {
+        return NirKind::Aborted;
+    }
+    match hir.kind() {
+        HirKind::Expr(ExprKind::Op(OpKind::App(..))) => in_call_frame(
+            || hir.span(),
+            || ensure_stack(|| normalize_hir_layer(env, hir)),
+        ),
+        _ => ensure_stack(|| normalize_hir_layer(env, hir)),
+    }
+}
+
+fn normalize_hir_layer(env: &NzEnv, hir: &Hir) -> NirKind {
     match hir.kind() {
         HirKind::Var(var) => env.lookup_val(*var),
         HirKind::Import(hir, _) => normalize_hir(env, hir),
+        HirKind::Foreign(f) => ForeignClosure::new(f.clone()),
         HirKind::Expr(ExprKind::Lam(binder, annot, body)) => {
             let annot = annot.eval(env);
             NirKind::LamClosure {
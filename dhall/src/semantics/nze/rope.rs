@@ -0,0 +1,126 @@
+use std::fmt;
+
+use crate::shared::Rc;
+
+/// Below this length, concatenating two flat pieces of text copies them into a new one instead
+/// of making a node. This keeps ropes built from many small literals shallow.
+const FLATTEN_BELOW: usize = 64;
+
+/// A piece of text built by concatenation. Concatenating two ropes takes constant time: the
+/// pieces are only copied into a single `String` when the text is needed as a whole, e.g. when
+/// converting back to an expression. This keeps chains of `++` on large text linear.
+#[derive(Clone)]
+pub struct Rope(Rc<RopeNode>);
+
+enum RopeNode {
+    Leaf(String),
+    /// Invariant: `parts` has two non-empty elements, and `len` is their total length.
+    Concat {
+        len: usize,
+        parts: Vec<Rope>,
+    },
+}
+
+impl Rope {
+    pub fn new(s: String) -> Self {
+        Rope(Rc::new(RopeNode::Leaf(s)))
+    }
+
+    pub fn len(&self) -> usize {
+        match &*self.0 {
+            RopeNode::Leaf(s) => s.len(),
+            RopeNode::Concat { len, .. } => *len,
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// The text, if it is stored as a single piece.
+    pub fn as_flat(&self) -> Option<&str> {
+        match &*self.0 {
+            RopeNode::Leaf(s) => Some(s),
+            RopeNode::Concat { .. } => None,
+        }
+    }
+
+    pub fn concat(&self, other: &Rope) -> Rope {
+        if self.is_empty() {
+            return other.clone();
+        }
+        if other.is_empty() {
+            return self.clone();
+        }
+        let len = self.len() + other.len();
+        match (self.as_flat(), other.as_flat()) {
+            (Some(x), Some(y)) if len < FLATTEN_BELOW => {
+                Rope::new(format!("{}{}", x, y))
+            }
+            _ => Rope(Rc::new(RopeNode::Concat {
+                len,
+                parts: vec![self.clone(), other.clone()],
+            })),
+        }
+    }
+
+    /// Iterates over the pieces of the text, in order. Ropes can be very deep, so this doesn't
+    /// recurse.
+    fn pieces(&self) -> impl Iterator<Item = &str> {
+        let mut stack = vec![self];
+        std::iter::from_fn(move || loop {
+            match &*stack.pop()?.0 {
+                RopeNode::Leaf(s) => return Some(s.as_str()),
+                RopeNode::Concat { parts, .. } => {
+                    stack.extend(parts.iter().rev())
+                }
+            }
+        })
+    }
+}
+
+impl fmt::Display for Rope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.pieces().try_for_each(|s| f.write_str(s))
+    }
+}
+
+impl fmt::Debug for Rope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.to_string(), f)
+    }
+}
+
+impl From<String> for Rope {
+    fn from(s: String) -> Self {
+        Rope::new(s)
+    }
+}
+
+impl PartialEq for Rope {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+            || (self.len() == other.len()
+                && self
+                    .pieces()
+                    .flat_map(str::bytes)
+                    .eq(other.pieces().flat_map(str::bytes)))
+    }
+}
+impl Eq for Rope {}
+
+/// Dropping a deep rope recursively could overflow the stack, so the nodes that aren't shared
+/// are taken apart iteratively instead.
+impl Drop for RopeNode {
+    fn drop(&mut self) {
+        let mut stack = match self {
+            RopeNode::Leaf(_) => return,
+            RopeNode::Concat { parts, .. } => std::mem::take(parts),
+        };
+        while let Some(rope) = stack.pop() {
+            if let Ok(RopeNode::Concat { parts, .. }) =
+                Rc::try_unwrap(rope.0).as_mut()
+            {
+                stack.append(parts);
+            }
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use std::cell::RefCell;
+
+use crate::builtins::Builtin;
+use crate::semantics::{Nir, NirKind};
+use crate::syntax::{Label, Span};
+use crate::Normalized;
+
+/// Callback called on every reduction while tracing. See `with_tracer`.
+pub type Tracer = dyn FnMut(&Reduction<'_>);
+
+thread_local! {
+    /// The tracer in use, if evaluation is being traced.
+    static TRACER: RefCell<Option<Box<Tracer>>> = const { RefCell::new(None) };
+}
+
+/// What got reduced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReductionKind {
+    /// A lambda applied to an argument.
+    Beta,
+    /// A builtin that computed a result from its arguments.
+    Builtin(Builtin),
+    /// A builtin provided by the host application that computed a result.
+    Foreign(Label),
+}
+
+/// One reduction step: a function applied to its last argument, and the value it reduced to.
+/// Reductions can happen under binders, in which case the values involved have free variables
+/// and can't be converted back to expressions on their own.
+#[derive(Debug)]
+pub struct Reduction<'a> {
+    kind: ReductionKind,
+    span: Option<Span>,
+    function: &'a Nir,
+    argument: &'a Nir,
+    result: &'a NirKind,
+}
+
+impl<'a> Reduction<'a> {
+    pub fn kind(&self) -> &ReductionKind {
+        &self.kind
+    }
+    /// The location of the body of the lambda, for beta-reductions.
+    pub fn span(&self) -> Option<&Span> {
+        self.span.as_ref()
+    }
+    /// The function that got applied, with the arguments given before the last one. Returns
+    /// `None` if it has free variables.
+    pub fn function(&self) -> Option<Normalized> {
+        closed(self.function.clone())
+    }
+    /// The last argument, that triggered the reduction. Returns `None` if it has free variables.
+    pub fn argument(&self) -> Option<Normalized> {
+        closed(self.argument.clone())
+    }
+    /// The value the application reduced to. Returns `None` if it has free variables.
+    pub fn result(&self) -> Option<Normalized> {
+        closed(Nir::from_kind(self.result.clone()))
+    }
+}
+
+fn closed(nir: Nir) -> Option<Normalized> {
+    if nir.is_closed() {
+        Some(Normalized(nir))
+    } else {
+        None
+    }
+}
+
+/// Run `f`, calling `tracer` on every reduction it performs. Evaluation is lazy, so only the
+/// reductions needed by `f` happen. Reductions that happen inside `tracer`, e.g. when it looks at
+/// the values, aren't traced.
+pub fn with_tracer<R>(
+    tracer: impl FnMut(&Reduction<'_>) + 'static,
+    f: impl FnOnce() -> R,
+) -> R {
+    let outer = TRACER.with(|t| t.borrow_mut().replace(Box::new(tracer)));
+    let _restore = Restore(outer);
+    f()
+}
+
+/// Report the reduction of `function` applied to `argument` to the tracer, if there is one.
+pub fn trace_reduction(function: &Nir, argument: &Nir, result: &NirKind) {
+    // Take the tracer out while it runs, so that evaluation it triggers isn't traced.
+    let tracer = match TRACER.with(|t| t.borrow_mut().take()) {
+        Some(tracer) => tracer,
+        None => return,
+    };
+    let mut tracer = Restore(Some(tracer));
+    let (kind, span) = match function.kind() {
+        NirKind::LamClosure { closure, .. } => {
+            (ReductionKind::Beta, closure.span())
+        }
+        NirKind::AppliedBuiltin(closure) => match result {
+            // Only report builtins that actually computed something.
+            NirKind::AppliedBuiltin(ret)
+                if ret.builtin() == closure.builtin() =>
+            {
+                return
+            }
+            _ => (ReductionKind::Builtin(closure.builtin()), None),
+        },
+        NirKind::Foreign(closure) => match result {
+            NirKind::Foreign(ret) if ret.name() == closure.name() => return,
+            _ => (ReductionKind::Foreign(closure.name().clone()), None),
+        },
+        _ => return,
+    };
+    (tracer.0.as_mut().unwrap())(&Reduction {
+        kind,
+        span,
+        function,
+        argument,
+        result,
+    });
+}
+
+/// Puts a tracer back in place when dropped, so that it gets restored even on unwinding.
+struct Restore(Option<Box<Tracer>>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        let tracer = self.0.take();
+        TRACER.with(|t| *t.borrow_mut() = tracer);
+    }
+}
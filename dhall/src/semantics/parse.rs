@@ -1,34 +1,135 @@
 use std::fs::File;
 use std::io::Read;
 use std::path::Path;
+use std::time::Duration;
 use url::Url;
 
-use crate::error::Error;
+use crate::error::{
+    Error, FetchError, FetchErrorKind, ImportError, ParseError,
+};
 use crate::semantics::resolve::ImportLocation;
 use crate::syntax::binary;
-use crate::syntax::parse_expr;
+use crate::syntax::{
+    parse_expr, parse_expr_from_reader, parse_expr_lossless,
+    parse_expr_with_nesting_limit, parse_expr_with_recovery, SyntaxTree,
+};
 use crate::Parsed;
 
 pub fn parse_file(f: &Path) -> Result<Parsed, Error> {
-    let text = std::fs::read_to_string(f)?;
-    let expr = parse_expr(&text)?;
+    let expr = parse_expr_from_reader(File::open(f)?)?;
     let root = ImportLocation::Local(f.to_owned());
     Ok(Parsed(expr, root))
 }
 
+pub fn parse_reader(reader: impl Read) -> Result<Parsed, Error> {
+    let expr = parse_expr_from_reader(reader)?;
+    let root = ImportLocation::Missing;
+    Ok(Parsed(expr, root))
+}
+
 pub fn parse_remote(url: Url) -> Result<Parsed, Error> {
-    let body = reqwest::blocking::get(url.clone()).unwrap().text().unwrap();
+    parse_remote_with_headers(url, &[])
+}
+
+pub fn parse_remote_with_headers(
+    url: Url,
+    headers: &[(String, String)],
+) -> Result<Parsed, Error> {
+    let body = download(url.clone(), headers)?;
     let expr = parse_expr(&body)?;
     let root = ImportLocation::Remote(url);
     Ok(Parsed(expr, root))
 }
 
+/// Fetches the contents of a remote file, sending the provided headers.
+pub fn download(
+    url: Url,
+    headers: &[(String, String)],
+) -> Result<String, Error> {
+    let client = reqwest::blocking::Client::new();
+    Ok(download_response(&client, url, headers, None, None)?.0)
+}
+
+/// Like `download`, but also returns the `Access-Control-Allow-Origin` header of the response.
+/// `timeout` bounds the time taken by the whole request, including reading the body. If
+/// `max_len` is set, at most one byte more than it is read, so that callers can detect overly
+/// large responses without downloading them entirely.
+pub(crate) fn download_response(
+    client: &reqwest::blocking::Client,
+    url: Url,
+    headers: &[(String, String)],
+    timeout: Option<Duration>,
+    max_len: Option<usize>,
+) -> Result<(String, Option<String>), Error> {
+    let fetch_error = |url: &Url, err: reqwest::Error| {
+        let kind = if err.is_timeout() {
+            FetchErrorKind::Timeout
+        } else {
+            FetchErrorKind::Connection(err.to_string())
+        };
+        let url = url.clone();
+        ImportError::Fetch(Box::new(FetchError { url, kind }))
+    };
+    let mut request = client.get(url.clone());
+    for (name, value) in headers {
+        request = request.header(name.as_str(), value.as_str());
+    }
+    if let Some(timeout) = timeout {
+        request = request.timeout(timeout);
+    }
+    let response = request.send().map_err(|err| fetch_error(&url, err))?;
+    let status = response.status();
+    if !status.is_success() {
+        let kind = FetchErrorKind::Status(status.as_u16());
+        return Err(
+            ImportError::Fetch(Box::new(FetchError { url, kind })).into()
+        );
+    }
+    let allow_origin = response
+        .headers()
+        .get(reqwest::header::ACCESS_CONTROL_ALLOW_ORIGIN)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned);
+    let text = match max_len {
+        None => response.text().map_err(|err| fetch_error(&url, err))?,
+        Some(max_len) => {
+            let mut text = String::new();
+            response
+                .take(max_len as u64 + 1)
+                .read_to_string(&mut text)?;
+            text
+        }
+    };
+    Ok((text, allow_origin))
+}
+
 pub fn parse_str(s: &str) -> Result<Parsed, Error> {
     let expr = parse_expr(s)?;
     let root = ImportLocation::Missing;
     Ok(Parsed(expr, root))
 }
 
+pub fn parse_str_with_nesting_limit(
+    s: &str,
+    max_depth: Option<usize>,
+) -> Result<Parsed, Error> {
+    let expr = parse_expr_with_nesting_limit(s, max_depth)?;
+    let root = ImportLocation::Missing;
+    Ok(Parsed(expr, root))
+}
+
+pub fn parse_str_lossless(s: &str) -> Result<(Parsed, SyntaxTree), Error> {
+    let (expr, tree) = parse_expr_lossless(s)?;
+    let root = ImportLocation::Missing;
+    Ok((Parsed(expr, root), tree))
+}
+
+pub fn parse_str_with_recovery(s: &str) -> (Option<Parsed>, Vec<ParseError>) {
+    let (expr, errors) = parse_expr_with_recovery(s);
+    let parsed = expr.map(|expr| Parsed(expr, ImportLocation::Missing));
+    (parsed, errors)
+}
+
 pub fn parse_binary(data: &[u8]) -> Result<Parsed, Error> {
     let expr = binary::decode(data)?;
     let root = ImportLocation::Missing;
@@ -0,0 +1,164 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+use crate::error::Error;
+use crate::semantics::{skip_resolve_expr, Hir, TypedHir};
+use crate::syntax::{binary, Hash};
+use crate::Parsed;
+
+/// Semantic cache for protected imports, stored as files named after the multihash of their
+/// contents (`1220` followed by the hex-encoded sha256). The layout is the one described in the
+/// standard, so caches can be shared with other implementations.
+///
+/// The cache also keeps the last downloaded version of remote files in its `http` subdirectory,
+/// which is used when resolving imports offline, and the normal forms of whole expressions in
+/// its `normalized` subdirectory (see `Typed::normalize_cached`).
+#[derive(Debug, Clone)]
+pub struct Cache {
+    cache_dir: PathBuf,
+}
+
+/// Finds the default cache directory: `$DHALL_CACHE` if set, otherwise `$XDG_CACHE_HOME/dhall`
+/// or an OS-specific fallback. Setting `DHALL_CACHE` to an empty value disables the cache.
+fn default_cache_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("DHALL_CACHE") {
+        return if dir.is_empty() {
+            None
+        } else {
+            Some(PathBuf::from(dir))
+        };
+    }
+    let base = match env::var_os("XDG_CACHE_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None if cfg!(windows) => PathBuf::from(env::var_os("LOCALAPPDATA")?),
+        None => Path::new(&env::var_os("HOME")?).join(".cache"),
+    };
+    Some(base.join("dhall"))
+}
+
+impl Cache {
+    /// Finds the cache in the default location. The directory is only created once something is
+    /// written to it. Returns `None` if no suitable location exists.
+    pub fn new() -> Option<Cache> {
+        Some(Cache {
+            cache_dir: default_cache_dir()?,
+        })
+    }
+
+    pub fn with_dir(cache_dir: PathBuf) -> Result<Cache, Error> {
+        std::fs::create_dir_all(&cache_dir)?;
+        Ok(Cache { cache_dir })
+    }
+
+    fn entry_path(&self, hash: &Hash) -> PathBuf {
+        let Hash::SHA256(hash) = hash;
+        self.cache_dir.join(format!("1220{}", hex::encode(hash)))
+    }
+
+    /// Looks up an expression by hash. Entries whose contents don't match their name are
+    /// ignored.
+    pub fn get(&self, hash: &Hash) -> Option<TypedHir> {
+        let data = std::fs::read(self.entry_path(hash)).ok()?;
+        let Hash::SHA256(expected) = hash;
+        if expected[..] != crate::hash::sha256(&data)[..] {
+            return None;
+        }
+        let typed = Parsed::parse_binary(&data)
+            .ok()?
+            .skip_resolve()
+            .ok()?
+            .typecheck()
+            .ok()?;
+        Some((typed.normalize().to_hir(), typed.ty().clone()))
+    }
+
+    fn remote_path(&self, url: &Url) -> PathBuf {
+        let key = crate::hash::sha256(url.as_str().as_bytes());
+        self.cache_dir.join("http").join(hex::encode(key))
+    }
+
+    /// The `Access-Control-Allow-Origin` header of a remote file is kept next to its contents.
+    fn allow_origin_path(&self, url: &Url) -> PathBuf {
+        self.remote_path(url).with_extension("allow-origin")
+    }
+
+    /// Looks up the last downloaded contents of a remote file, along with the
+    /// `Access-Control-Allow-Origin` header it was served with, if any.
+    pub fn get_remote(&self, url: &Url) -> Option<(String, Option<String>)> {
+        let text = std::fs::read_to_string(self.remote_path(url)).ok()?;
+        let allow_origin =
+            std::fs::read_to_string(self.allow_origin_path(url)).ok();
+        Some((text, allow_origin))
+    }
+
+    /// Records the contents of a remote file and its `Access-Control-Allow-Origin` header, for
+    /// use when the network is not available.
+    pub fn insert_remote(
+        &self,
+        url: &Url,
+        text: &str,
+        allow_origin: Option<&str>,
+    ) -> Result<(), Error> {
+        let path = self.remote_path(url);
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        // Remove the old header first, so that the new contents never appear with it.
+        let origin_path = self.allow_origin_path(url);
+        match std::fs::remove_file(&origin_path) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                return Err(err.into())
+            }
+            _ => {}
+        }
+        std::fs::write(path, text)?;
+        if let Some(allow_origin) = allow_origin {
+            std::fs::write(origin_path, allow_origin)?;
+        }
+        Ok(())
+    }
+
+    /// Entries of normal forms are keyed by the hash of the input expression, with its imports
+    /// inlined and its variables alpha-normalized. Unlike the semantic hash, this can be computed
+    /// without normalizing.
+    fn normalized_path(&self, input: &Hir) -> Result<PathBuf, Error> {
+        let data = binary::encode(&input.to_expr_alpha())?;
+        let key = crate::hash::sha256(&data);
+        Ok(self.cache_dir.join("normalized").join(hex::encode(key)))
+    }
+
+    /// Looks up the normal form of a resolved and typechecked expression. Expressions that use
+    /// builtins provided by the host are never found, since their normal form depends on the
+    /// host's implementation of the builtins.
+    pub fn get_normalized(&self, input: &Hir) -> Option<Hir> {
+        if input.uses_foreign() {
+            return None;
+        }
+        let data = std::fs::read(self.normalized_path(input).ok()?).ok()?;
+        skip_resolve_expr(&binary::decode(&data).ok()?).ok()
+    }
+
+    /// Records the normal form of a resolved and typechecked expression. Does nothing for
+    /// expressions that use builtins provided by the host.
+    pub fn insert_normalized(
+        &self,
+        input: &Hir,
+        normalized: &Hir,
+    ) -> Result<(), Error> {
+        if input.uses_foreign() {
+            return Ok(());
+        }
+        let path = self.normalized_path(input)?;
+        let data = binary::encode(&normalized.to_expr_noopts())?;
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        std::fs::write(path, data)?;
+        Ok(())
+    }
+
+    /// Stores an already normalized expression under the given hash.
+    pub fn insert(&self, hash: &Hash, hir: &Hir) -> Result<(), Error> {
+        let data = binary::encode(&hir.to_expr_alpha())?;
+        std::fs::create_dir_all(&self.cache_dir)?;
+        std::fs::write(self.entry_path(hash), data)?;
+        Ok(())
+    }
+}
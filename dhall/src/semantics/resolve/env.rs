@@ -1,7 +1,11 @@
 use std::collections::HashMap;
+use std::time::Duration;
 
-use crate::error::{Error, ImportError};
-use crate::semantics::{AlphaVar, ImportLocation, TypedHir, VarEnv};
+use percent_encoding::percent_decode_str;
+use url::Url;
+
+use crate::error::{Error, ImportError, ImportLimit};
+use crate::semantics::{AlphaVar, Cache, ImportLocation, TypedHir, VarEnv};
 use crate::syntax::{Label, V};
 
 /// Environment for resolving names.
@@ -18,6 +22,30 @@ pub type ImportStack = Vec<ImportLocation>;
 pub struct ImportEnv {
     cache: ImportCache,
     stack: ImportStack,
+    file_cache: Option<Cache>,
+    offline: bool,
+    overrides: ImportCache,
+    sources: HashMap<ImportLocation, String>,
+    http_client: Option<reqwest::blocking::Client>,
+    connect_timeout: Option<Duration>,
+    timeout: Option<Duration>,
+    retries: u32,
+    mirrors: Vec<(String, ImportLocation)>,
+    limits: ImportLimits,
+    imports: usize,
+    fetched_bytes: usize,
+}
+
+/// Bounds on the work done to resolve imports, e.g. to safely resolve untrusted expressions.
+/// The limits apply to everything resolved with the same `ImportEnv`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ImportLimits {
+    /// The maximum number of nested imports.
+    pub max_depth: Option<usize>,
+    /// The maximum number of distinct imports.
+    pub max_imports: Option<usize>,
+    /// The maximum number of bytes read from files, the network and environment variables.
+    pub max_bytes: Option<usize>,
 }
 
 impl NameEnv {
@@ -66,7 +94,268 @@ impl NameEnv {
 
 impl ImportEnv {
     pub fn new() -> Self {
-        ImportEnv::default()
+        ImportEnv {
+            file_cache: Cache::new(),
+            ..ImportEnv::default()
+        }
+    }
+
+    /// Forbids fetching remote imports. Remote imports can still be resolved if they are found in
+    /// the cache, either by hash or because they were downloaded before.
+    pub fn offline(self, offline: bool) -> Self {
+        ImportEnv { offline, ..self }
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.offline
+    }
+
+    /// Uses the given values in place of the corresponding imports instead of fetching them.
+    /// See `parse_overrides`.
+    pub fn overrides(self, overrides: ImportCache) -> Self {
+        ImportEnv { overrides, ..self }
+    }
+
+    /// Fetches remote imports whose URL starts with one of the given prefixes from the associated
+    /// location instead: either another URL, or a local directory. The rest of the URL is appended
+    /// to that location. The first matching prefix is used. The imports keep their original
+    /// location, so relative imports and hashes work as if they had been fetched from the
+    /// original URL. See `parse_mirrors`.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use dhall::semantics::{ImportEnv, ImportLocation};
+    ///
+    /// let vendor = std::env::temp_dir().join("dhall-mirror-doctest");
+    /// std::fs::create_dir_all(&vendor)?;
+    /// std::fs::write(vendor.join("package.dhall"), "./answer.dhall")?;
+    /// std::fs::write(vendor.join("answer.dhall"), "42")?;
+    ///
+    /// let prefix = "https://example.com/lib/".to_owned();
+    /// let mut env = ImportEnv::new()
+    ///     .mirrors(vec![(prefix, ImportLocation::Local(vendor))]);
+    /// let value = dhall::Parsed::parse_str("https://example.com/lib/package.dhall")?
+    ///     .resolve_with(&mut env)?
+    ///     .typecheck()?
+    ///     .normalize();
+    /// assert_eq!(value.to_string(), "42");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn mirrors(mut self, mirrors: Vec<(String, ImportLocation)>) -> Self {
+        self.mirrors.extend(mirrors);
+        self
+    }
+
+    /// Where to fetch the given URL from, if it is mirrored.
+    pub fn get_mirror(&self, url: &Url) -> Option<ImportLocation> {
+        self.mirrors.iter().find_map(|(prefix, target)| {
+            let rest = url.as_str().strip_prefix(prefix.as_str())?;
+            match target {
+                ImportLocation::Local(dir) => {
+                    let rest = rest.split(['?', '#']).next()?;
+                    let rest = percent_decode_str(rest).decode_utf8_lossy();
+                    Some(ImportLocation::Local(dir.join(rest.as_ref())))
+                }
+                ImportLocation::Remote(base) => {
+                    let url = Url::parse(&format!("{}{}", base, rest)).ok()?;
+                    Some(ImportLocation::Remote(url))
+                }
+                _ => None,
+            }
+        })
+    }
+
+    /// Serves the given locations from memory instead of reading them from the filesystem, the
+    /// network or the environment. Local files are looked up by their absolute path. Other
+    /// locations are fetched as usual.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), dhall::error::Error> {
+    /// use std::collections::HashMap;
+    /// use std::path::PathBuf;
+    /// use dhall::semantics::{ImportEnv, ImportLocation};
+    ///
+    /// let mut sources = HashMap::new();
+    /// sources.insert(
+    ///     ImportLocation::Local(PathBuf::from("/virtual/a.dhall")),
+    ///     "./b.dhall + 1".to_owned(),
+    /// );
+    /// sources.insert(
+    ///     ImportLocation::Local(PathBuf::from("/virtual/b.dhall")),
+    ///     "41".to_owned(),
+    /// );
+    /// let mut env = ImportEnv::new().import_map(sources);
+    /// let value = dhall::Parsed::parse_str("/virtual/a.dhall")?
+    ///     .resolve_with(&mut env)?
+    ///     .typecheck()?
+    ///     .normalize();
+    /// assert_eq!(value.to_string(), "42");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn import_map(self, sources: HashMap<ImportLocation, String>) -> Self {
+        ImportEnv { sources, ..self }
+    }
+
+    /// Uses the given client to fetch remote imports, e.g. to trust additional root certificates
+    /// or to authenticate with a client certificate. By default, a client with the standard
+    /// configuration is used; it honors the `HTTP_PROXY`, `HTTPS_PROXY` and `NO_PROXY`
+    /// environment variables.
+    ///
+    /// ```
+    /// use dhall::semantics::ImportEnv;
+    ///
+    /// let client = reqwest::blocking::Client::builder()
+    ///     .timeout(std::time::Duration::from_secs(10))
+    ///     .build()
+    ///     .unwrap();
+    /// let env = ImportEnv::new().http_client(client);
+    /// ```
+    pub fn http_client(self, client: reqwest::blocking::Client) -> Self {
+        ImportEnv {
+            http_client: Some(client),
+            ..self
+        }
+    }
+
+    /// Bounds the time taken to connect to a server when fetching a remote import. This is
+    /// ignored if a custom client is set with `http_client`.
+    pub fn connect_timeout(self, timeout: Duration) -> Self {
+        ImportEnv {
+            connect_timeout: Some(timeout),
+            ..self
+        }
+    }
+
+    /// Bounds the time taken to download each remote import, from sending the request to reading
+    /// the whole response.
+    pub fn timeout(self, timeout: Duration) -> Self {
+        ImportEnv {
+            timeout: Some(timeout),
+            ..self
+        }
+    }
+
+    /// Tries fetching remote imports this many more times when they fail because of a timeout, a
+    /// connection error or a server error (5xx). Attempts are spaced with an exponential backoff
+    /// starting at 100ms. Defaults to 0.
+    pub fn retries(self, retries: u32) -> Self {
+        ImportEnv { retries, ..self }
+    }
+
+    pub fn get_http_client(&self) -> Result<reqwest::blocking::Client, Error> {
+        if let Some(client) = &self.http_client {
+            return Ok(client.clone());
+        }
+        let mut builder = reqwest::blocking::Client::builder();
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        builder
+            .build()
+            .map_err(|err| std::io::Error::other(err).into())
+    }
+
+    pub fn get_timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    pub fn get_retries(&self) -> u32 {
+        self.retries
+    }
+
+    /// Fails resolution with `ImportError::LimitExceeded` when it goes over the given limits.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), dhall::error::Error> {
+    /// use dhall::semantics::{ImportEnv, ImportLimits};
+    ///
+    /// let limits = ImportLimits {
+    ///     max_bytes: Some(4),
+    ///     ..ImportLimits::default()
+    /// };
+    /// let mut env = ImportEnv::new().limits(limits);
+    /// std::env::set_var("DHALL_LIMITS_DOCTEST", "\"too long\"");
+    /// let result =
+    ///     dhall::Parsed::parse_str("env:DHALL_LIMITS_DOCTEST")?.resolve_with(&mut env);
+    /// assert_eq!(
+    ///     result.unwrap_err().to_string(),
+    ///     "Import limit exceeded: more than 4 bytes were read"
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn limits(self, limits: ImportLimits) -> Self {
+        ImportEnv { limits, ..self }
+    }
+
+    /// The number of bytes that can still be read before going over the limit.
+    pub fn remaining_bytes(&self) -> Option<usize> {
+        let max = self.limits.max_bytes?;
+        Some(max.saturating_sub(self.fetched_bytes))
+    }
+
+    /// Records that `len` bytes were read, failing if this goes over the limit.
+    pub fn record_fetched_bytes(&mut self, len: usize) -> Result<(), Error> {
+        self.fetched_bytes += len;
+        match self.limits.max_bytes {
+            Some(max) if self.fetched_bytes > max => {
+                Err(ImportError::LimitExceeded(ImportLimit::Bytes(max)).into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn get_source(&self, location: &ImportLocation) -> Option<&String> {
+        self.sources.get(location)
+    }
+
+    pub fn get_override(&self, location: &ImportLocation) -> Option<&TypedHir> {
+        self.overrides.get(location)
+    }
+
+    /// Uses the given semantic cache instead of the one in the default location, or no cache at
+    /// all if `None`.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), dhall::error::Error> {
+    /// use dhall::semantics::{Cache, ImportEnv};
+    ///
+    /// let dir = std::env::temp_dir().join("dhall-cache-doctest");
+    /// let custom = ImportEnv::new().semantic_cache(Some(Cache::with_dir(dir)?));
+    /// let uncached = ImportEnv::new().semantic_cache(None);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn semantic_cache(self, file_cache: Option<Cache>) -> Self {
+        ImportEnv { file_cache, ..self }
+    }
+
+    pub fn file_cache(&self) -> Option<&Cache> {
+        self.file_cache.as_ref()
+    }
+
+    /// Forgets all the imports resolved so far.
+    pub fn clear_cache(&mut self) {
+        self.cache.clear();
+    }
+
+    /// Forgets the resolved imports whose contents may have changed since, i.e. all of them but
+    /// remote files that aren't mirrored to local files. Remote files can't import local files or
+    /// environment variables, so what they resolved to stays valid.
+    pub fn clear_local_imports(&mut self) {
+        let cache = std::mem::take(&mut self.cache);
+        self.cache = cache
+            .into_iter()
+            .filter(|(location, _)| match location {
+                ImportLocation::Remote(url) => !matches!(
+                    self.get_mirror(url),
+                    Some(ImportLocation::Local(_))
+                ),
+                _ => false,
+            })
+            .collect();
     }
 
     pub fn handle_import(
@@ -82,6 +371,19 @@ impl ImportEnv {
         Ok(match self.cache.get(&location) {
             Some(expr) => expr.clone(),
             None => {
+                if let Some(max) = self.limits.max_depth {
+                    if self.stack.len() >= max {
+                        let limit = ImportLimit::Depth(max);
+                        return Err(ImportError::LimitExceeded(limit).into());
+                    }
+                }
+                self.imports += 1;
+                if let Some(max) = self.limits.max_imports {
+                    if self.imports > max {
+                        let limit = ImportLimit::Imports(max);
+                        return Err(ImportError::LimitExceeded(limit).into());
+                    }
+                }
                 let expr = {
                     // Push the current location on the stack
                     self.stack.push(location);
@@ -1,6 +1,9 @@
 use crate::error::TypeError;
-use crate::semantics::{type_with, NameEnv, Nir, NzEnv, Tir, TyEnv, Type};
-use crate::syntax::{Expr, ExprKind, Span, V};
+use crate::semantics::{
+    type_with, ForeignBuiltin, NameEnv, Nir, NzEnv, Tir, TyEnv, Type,
+};
+use crate::shared::{ensure_stack, stack_is_low};
+use crate::syntax::{Const, Expr, ExprKind, Span, V};
 use crate::ToExprOptions;
 
 /// Stores an alpha-normalized variable.
@@ -15,12 +18,14 @@ pub enum HirKind {
     Var(AlphaVar),
     /// Result of resolving an import.
     Import(Hir, Type),
-    // Forbidden ExprKind variants: Var, Import, Completion
+    /// A builtin provided by the host application.
+    Foreign(ForeignBuiltin),
+    // Forbidden ExprKind variants: Var, Import
     Expr(ExprKind<Hir>),
 }
 
 // An expression with resolved variables and imports.
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct Hir {
     kind: Box<HirKind>,
     span: Span,
@@ -80,6 +85,42 @@ impl Hir {
         self.typecheck(&TyEnv::new())
     }
 
+    /// Whether this expression uses builtins provided by the host application. Their
+    /// implementation isn't part of the expression, so neither is what they evaluate to.
+    pub fn uses_foreign(&self) -> bool {
+        ensure_stack(|| match self.kind() {
+            HirKind::Foreign(_) => true,
+            HirKind::Var(_) => false,
+            HirKind::Import(hir, _) => hir.uses_foreign(),
+            HirKind::Expr(e) => e
+                .traverse_ref(
+                    |x| if x.uses_foreign() { Err(()) } else { Ok(()) },
+                )
+                .is_err(),
+        })
+    }
+
+    /// The locations where the given variable is used in this expression.
+    pub fn occurrences(&self, var: AlphaVar) -> Vec<Span> {
+        let mut spans = Vec::new();
+        self.collect_occurrences(var.idx(), &mut spans);
+        spans
+    }
+    fn collect_occurrences(&self, idx: usize, spans: &mut Vec<Span>) {
+        match self.kind() {
+            HirKind::Var(v) if v.idx() == idx => spans.push(self.span()),
+            // Imports are closed.
+            HirKind::Var(_) | HirKind::Import(..) | HirKind::Foreign(_) => {}
+            HirKind::Expr(e) => {
+                let _: Result<_, ()> = e.traverse_ref_maybe_binder(|l, x| {
+                    let idx = if l.is_some() { idx + 1 } else { idx };
+                    x.collect_occurrences(idx, spans);
+                    Ok(())
+                });
+            }
+        }
+    }
+
     /// Eval the Hir. It will actually get evaluated only as needed on demand.
     pub fn eval(&self, env: impl Into<NzEnv>) -> Nir {
         Nir::new_thunk(env.into(), self.clone())
@@ -92,41 +133,71 @@ impl Hir {
 }
 
 fn hir_to_expr(hir: &Hir, opts: ToExprOptions, env: &mut NameEnv) -> Expr {
-    let kind = match hir.kind() {
-        HirKind::Var(v) if opts.alpha => ExprKind::Var(V("_".into(), v.idx())),
-        HirKind::Var(v) => ExprKind::Var(env.label_var(*v)),
-        HirKind::Import(hir, _) => {
-            return hir_to_expr(hir, opts, &mut NameEnv::new())
-        }
-        HirKind::Expr(e) => {
-            let e = e.map_ref_maybe_binder(|l, hir| {
-                if let Some(l) = l {
-                    env.insert_mut(l);
-                }
-                let e = hir_to_expr(hir, opts, env);
-                if l.is_some() {
-                    env.remove_mut();
-                }
-                e
-            });
+    ensure_stack(|| {
+        let kind = match hir.kind() {
+            HirKind::Var(v) if opts.alpha => {
+                ExprKind::Var(V("_".into(), v.idx()))
+            }
+            HirKind::Var(v) => ExprKind::Var(env.label_var(*v)),
+            HirKind::Import(hir, _) => {
+                return hir_to_expr(hir, opts, &mut NameEnv::new())
+            }
+            // Foreign builtins have no syntax; they are printed as the name they were registered
+            // with.
+            HirKind::Foreign(f) => ExprKind::Var(V(f.name().clone(), 0)),
+            HirKind::Expr(e) => {
+                let e = e.map_ref_maybe_binder(|l, hir| {
+                    if let Some(l) = l {
+                        env.insert_mut(l);
+                    }
+                    let e = hir_to_expr(hir, opts, env);
+                    if l.is_some() {
+                        env.remove_mut();
+                    }
+                    e
+                });
 
-            match e {
-                ExprKind::Lam(_, t, e) if opts.alpha => {
-                    ExprKind::Lam("_".into(), t, e)
-                }
-                ExprKind::Pi(_, t, e) if opts.alpha => {
-                    ExprKind::Pi("_".into(), t, e)
+                match e {
+                    ExprKind::Lam(_, t, e) if opts.alpha => {
+                        ExprKind::Lam("_".into(), t, e)
+                    }
+                    ExprKind::Pi(_, t, e) if opts.alpha => {
+                        ExprKind::Pi("_".into(), t, e)
+                    }
+                    e => e,
                 }
-                e => e,
             }
+        };
+        Expr::new(kind, hir.span())
+    })
+}
+
+// Like for `Expr`, the traversals implemented here move to a fresh stack segment when the current
+// one runs low.
+impl Clone for Hir {
+    fn clone(&self) -> Self {
+        ensure_stack(|| Hir {
+            kind: self.kind.clone(),
+            span: self.span.clone(),
+        })
+    }
+}
+
+impl Drop for Hir {
+    fn drop(&mut self) {
+        if stack_is_low() {
+            let kind = std::mem::replace(
+                &mut self.kind,
+                Box::new(HirKind::Expr(ExprKind::Const(Const::Type))),
+            );
+            ensure_stack(|| drop(kind));
         }
-    };
-    Expr::new(kind, hir.span())
+    }
 }
 
 impl std::cmp::PartialEq for Hir {
     fn eq(&self, other: &Self) -> bool {
-        self.kind == other.kind
+        ensure_stack(|| self.kind == other.kind)
     }
 }
 impl std::cmp::Eq for Hir {}
@@ -1,6 +1,12 @@
+pub mod cache;
 pub mod env;
 pub mod hir;
+pub mod pin;
 pub mod resolve;
+pub mod warm;
+pub use cache::*;
 pub use env::*;
 pub use hir::*;
+pub use pin::*;
 pub use resolve::*;
+pub use warm::*;
@@ -0,0 +1,75 @@
+use crate::error::Error;
+use crate::semantics::{resolve, ImportLocation};
+use crate::syntax::{
+    Expr, ExprKind, Hash, Import, ImportMode, ImportTarget, Span,
+};
+use crate::Parsed;
+
+const PRELUDE_AUTHORITY: &str = "prelude.dhall-lang.org";
+
+/// Whether a path component looks like a Prelude release tag, e.g. `v17.0.0`.
+fn is_version_tag(s: &str) -> bool {
+    s.starts_with('v')
+        && s.len() > 1
+        && s[1..].chars().all(|c| c.is_ascii_digit() || c == '.')
+}
+
+/// Rewrites the imports of `prelude.dhall-lang.org` that don't specify a version so that they
+/// point to the given tagged version. If `freeze` is set, Prelude imports that don't have a hash
+/// are also resolved and get their hash added.
+pub fn pin_prelude(
+    parsed: Parsed,
+    version: &str,
+    freeze: bool,
+) -> Result<Parsed, Error> {
+    let Parsed(expr, location) = parsed;
+    let expr = pin_prelude_expr(&expr, &location, version, freeze)?;
+    Ok(Parsed(expr, location))
+}
+
+fn pin_prelude_expr(
+    expr: &Expr,
+    location: &ImportLocation,
+    version: &str,
+    freeze: bool,
+) -> Result<Expr, Error> {
+    let kind = expr
+        .kind()
+        .traverse_ref(|e| pin_prelude_expr(e, location, version, freeze))?;
+    let kind = match kind {
+        ExprKind::Import(mut import) => {
+            let is_prelude = match &mut import.location {
+                ImportTarget::Remote(url)
+                    if url.authority == PRELUDE_AUTHORITY =>
+                {
+                    let path = &mut url.path.file_path;
+                    if !matches!(path.first(), Some(c) if is_version_tag(c)) {
+                        path.insert(0, version.to_owned());
+                    }
+                    true
+                }
+                _ => false,
+            };
+            if is_prelude
+                && freeze
+                && import.hash.is_none()
+                && import.mode == ImportMode::Code
+            {
+                import.hash = Some(hash_import(&import, location)?);
+            }
+            ExprKind::Import(import)
+        }
+        kind => kind,
+    };
+    Ok(Expr::new(kind, expr.span()))
+}
+
+fn hash_import(
+    import: &Import<Expr>,
+    location: &ImportLocation,
+) -> Result<Hash, Error> {
+    let expr = Expr::new(ExprKind::Import(import.clone()), Span::Artificial);
+    let typed = resolve(Parsed(expr, location.clone()))?.typecheck()?;
+    let hash = typed.normalize().hash()?;
+    Ok(Hash::SHA256(hash.into_vec()))
+}
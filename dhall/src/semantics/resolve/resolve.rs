@@ -2,24 +2,36 @@ use itertools::Itertools;
 use std::borrow::Cow;
 use std::collections::BTreeMap;
 use std::env;
-use std::path::PathBuf;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
 use url::Url;
 
 use crate::builtins::Builtin;
-use crate::error::ErrorBuilder;
-use crate::error::{Error, ImportError};
+use crate::error::{
+    CorsError, Error, ErrorKind, ImportError, ReferentialSanityError,
+};
+use crate::error::{ErrorBuilder, TypeErrorKind};
 use crate::operations::{BinOp, OpKind};
-use crate::semantics::{mkerr, Hir, HirKind, ImportEnv, NameEnv, Type};
+use crate::semantics::parse::download_response;
+use crate::semantics::{
+    mkerr, Hir, HirKind, ImportCache, ImportEnv, NameEnv, Nir, NirKind, NzEnv,
+    TyEnv, Type,
+};
+use crate::shared::ensure_stack;
 use crate::syntax;
 use crate::syntax::{
-    Expr, ExprKind, FilePath, FilePrefix, Hash, ImportMode, ImportTarget, Span,
-    UnspannedExpr, URL,
+    parse_expr, percent_encode_path_component, Expr, ExprKind, FilePath,
+    FilePrefix, Hash, ImportMode, ImportTarget, Label, Span, UnspannedExpr,
+    URL,
 };
 use crate::{Parsed, Resolved};
 
-// TODO: evaluate import headers
 pub type Import = syntax::Import<()>;
 
+/// The headers sent with a remote import, as evaluated from its `using` clause.
+pub type Headers = Vec<(String, String)>;
+
 /// Owned Hir with a type. Different from Tir because the Hir is owned.
 pub type TypedHir = (Hir, Type);
 
@@ -46,17 +58,11 @@ impl ImportLocation {
         target: &ImportTarget<()>,
         sanity_check: bool,
     ) -> Result<ImportLocation, Error> {
-        Ok(match target {
+        let location = match target {
             ImportTarget::Local(prefix, path) => {
                 self.chain_local(*prefix, path)?
             }
             ImportTarget::Remote(remote) => {
-                if sanity_check {
-                    if let ImportLocation::Remote(..) = self {
-                        // TODO: allow if CORS check passes
-                        return Err(ImportError::SanityCheck.into());
-                    }
-                }
                 let mut url = Url::parse(&format!(
                     "{}://{}",
                     remote.scheme, remote.authority
@@ -66,15 +72,24 @@ impl ImportLocation {
                 ImportLocation::Remote(url)
             }
             ImportTarget::Env(var_name) => {
-                if sanity_check {
-                    if let ImportLocation::Remote(..) = self {
-                        return Err(ImportError::SanityCheck.into());
-                    }
-                }
                 ImportLocation::Env(var_name.clone())
             }
             ImportTarget::Missing => ImportLocation::Missing,
-        })
+        };
+        // Remote imports may only import other remote imports.
+        match (self, &location) {
+            (
+                ImportLocation::Remote(parent),
+                ImportLocation::Local(..) | ImportLocation::Env(..),
+            ) if sanity_check => {
+                let err = ReferentialSanityError {
+                    parent: parent.clone(),
+                    target: location,
+                };
+                Err(ImportError::ReferentialSanity(Box::new(err)).into())
+            }
+            _ => Ok(location),
+        }
     }
 
     fn chain_local(
@@ -82,92 +97,109 @@ impl ImportLocation {
         prefix: FilePrefix,
         path: &FilePath,
     ) -> Result<ImportLocation, Error> {
-        Ok(match self {
-            ImportLocation::Local(..)
-            | ImportLocation::Env(..)
-            | ImportLocation::Missing => {
-                let dir = match self {
-                    ImportLocation::Local(path) => {
-                        path.parent().unwrap().to_owned()
-                    }
-                    ImportLocation::Env(..) | ImportLocation::Missing => {
-                        std::env::current_dir()?
-                    }
-                    _ => unreachable!(),
-                };
-                let mut dir: Vec<String> = dir
-                    .components()
-                    .map(|component| {
-                        component.as_os_str().to_string_lossy().into_owned()
-                    })
-                    .collect();
-                let root = match prefix {
-                    FilePrefix::Here => dir,
-                    FilePrefix::Parent => {
-                        dir.push("..".to_string());
-                        dir
+        Ok(match (self, prefix) {
+            (
+                ImportLocation::Remote(url),
+                FilePrefix::Here | FilePrefix::Parent,
+            ) => {
+                let mut url = url.clone();
+                if prefix == FilePrefix::Parent {
+                    url = url.join("..")?;
+                }
+                let path = path
+                    .file_path
+                    .iter()
+                    .map(|c| percent_encode_path_component(c))
+                    .join("/");
+                url = url.join(&path)?;
+                ImportLocation::Remote(url)
+            }
+            _ => {
+                // The location is made of a root (`.`, `~` or an absolute root) followed by
+                // path components, which get canonicalized.
+                let (root, dir) = match prefix {
+                    FilePrefix::Here | FilePrefix::Parent => {
+                        let (root, mut dir) = match self {
+                            ImportLocation::Local(path) => {
+                                split_local_dir(path.parent().unwrap())
+                            }
+                            // Imports from an environment variable or from the root expression
+                            // are relative to the current directory.
+                            _ => (PathBuf::from("."), vec![]),
+                        };
+                        if prefix == FilePrefix::Parent {
+                            dir.push("..".to_string());
+                        }
+                        (root, dir)
                     }
-                    FilePrefix::Absolute => vec![],
-                    FilePrefix::Home => vec![],
+                    FilePrefix::Absolute => (PathBuf::from("/"), vec![]),
+                    FilePrefix::Home => (PathBuf::from("~"), vec![]),
                 };
-                let path: Vec<_> = root
+                let path: Vec<_> = dir
                     .into_iter()
                     .chain(path.file_path.iter().cloned())
                     .collect();
-                let path =
+                let mut path =
                     (FilePath { file_path: path }).canonicalize().file_path;
-                let prefix = match prefix {
-                    FilePrefix::Here | FilePrefix::Parent => ".",
-                    FilePrefix::Absolute => "/",
-                    FilePrefix::Home => "~",
-                };
-                let path =
-                    Some(prefix.to_string()).into_iter().chain(path).collect();
-                ImportLocation::Local(path)
-            }
-            ImportLocation::Remote(url) => {
-                let mut url = url.clone();
-                match prefix {
-                    FilePrefix::Here => {}
-                    FilePrefix::Parent => {
-                        url = url.join("..")?;
-                    }
-                    FilePrefix::Absolute => panic!("error"),
-                    FilePrefix::Home => panic!("error"),
+                if root.has_root() {
+                    // The parent of the root directory is itself.
+                    path.retain(|c| c != "..");
                 }
-                url = url.join(&path.file_path.join("/"))?;
-                ImportLocation::Remote(url)
+                ImportLocation::Local(
+                    root.join(path.iter().collect::<PathBuf>()),
+                )
             }
         })
     }
 
-    fn fetch_dhall(self) -> Result<Parsed, Error> {
-        Ok(match self {
-            ImportLocation::Local(path) => Parsed::parse_file(&path)?,
-            ImportLocation::Remote(url) => Parsed::parse_remote(url)?,
-            ImportLocation::Env(var_name) => {
-                let val = match env::var(var_name) {
-                    Ok(val) => val,
-                    Err(_) => return Err(ImportError::MissingEnvVar.into()),
-                };
-                Parsed::parse_str(&val)?
+    /// Whether both locations are remote and share the same origin (scheme, host and port).
+    fn same_origin(&self, other: &ImportLocation) -> bool {
+        match (self, other) {
+            (ImportLocation::Remote(x), ImportLocation::Remote(y)) => {
+                x.origin() == y.origin()
             }
-            ImportLocation::Missing => return Err(ImportError::Missing.into()),
-        })
+            _ => false,
+        }
     }
 
-    fn fetch_text(self) -> Result<String, Error> {
-        Ok(match self {
-            ImportLocation::Local(path) => std::fs::read_to_string(&path)?,
-            ImportLocation::Remote(url) => {
-                reqwest::blocking::get(url).unwrap().text().unwrap()
-            }
-            ImportLocation::Env(var_name) => match env::var(var_name) {
-                Ok(val) => val,
-                Err(_) => return Err(ImportError::MissingEnvVar.into()),
+    /// Reads and parses the file at this location, which is imported from `parent`.
+    fn fetch_dhall(
+        self,
+        env: &mut ImportEnv,
+        parent: &ImportLocation,
+        headers: &[(String, String)],
+    ) -> Result<Parsed, Error> {
+        let text = self.clone().fetch_text(env, parent, headers)?;
+        Ok(Parsed(parse_expr(&text)?, self))
+    }
+
+    /// Reads the file at this location, which is imported from `parent`.
+    fn fetch_text(
+        self,
+        env: &mut ImportEnv,
+        parent: &ImportLocation,
+        headers: &[(String, String)],
+    ) -> Result<String, Error> {
+        let text = match env.get_source(&self) {
+            Some(source) => source.clone(),
+            None => match self {
+                ImportLocation::Local(path) => {
+                    read_file(&expand_home(&path)?, env.remaining_bytes())?
+                }
+                ImportLocation::Remote(url) => {
+                    fetch_remote(env, url, parent, headers)?
+                }
+                ImportLocation::Env(var_name) => match env::var(var_name) {
+                    Ok(val) => val,
+                    Err(_) => return Err(ImportError::MissingEnvVar.into()),
+                },
+                ImportLocation::Missing => {
+                    return Err(ImportError::Missing.into())
+                }
             },
-            ImportLocation::Missing => return Err(ImportError::Missing.into()),
-        })
+        };
+        env.record_fetched_bytes(text.len())?;
+        Ok(text)
     }
 
     fn into_location(self) -> Expr {
@@ -193,6 +225,79 @@ impl ImportLocation {
     }
 }
 
+impl std::fmt::Display for ImportLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ImportLocation::Local(path) => write!(f, "{}", path.display()),
+            ImportLocation::Remote(url) => write!(f, "{}", url),
+            ImportLocation::Env(name) => write!(f, "env:{}", name),
+            ImportLocation::Missing => write!(f, "missing"),
+        }
+    }
+}
+
+/// Reads a file. If `max_len` is set, at most one byte more than it is read.
+fn read_file(path: &Path, max_len: Option<usize>) -> Result<String, Error> {
+    let max_len =
+        max_len.map_or(u64::MAX, |len| (len as u64).saturating_add(1));
+    let mut text = String::new();
+    std::fs::File::open(path)?
+        .take(max_len)
+        .read_to_string(&mut text)?;
+    Ok(text)
+}
+
+/// Replaces a leading `~` with the user's home directory. Locations keep the `~` so that
+/// `as Location` gives back the path as written; it is only expanded to read the file.
+fn expand_home(path: &Path) -> Result<Cow<'_, Path>, Error> {
+    let rest = match path.strip_prefix("~") {
+        Ok(rest) => rest,
+        Err(_) => return Ok(Cow::Borrowed(path)),
+    };
+    let home = env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!(
+                    "cannot resolve `{}`: the home directory is unknown",
+                    path.display()
+                ),
+            )
+        })?;
+    Ok(Cow::Owned(PathBuf::from(home).join(rest)))
+}
+
+/// Splits a directory into its root (`.` for relative paths, `~` for paths relative to the home
+/// directory) and the rest of its components.
+fn split_local_dir(dir: &Path) -> (PathBuf, Vec<String>) {
+    let mut root = PathBuf::new();
+    let mut components = Vec::new();
+    for component in dir.components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => {
+                root.push(component.as_os_str())
+            }
+            Component::CurDir => {}
+            Component::ParentDir => components.push("..".to_string()),
+            Component::Normal(c)
+                if root.as_os_str().is_empty()
+                    && components.is_empty()
+                    && c == "~" =>
+            {
+                root.push("~")
+            }
+            Component::Normal(c) => {
+                components.push(c.to_string_lossy().into_owned())
+            }
+        }
+    }
+    if root.as_os_str().is_empty() {
+        root.push(".");
+    }
+    (root, components)
+}
+
 fn mkexpr(kind: UnspannedExpr) -> Expr {
     Expr::new(kind, Span::Artificial)
 }
@@ -207,18 +312,223 @@ fn make_aslocation_uniontype() -> Expr {
     mkexpr(ExprKind::UnionType(union))
 }
 
+/// The type that the expression in a `using` clause must have.
+fn headers_type() -> Nir {
+    let text = || Nir::from_builtin(Builtin::Text);
+    let mut kts = BTreeMap::new();
+    kts.insert("mapKey".into(), text());
+    kts.insert("mapValue".into(), text());
+    Nir::from_kind(NirKind::ListType(Nir::from_kind(NirKind::RecordType(kts))))
+}
+
+/// Typecheck and evaluate the expression in a `using` clause.
+fn eval_headers(headers: &Hir) -> Result<Headers, Error> {
+    let span = headers.span();
+    let tir = headers.typecheck_noenv()?;
+    if tir.ty().as_nir() != &headers_type() {
+        mkerr(
+            ErrorBuilder::new("invalid type for import headers")
+                .span_err(
+                    span.clone(),
+                    format!(
+                        "this has type `{}`",
+                        tir.ty().to_expr_tyenv(&TyEnv::new())
+                    ),
+                )
+                .help(
+                    "headers must have type \
+                     `List { mapKey : Text, mapValue : Text }`",
+                )
+                .format(),
+        )?
+    }
+    let as_text = |nir: &Nir| match nir.kind() {
+        NirKind::TextLit(t) => t.as_text(),
+        _ => None,
+    };
+    let entries = match tir.eval(NzEnv::new()).kind() {
+        NirKind::EmptyListLit(_) => vec![],
+        NirKind::NEListLit(entries) => entries.iter().cloned().collect(),
+        _ => unreachable!("headers should be a list literal"),
+    };
+    let mut result = Vec::new();
+    for entry in &entries {
+        let (name, value) = match entry.kind() {
+            NirKind::RecordLit(kvs) => (
+                kvs.get(&Label::from("mapKey")).and_then(as_text),
+                kvs.get(&Label::from("mapValue")).and_then(as_text),
+            ),
+            _ => (None, None),
+        };
+        let (name, value) = match (name, value) {
+            (Some(name), Some(value)) => (name, value),
+            _ => unreachable!("headers should be fully normalized"),
+        };
+        if reqwest::header::HeaderName::from_bytes(name.as_bytes()).is_err()
+            || reqwest::header::HeaderValue::from_str(&value).is_err()
+        {
+            mkerr(
+                ErrorBuilder::new(format!("invalid header `{}`", name))
+                    .span_err(span.clone(), "in these headers")
+                    .format(),
+            )?
+        }
+        result.push((name, value));
+    }
+    Ok(result)
+}
+
+fn is_transient(err: &Error) -> bool {
+    match err.kind() {
+        ErrorKind::Resolve(ImportError::Fetch(err)) => err.is_transient(),
+        _ => false,
+    }
+}
+
+/// Checks that the server of `url` allows `parent` to import it, if `parent` is a remote file of
+/// another origin: the `Access-Control-Allow-Origin` header must match the importing origin or be
+/// `*`.
+fn check_cors(
+    url: &Url,
+    parent: &ImportLocation,
+    allow_origin: Option<String>,
+) -> Result<(), Error> {
+    if let ImportLocation::Remote(parent) = parent {
+        let origin = parent.origin().ascii_serialization();
+        if parent.origin() != url.origin()
+            && allow_origin.as_deref() != Some("*")
+            && allow_origin.as_deref() != Some(origin.as_str())
+        {
+            let err = CorsError {
+                origin,
+                url: url.clone(),
+                allow_origin,
+            };
+            return Err(ImportError::Cors(Box::new(err)).into());
+        }
+    }
+    Ok(())
+}
+
+/// Downloads a remote file, recording it in the HTTP cache. In offline mode, the file is only
+/// looked up in that cache.
+///
+/// If the file is imported from a remote file of another origin, the server must allow it by
+/// sending an `Access-Control-Allow-Origin` header that matches the importing origin or is `*`.
+/// The cache records that header, so that the same check applies offline. It is not checked for
+/// mirrored files, since the mirror is not the server that the importing file refers to.
+fn fetch_remote(
+    env: &ImportEnv,
+    url: Url,
+    parent: &ImportLocation,
+    headers: &[(String, String)],
+) -> Result<String, Error> {
+    let (url, mirrored) = match env.get_mirror(&url) {
+        Some(ImportLocation::Local(path)) => {
+            return read_file(&path, env.remaining_bytes())
+        }
+        Some(ImportLocation::Remote(mirror)) => (mirror, true),
+        _ => (url, false),
+    };
+    let cache = env.file_cache();
+    if env.is_offline() {
+        let (text, allow_origin) =
+            match cache.and_then(|cache| cache.get_remote(&url)) {
+                Some(entry) => entry,
+                None => return Err(ImportError::Offline(url).into()),
+            };
+        if !mirrored {
+            check_cors(&url, parent, allow_origin)?;
+        }
+        return Ok(text);
+    }
+    let client = env.get_http_client()?;
+    let mut attempt = 0;
+    let (text, allow_origin) = loop {
+        let result = download_response(
+            &client,
+            url.clone(),
+            headers,
+            env.get_timeout(),
+            env.remaining_bytes(),
+        );
+        match result {
+            Err(err) if attempt < env.get_retries() && is_transient(&err) => {
+                std::thread::sleep(Duration::from_millis(
+                    100 << attempt.min(10),
+                ));
+                attempt += 1;
+            }
+            result => break result?,
+        }
+    };
+    if let Some(cache) = cache {
+        // Failing to write to the cache is not an error.
+        let _ = cache.insert_remote(&url, &text, allow_origin.as_deref());
+    }
+    if !mirrored {
+        check_cors(&url, parent, allow_origin)?;
+    }
+    Ok(text)
+}
+
+/// `parent_headers` are the headers that were used to fetch the file found at `location`. They
+/// are forwarded to imports of the same origin that don't specify headers of their own.
 fn resolve_one_import(
     env: &mut ImportEnv,
-    import: &Import,
+    import: &syntax::Import<Hir>,
     location: &ImportLocation,
+    parent_headers: &[(String, String)],
     span: Span,
 ) -> Result<TypedHir, Error> {
     let do_sanity_check = import.mode != ImportMode::Location;
+    let own_headers = match &import.location {
+        ImportTarget::Remote(URL {
+            headers: Some(headers),
+            ..
+        }) => Some(headers),
+        _ => None,
+    };
+    let import = &import.traverse_ref(|_| Ok::<_, Error>(()))?;
+    let parent_location = location;
     let location = location.chain(&import.location, do_sanity_check)?;
+    let headers = match own_headers {
+        _ if import.mode == ImportMode::Location => Vec::new(),
+        Some(headers) => eval_headers(headers)?,
+        None if parent_location.same_origin(&location) => {
+            parent_headers.to_vec()
+        }
+        None => Vec::new(),
+    };
     env.handle_import(location.clone(), |env| match import.mode {
+        ImportMode::Code | ImportMode::RawText
+            if env.get_override(&location).is_some() =>
+        {
+            let (hir, ty) = env.get_override(&location).unwrap().clone();
+            if import.mode == ImportMode::RawText
+                && ty != Type::from_builtin(Builtin::Text)
+            {
+                mkerr(
+                    ErrorBuilder::new(format!(
+                        "override for `{}` is not Text",
+                        location
+                    ))
+                    .span_err(span, "imported as Text here")
+                    .format(),
+                )?
+            }
+            Ok((hir, ty))
+        }
         ImportMode::Code => {
-            let parsed = location.fetch_dhall()?;
-            let typed = resolve_with_env(env, parsed)?.typecheck()?;
+            if let (Some(hash), Some(cache)) = (&import.hash, env.file_cache())
+            {
+                if let Some(cached) = cache.get(hash) {
+                    return Ok(cached);
+                }
+            }
+            let parsed =
+                location.fetch_dhall(env, parent_location, &headers)?;
+            let typed = resolve_with_env(env, parsed, &headers)?.typecheck()?;
             let hir = typed.normalize().to_hir();
             let ty = typed.ty().clone();
             match &import.hash {
@@ -239,13 +549,18 @@ fn resolve_one_import(
                                 .format(),
                         )?
                     }
+                    if let Some(cache) = env.file_cache() {
+                        // Failing to write to the cache is not an error.
+                        let _ =
+                            cache.insert(import.hash.as_ref().unwrap(), &hir);
+                    }
                 }
                 None => {}
             }
             Ok((hir, ty))
         }
         ImportMode::RawText => {
-            let text = location.fetch_text()?;
+            let text = location.fetch_text(env, parent_location, &headers)?;
             let hir = Hir::new(
                 HirKind::Expr(ExprKind::TextLit(text.into())),
                 Span::Artificial,
@@ -261,108 +576,216 @@ fn resolve_one_import(
     })
 }
 
-/// Desugar the first level of the expression.
-fn desugar(expr: &Expr) -> Cow<'_, Expr> {
-    match expr.kind() {
-        ExprKind::Op(OpKind::Completion(ty, compl)) => {
-            let ty_field_default = Expr::new(
-                ExprKind::Op(OpKind::Field(ty.clone(), "default".into())),
-                expr.span(),
-            );
-            let merged = Expr::new(
-                ExprKind::Op(OpKind::BinOp(
-                    BinOp::RightBiasedRecordMerge,
-                    ty_field_default,
-                    compl.clone(),
-                )),
-                expr.span(),
-            );
-            let ty_field_type = Expr::new(
-                ExprKind::Op(OpKind::Field(ty.clone(), "Type".into())),
-                expr.span(),
-            );
-            Cow::Owned(Expr::new(
-                ExprKind::Annot(merged, ty_field_type),
-                expr.span(),
-            ))
-        }
-        _ => Cow::Borrowed(expr),
-    }
-}
-
 /// Traverse the expression, handling import alternatives and passing
 /// found imports to the provided function. Also resolving names.
 fn traverse_resolve_expr(
     name_env: &mut NameEnv,
     expr: &Expr,
-    f: &mut impl FnMut(Import, Span) -> Result<TypedHir, Error>,
+    f: &mut impl FnMut(syntax::Import<Hir>, Span) -> Result<TypedHir, Error>,
 ) -> Result<Hir, Error> {
-    let expr = desugar(expr);
-    Ok(match expr.kind() {
-        ExprKind::Var(var) => match name_env.unlabel_var(&var) {
-            Some(v) => Hir::new(HirKind::Var(v), expr.span()),
-            None => mkerr(
-                ErrorBuilder::new(format!("unbound variable `{}`", var))
-                    .span_err(expr.span(), "not found in this scope")
-                    .format(),
-            )?,
-        },
-        ExprKind::Op(OpKind::BinOp(BinOp::ImportAlt, l, r)) => {
-            match traverse_resolve_expr(name_env, l, f) {
-                Ok(l) => l,
-                Err(_) => {
-                    match traverse_resolve_expr(name_env, r, f) {
+    ensure_stack(|| {
+        Ok(match expr.kind() {
+            ExprKind::Var(var) => match name_env.unlabel_var(&var) {
+                Some(v) => Hir::new(HirKind::Var(v), expr.span()),
+                None => mkerr(
+                    ErrorBuilder::new(format!("unbound variable `{}`", var))
+                        .kind(TypeErrorKind::UnboundVariable)
+                        .span_err(expr.span(), "not found in this scope")
+                        .format(),
+                )?,
+            },
+            ExprKind::Op(OpKind::BinOp(BinOp::ImportAlt, l, r)) => {
+                match traverse_resolve_expr(name_env, l, f) {
+                    Ok(l) => l,
+                    Err(el) => match traverse_resolve_expr(name_env, r, f) {
                         Ok(r) => r,
-                        // TODO: keep track of the other error too
-                        Err(e) => return Err(e),
-                    }
+                        Err(er) => {
+                            // Flatten nested alternatives so that `a ? b ? c` lists three errors.
+                            let mut errors = Vec::new();
+                            let mut push = |e: Error| match e.into_kind() {
+                                ErrorKind::Resolve(
+                                    ImportError::ImportAlternatives(errs),
+                                ) => errors.extend(errs),
+                                kind => errors.push(kind.into()),
+                            };
+                            push(el);
+                            push(er);
+                            return Err(ImportError::ImportAlternatives(
+                                errors,
+                            )
+                            .into());
+                        }
+                    },
                 }
             }
-        }
-        kind => {
-            let kind = kind.traverse_ref_maybe_binder(|l, e| {
-                if let Some(l) = l {
-                    name_env.insert_mut(l);
-                }
-                let hir = traverse_resolve_expr(name_env, e, f)?;
-                if l.is_some() {
-                    name_env.remove_mut();
-                }
-                Ok::<_, Error>(hir)
-            })?;
-            let kind = match kind {
-                ExprKind::Import(import) => {
-                    // TODO: evaluate import headers
-                    let import = import.traverse_ref(|_| Ok::<_, Error>(()))?;
-                    let imported = f(import, expr.span())?;
-                    HirKind::Import(imported.0, imported.1)
-                }
-                kind => HirKind::Expr(kind),
-            };
-            Hir::new(kind, expr.span())
-        }
+            ExprKind::Import(import) => {
+                // The headers of an import can't refer to variables bound outside of it.
+                let import = import.traverse_ref(|headers| {
+                    traverse_resolve_expr(&mut NameEnv::new(), headers, f)
+                })?;
+                let imported = f(import, expr.span())?;
+                Hir::new(HirKind::Import(imported.0, imported.1), expr.span())
+            }
+            kind => {
+                let kind = kind.traverse_ref_maybe_binder(|l, e| {
+                    if let Some(l) = l {
+                        name_env.insert_mut(l);
+                    }
+                    let hir = traverse_resolve_expr(name_env, e, f)?;
+                    if l.is_some() {
+                        name_env.remove_mut();
+                    }
+                    Ok::<_, Error>(hir)
+                })?;
+                Hir::new(HirKind::Expr(kind), expr.span())
+            }
+        })
     })
 }
 
 fn resolve_with_env(
     env: &mut ImportEnv,
     parsed: Parsed,
+    headers: &[(String, String)],
+) -> Result<Resolved, Error> {
+    resolve_in_scope(env, parsed, headers, &mut NameEnv::new())
+}
+
+/// Resolves an expression that may refer to the variables of `names`, which are bound outside of
+/// it.
+fn resolve_in_scope(
+    env: &mut ImportEnv,
+    parsed: Parsed,
+    headers: &[(String, String)],
+    names: &mut NameEnv,
 ) -> Result<Resolved, Error> {
     let Parsed(expr, location) = parsed;
-    let resolved = traverse_resolve_expr(
-        &mut NameEnv::new(),
-        &expr,
-        &mut |import, span| resolve_one_import(env, &import, &location, span),
-    )?;
+    let resolved = traverse_resolve_expr(names, &expr, &mut |import, span| {
+        resolve_one_import(env, &import, &location, headers, span)
+    })?;
     Ok(Resolved(resolved))
 }
 
 pub fn resolve(parsed: Parsed) -> Result<Resolved, Error> {
-    resolve_with_env(&mut ImportEnv::new(), parsed)
+    resolve_with_env(&mut ImportEnv::new(), parsed, &[])
+}
+
+/// Reads a set of import overrides from a record whose fields are named after the imports they
+/// replace, either `env:NAME` or a URL. For example:
+///
+/// ```text
+/// { `env:HOME` = "/home/test"
+/// , `https://example.com/defaults.dhall` = { port = 8080 }
+/// }
+/// ```
+pub fn parse_overrides(parsed: Parsed) -> Result<ImportCache, Error> {
+    let hir = resolve(parsed)?.0;
+    let span = hir.span();
+    let tir = hir.typecheck_noenv()?;
+    let fields = match tir.eval(NzEnv::new()).kind() {
+        NirKind::RecordLit(kvs) => kvs.clone(),
+        _ => mkerr(
+            ErrorBuilder::new("invalid import overrides")
+                .span_err(span.clone(), "this is not a record")
+                .format(),
+        )?,
+    };
+    let mut overrides = ImportCache::new();
+    for (key, value) in fields {
+        let key = String::from(&key);
+        let location = match key.strip_prefix("env:") {
+            Some(var_name) => ImportLocation::Env(var_name.to_owned()),
+            None => match Url::parse(&key) {
+                Ok(url) => ImportLocation::Remote(url),
+                Err(_) => mkerr(
+                    ErrorBuilder::new(format!("invalid override `{}`", key))
+                        .span_err(span.clone(), "in these overrides")
+                        .help("overrides must be named `env:NAME` or a URL")
+                        .format(),
+                )?,
+            },
+        };
+        let hir = value.to_hir_noenv();
+        let ty = hir.typecheck_noenv()?.ty().clone();
+        overrides.insert(location, (hir, ty));
+    }
+    Ok(overrides)
+}
+
+/// Reads a mirror configuration from a record whose fields are named after the URL prefixes to
+/// rewrite, and whose values are the URLs or local directories to fetch them from instead. For
+/// example:
+///
+/// ```text
+/// { `https://prelude.dhall-lang.org/` = "/opt/vendor/dhall-prelude/"
+/// , `https://example.com/` = "https://mirror.internal/example.com/"
+/// }
+/// ```
+pub fn parse_mirrors(
+    parsed: Parsed,
+) -> Result<Vec<(String, ImportLocation)>, Error> {
+    let hir = resolve(parsed)?.0;
+    let span = hir.span();
+    let tir = hir.typecheck_noenv()?;
+    let invalid = || {
+        ErrorBuilder::new("invalid mirror configuration")
+            .span_err(span.clone(), "in this configuration")
+            .help(
+                "mirrors must be a record of `Text` values, \
+                 named after URL prefixes",
+            )
+            .format()
+    };
+    let fields = match tir.eval(NzEnv::new()).kind() {
+        NirKind::RecordLit(kvs) => kvs.clone(),
+        _ => mkerr(invalid())?,
+    };
+    let mut mirrors = Vec::new();
+    for (prefix, target) in fields {
+        let prefix = String::from(&prefix);
+        let target = match target.kind() {
+            NirKind::TextLit(t) => t.as_text(),
+            _ => None,
+        };
+        let target = match target {
+            Some(target) if Url::parse(&prefix).is_ok() => target,
+            _ => mkerr(invalid())?,
+        };
+        let target = match Url::parse(&target) {
+            Ok(url) => ImportLocation::Remote(url),
+            Err(_) => ImportLocation::Local(PathBuf::from(target)),
+        };
+        mirrors.push((prefix, target));
+    }
+    Ok(mirrors)
+}
+
+/// Resolves imports reusing the given environment, so that imports already resolved with it
+/// are not fetched again.
+pub fn resolve_with(
+    env: &mut ImportEnv,
+    parsed: Parsed,
+) -> Result<Resolved, Error> {
+    resolve_with_env(env, parsed, &[])
+}
+
+/// Like `resolve_with`, but the expression may also refer to the given variables, in order of
+/// binding. The resulting `Hir` has the corresponding free variables.
+pub fn resolve_with_names(
+    env: &mut ImportEnv,
+    parsed: Parsed,
+    names: &[Label],
+) -> Result<Resolved, Error> {
+    let mut name_env = NameEnv::new();
+    for name in names {
+        name_env.insert_mut(name);
+    }
+    resolve_in_scope(env, parsed, &[], &mut name_env)
 }
 
 pub fn skip_resolve_expr(expr: &Expr) -> Result<Hir, Error> {
     traverse_resolve_expr(&mut NameEnv::new(), expr, &mut |import, _span| {
+        let import = import.traverse_ref(|_| Ok::<_, Error>(()))?;
         Err(ImportError::UnexpectedImport(import).into())
     })
 }
@@ -0,0 +1,57 @@
+use std::path::{Path, PathBuf};
+
+use crate::error::Error;
+use crate::semantics::ImportEnv;
+use crate::Parsed;
+
+/// The outcome of `warm`.
+#[derive(Debug, Default)]
+pub struct WarmReport {
+    /// The files that were resolved and typechecked successfully.
+    pub checked: Vec<PathBuf>,
+    /// The files that failed to resolve or typecheck.
+    pub errors: Vec<(PathBuf, Error)>,
+}
+
+/// Lists the `.dhall` files under `dir`, skipping hidden directories.
+fn find_dhall_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), Error> {
+    let mut entries = std::fs::read_dir(dir)?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<Result<Vec<_>, std::io::Error>>()?;
+    entries.sort();
+    for path in entries {
+        let hidden = path
+            .file_name()
+            .map(|name| name.to_string_lossy().starts_with('.'))
+            .unwrap_or(false);
+        if path.is_dir() {
+            if !hidden {
+                find_dhall_files(&path, files)?;
+            }
+        } else if path.extension().filter(|ext| *ext == "dhall").is_some() {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Resolves and typechecks every `.dhall` file under `root`. This fills the cache with the
+/// project's protected imports and with the contents of its remote imports, so that later
+/// evaluations can be done offline.
+pub fn warm(root: &Path) -> Result<WarmReport, Error> {
+    let mut files = Vec::new();
+    find_dhall_files(root, &mut files)?;
+    // Share the environment so that common imports are only resolved once.
+    let mut env = ImportEnv::new();
+    let mut report = WarmReport::default();
+    for file in files {
+        let result = Parsed::parse_file(&file)
+            .and_then(|parsed| parsed.resolve_with(&mut env))
+            .and_then(|resolved| Ok(resolved.typecheck()?));
+        match result {
+            Ok(_) => report.checked.push(file),
+            Err(err) => report.errors.push((file, err)),
+        }
+    }
+    Ok(report)
+}
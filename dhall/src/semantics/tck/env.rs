@@ -1,3 +1,4 @@
+use crate::error::TypeDisplayLimits;
 use crate::semantics::{AlphaVar, NameEnv, Nir, NzEnv, NzVar, Type, ValEnv};
 use crate::syntax::Label;
 
@@ -12,6 +13,8 @@ pub struct VarEnv {
 pub struct TyEnv {
     names: NameEnv,
     items: ValEnv<Type>,
+    /// How much of the types mentioned in type errors gets printed.
+    display_limits: TypeDisplayLimits,
 }
 
 impl VarEnv {
@@ -43,8 +46,21 @@ impl TyEnv {
         TyEnv {
             names: NameEnv::new(),
             items: ValEnv::new(),
+            display_limits: TypeDisplayLimits::default(),
         }
     }
+    pub fn with_display_limits(
+        &self,
+        display_limits: TypeDisplayLimits,
+    ) -> Self {
+        TyEnv {
+            display_limits,
+            ..self.clone()
+        }
+    }
+    pub fn display_limits(&self) -> TypeDisplayLimits {
+        self.display_limits
+    }
     pub fn as_varenv(&self) -> VarEnv {
         self.names.as_varenv()
     }
@@ -59,12 +75,14 @@ impl TyEnv {
         TyEnv {
             names: self.names.insert(x),
             items: self.items.insert_type(ty),
+            display_limits: self.display_limits,
         }
     }
     pub fn insert_value(&self, x: &Label, e: Nir, ty: Type) -> Self {
         TyEnv {
             names: self.names.insert(x),
             items: self.items.insert_value(e, ty),
+            display_limits: self.display_limits,
         }
     }
     pub fn lookup(&self, var: AlphaVar) -> Type {
@@ -1,6 +1,8 @@
 pub mod env;
 pub mod tir;
 pub mod typecheck;
+pub mod typed_tree;
 pub use env::*;
 pub use tir::*;
 pub use typecheck::*;
+pub use typed_tree::*;
@@ -1,5 +1,5 @@
 use crate::builtins::Builtin;
-use crate::error::{ErrorBuilder, TypeError};
+use crate::error::{elide_type, ErrorBuilder, TypeError, TypeErrorKind};
 use crate::semantics::{mkerr, Hir, Nir, NirKind, NzEnv, TyEnv, VarEnv};
 use crate::syntax::{Const, Expr, Span};
 
@@ -139,11 +139,12 @@ impl<'hir> Tir<'hir> {
                     "Expected a type, found: `{}`",
                     self.to_expr_tyenv(env),
                 ))
+                .kind(TypeErrorKind::NotAType)
                 .span_err(
                     self.span(),
                     format!(
                         "this has type: `{}`",
-                        self.ty().to_expr_tyenv(env)
+                        elide_type(env, &self.ty().to_expr_tyenv(env))
                     ),
                 )
                 .help(format!(
@@ -1,10 +1,18 @@
 use std::cmp::max;
 
 use crate::builtins::{type_of_builtin, Builtin};
-use crate::error::{ErrorBuilder, TypeError, TypeMessage};
-use crate::operations::typecheck_operation;
-use crate::semantics::{Hir, HirKind, Nir, NirKind, Tir, TyEnv, Type};
-use crate::syntax::{Const, ExprKind, InterpolatedTextContents, NumKind, Span};
+use crate::error::{
+    elide_type, Diagnostic, ErrorBuilder, TypeDisplayLimits, TypeError,
+    TypeErrorKind, TypeMessage,
+};
+use crate::operations::{typecheck_operation, BinOp, OpKind};
+use crate::semantics::{
+    Hir, HirKind, Nir, NirKind, Tir, TyEnv, Type, TypedNode,
+};
+use crate::shared::ensure_stack;
+use crate::syntax::{
+    Const, Expr, ExprKind, InterpolatedTextContents, NumKind, Span,
+};
 
 fn function_check(a: Const, b: Const) -> Const {
     if b == Const::Type {
@@ -14,18 +22,93 @@ fn function_check(a: Const, b: Const) -> Const {
     }
 }
 
-pub fn mkerr<T, S: ToString>(msg: S) -> Result<T, TypeError> {
-    Err(TypeError::new(TypeMessage::Custom(msg.to_string())))
+pub fn mkerr<T, S: Into<Diagnostic>>(msg: S) -> Result<T, TypeError> {
+    Err(TypeError::new(TypeMessage::Custom(Box::new(msg.into()))))
 }
 
-pub fn mk_span_err<T, S: ToString>(span: Span, msg: S) -> Result<T, TypeError> {
+/// Reports an error of the given kind at `span`, titled with the name of the kind.
+pub fn mk_span_err<T>(span: Span, kind: TypeErrorKind) -> Result<T, TypeError> {
+    let msg = format!("{:?}", kind);
     mkerr(
-        ErrorBuilder::new(msg.to_string())
-            .span_err(span, msg.to_string())
+        ErrorBuilder::new(&msg)
+            .kind(kind)
+            .span_err(span, msg)
             .format(),
     )
 }
 
+/// Reports an error on the subexpression `sub` of the expression at `span`, showing its type.
+pub fn mk_subexpr_err<T>(
+    env: &TyEnv,
+    span: Span,
+    sub: &Tir<'_>,
+    kind: TypeErrorKind,
+) -> Result<T, TypeError> {
+    mkerr(
+        ErrorBuilder::new(format!("{:?}", kind))
+            .kind(kind)
+            .span_err(
+                sub.span(),
+                format!(
+                    "this has type: `{}`",
+                    elide_type(env, &sub.ty().to_expr_tyenv(env))
+                ),
+            )
+            .span_help(span, "in this expression")
+            .format(),
+    )
+}
+
+/// Finds where two expressions first differ. Returns the path to that point, made of record
+/// fields and list indices (e.g. `.servers[1].port`), and the two subexpressions found there.
+fn first_difference(x: &Expr, y: &Expr) -> (String, Expr, Expr) {
+    let mut path = String::new();
+    let (mut x, mut y) = (x.clone(), y.clone());
+    loop {
+        let next = match (x.kind(), y.kind()) {
+            (ExprKind::RecordLit(xs), ExprKind::RecordLit(ys))
+            | (ExprKind::RecordType(xs), ExprKind::RecordType(ys))
+                if xs.keys().eq(ys.keys()) =>
+            {
+                xs.iter().zip(ys.values()).find(|((_, x), y)| x != y).map(
+                    |((k, x), y)| (format!(".{}", k), x.clone(), y.clone()),
+                )
+            }
+            (ExprKind::NEListLit(xs), ExprKind::NEListLit(ys))
+                if xs.len() == ys.len() =>
+            {
+                xs.iter()
+                    .zip(ys)
+                    .enumerate()
+                    .find(|(_, (x, y))| x != y)
+                    .map(|(i, (x, y))| {
+                        (format!("[{}]", i), x.clone(), y.clone())
+                    })
+            }
+            // Same constructor: look for the first child that differs.
+            (xk, yk) if xk.map_ref(|_| ()) == yk.map_ref(|_| ()) => {
+                let mut xs = Vec::new();
+                let mut ys = Vec::new();
+                xk.map_ref(|x| xs.push(x.clone()));
+                yk.map_ref(|y| ys.push(y.clone()));
+                xs.into_iter()
+                    .zip(ys)
+                    .find(|(x, y)| x != y)
+                    .map(|(x, y)| (String::new(), x, y))
+            }
+            _ => None,
+        };
+        match next {
+            Some((step, x_next, y_next)) => {
+                path.push_str(&step);
+                x = x_next;
+                y = y_next;
+            }
+            None => return (path, x, y),
+        }
+    }
+}
+
 /// When all sub-expressions have been typed, check the remaining toplevel
 /// layer.
 fn type_one_layer(
@@ -33,7 +116,9 @@ fn type_one_layer(
     ekind: ExprKind<Tir<'_>>,
     span: Span,
 ) -> Result<Type, TypeError> {
-    let span_err = |msg: &str| mk_span_err(span.clone(), msg);
+    let span_err = |kind| mk_span_err(span.clone(), kind);
+    let subexpr_err =
+        |sub: &Tir<'_>, kind| mk_subexpr_err(env, span.clone(), sub, kind);
 
     Ok(match &ekind {
         ExprKind::Import(..) => {
@@ -66,7 +151,10 @@ fn type_one_layer(
                 use InterpolatedTextContents::Expr;
                 if let Expr(x) = contents {
                     if *x.ty() != text_type {
-                        return span_err("InvalidTextInterpolation");
+                        return subexpr_err(
+                            x,
+                            TypeErrorKind::InvalidTextInterpolation,
+                        );
                     }
                 }
             }
@@ -74,7 +162,7 @@ fn type_one_layer(
         }
         ExprKind::SomeLit(x) => {
             if x.ty().ty().as_const() != Some(Const::Type) {
-                return span_err("InvalidOptionalType");
+                return subexpr_err(x, TypeErrorKind::InvalidOptionalType);
             }
 
             let t = x.ty().to_nir();
@@ -86,7 +174,7 @@ fn type_one_layer(
             let t = t.eval_to_type(env)?;
             match t.kind() {
                 NirKind::ListType(..) => {}
-                _ => return span_err("InvalidListType"),
+                _ => return span_err(TypeErrorKind::InvalidListType),
             };
             t
         }
@@ -95,11 +183,11 @@ fn type_one_layer(
             let x = iter.next().unwrap();
             for y in iter {
                 if x.ty() != y.ty() {
-                    return span_err("InvalidListElement");
+                    return subexpr_err(y, TypeErrorKind::InvalidListElement);
                 }
             }
             if x.ty().ty().as_const() != Some(Const::Type) {
-                return span_err("InvalidListType");
+                return span_err(TypeErrorKind::InvalidListType);
             }
 
             let t = x.ty().to_nir();
@@ -112,7 +200,12 @@ fn type_one_layer(
                 // Check that the fields have a valid kind
                 match v.ty().ty().as_const() {
                     Some(c) => k = max(k, c),
-                    None => return mk_span_err(v.span(), "InvalidFieldType"),
+                    None => {
+                        return mk_span_err(
+                            v.span(),
+                            TypeErrorKind::InvalidFieldType,
+                        )
+                    }
                 }
             }
 
@@ -130,7 +223,12 @@ fn type_one_layer(
                 // Check the type is a Const and compute final type
                 match t.ty().as_const() {
                     Some(c) => k = max(k, c),
-                    None => return mk_span_err(t.span(), "InvalidFieldType"),
+                    None => {
+                        return mk_span_err(
+                            t.span(),
+                            TypeErrorKind::InvalidFieldType,
+                        )
+                    }
                 }
             }
 
@@ -144,14 +242,20 @@ fn type_one_layer(
                     let c = match t.ty().as_const() {
                         Some(c) => c,
                         None => {
-                            return mk_span_err(t.span(), "InvalidVariantType")
+                            return mk_span_err(
+                                t.span(),
+                                TypeErrorKind::InvalidVariantType,
+                            )
                         }
                     };
                     match k {
                         None => k = Some(c),
                         Some(k) if k == c => {}
                         _ => {
-                            return mk_span_err(t.span(), "InvalidVariantType")
+                            return mk_span_err(
+                                t.span(),
+                                TypeErrorKind::InvalidVariantType,
+                            )
                         }
                     }
                 }
@@ -165,114 +269,302 @@ fn type_one_layer(
         }
         ExprKind::Op(op) => typecheck_operation(env, span, op)?,
         ExprKind::Assert(t) => {
+            let is_reflexive = match t.as_hir().kind() {
+                HirKind::Expr(ExprKind::Op(OpKind::BinOp(
+                    BinOp::Equivalence,
+                    x,
+                    y,
+                ))) => x == y,
+                _ => false,
+            };
             let t = t.eval_to_type(env)?;
             match t.kind() {
+                // Syntactically equal sides are equivalent; no need to normalize them.
+                NirKind::Equivalence(..) if is_reflexive => {}
                 NirKind::Equivalence(x, y) if x == y => {}
-                NirKind::Equivalence(..) => return span_err("AssertMismatch"),
-                _ => return span_err("AssertMustTakeEquivalence"),
+                NirKind::Equivalence(x, y) => {
+                    let x = x.to_expr_tyenv(env);
+                    let y = y.to_expr_tyenv(env);
+                    let (path, x_diff, y_diff) = first_difference(&x, &y);
+                    let mut err = ErrorBuilder::new("AssertMismatch");
+                    err.kind(TypeErrorKind::AssertMismatch)
+                        .span_err(span, "this assertion is false")
+                        .note(format!("the left side normalizes to: {}", x))
+                        .note(format!("the right side normalizes to: {}", y));
+                    if path.is_empty() {
+                        err.note(format!("`{}` is not `{}`", x_diff, y_diff));
+                    } else {
+                        err.note(format!(
+                            "the sides first differ at `{}`: `{}` is not `{}`",
+                            path, x_diff, y_diff
+                        ));
+                    }
+                    return mkerr(err.format());
+                }
+                _ => return span_err(TypeErrorKind::AssertMustTakeEquivalence),
             }
             t
         }
     })
 }
 
+/// Evaluates a type annotation. Unlike anywhere else, `Sort` is allowed there.
+fn annot_to_type(
+    env: &TyEnv,
+    t: &Hir,
+    nodes: Option<&mut Vec<TypedNode>>,
+) -> Result<Type, TypeError> {
+    Ok(match t.kind() {
+        HirKind::Expr(ExprKind::Const(Const::Sort)) => {
+            Type::from_const(Const::Sort)
+        }
+        _ => type_with_nodes(env, t, None, nodes)?.eval_to_type(env)?,
+    })
+}
+
+/// Rejects `Sort` as the type of a bound variable with a dedicated message, since `Sort` has no
+/// type itself.
+fn check_binder_annot(annot: &Hir) -> Result<(), TypeError> {
+    if let HirKind::Expr(ExprKind::Const(Const::Sort)) = annot.kind() {
+        return mkerr(
+            ErrorBuilder::new(
+                "`Sort` can't be the type of a function argument",
+            )
+            .kind(TypeErrorKind::InvalidInputType)
+            .span_err(
+                annot.span(),
+                "this would take `Kind` or `Kind → Kind` as argument",
+            )
+            .help(
+                "functions can take terms, types and kinds as arguments, \
+                     but not expressions of type `Sort`",
+            )
+            .format(),
+        );
+    }
+    Ok(())
+}
+
+/// `T::r` is syntax sugar for `(T.default ⫽ r) : T.Type`.
+fn desugar_completion(ty: &Hir, compl: &Hir, span: Span) -> Hir {
+    let expr = |kind| Hir::new(HirKind::Expr(kind), span.clone());
+    let field =
+        |l: &str| expr(ExprKind::Op(OpKind::Field(ty.clone(), l.into())));
+    let merged = expr(ExprKind::Op(OpKind::BinOp(
+        BinOp::RightBiasedRecordMerge,
+        field("default"),
+        compl.clone(),
+    )));
+    expr(ExprKind::Annot(merged, field("Type")))
+}
+
 /// `type_with` typechecks an expression in the provided environment. Optionally pass an annotation
-/// to compare with.
+/// to compare with, along with the span of the annotation to point at in case of mismatch.
 // We pass the annotation to avoid duplicating the annot checking logic. I hope one day we can use
 // it to handle the annotations in merge/toMap/etc. uniformly.
 pub fn type_with<'hir>(
     env: &TyEnv,
     hir: &'hir Hir,
-    annot: Option<Type>,
+    annot: Option<(Type, Span)>,
 ) -> Result<Tir<'hir>, TypeError> {
-    let tir = match hir.kind() {
-        HirKind::Var(var) => Tir::from_hir(hir, env.lookup(*var)),
-        HirKind::Import(_, ty) => Tir::from_hir(hir, ty.clone()),
-        HirKind::Expr(ExprKind::Var(_)) => {
-            unreachable!("Hir should contain no unresolved variables")
-        }
-        HirKind::Expr(ExprKind::Const(Const::Sort)) => {
-            return mk_span_err(hir.span(), "Sort does not have a type")
-        }
-        HirKind::Expr(ExprKind::Annot(x, t)) => {
-            let t = match t.kind() {
-                HirKind::Expr(ExprKind::Const(Const::Sort)) => {
-                    Type::from_const(Const::Sort)
-                }
-                _ => type_with(env, t, None)?.eval_to_type(env)?,
-            };
-            type_with(env, x, Some(t))?
-        }
+    type_with_nodes(env, hir, annot, None)
+}
 
-        HirKind::Expr(ExprKind::Lam(binder, annot, body)) => {
-            let annot = type_with(env, annot, None)?;
-            let annot_nf = annot.eval_to_type(env)?;
-            let body_env = env.insert_type(binder, annot_nf);
-            let body = type_with(&body_env, body, None)?;
+/// Like `type_with`, but if `nodes` is provided, additionally pushes a node for `hir` to it, with
+/// the nodes of its subexpressions as children.
+fn type_with_nodes<'hir>(
+    env: &TyEnv,
+    hir: &'hir Hir,
+    annot: Option<(Type, Span)>,
+    mut nodes: Option<&mut Vec<TypedNode>>,
+) -> Result<Tir<'hir>, TypeError> {
+    ensure_stack(move || {
+        let start = nodes.as_ref().map(|nodes| nodes.len());
+        let tir = match hir.kind() {
+            HirKind::Var(var) => Tir::from_hir(hir, env.lookup(*var)),
+            HirKind::Import(_, ty) => Tir::from_hir(hir, ty.clone()),
+            HirKind::Foreign(f) => Tir::from_hir(hir, f.ty().clone()),
+            HirKind::Expr(ExprKind::Var(_)) => {
+                unreachable!("Hir should contain no unresolved variables")
+            }
+            HirKind::Expr(ExprKind::Const(Const::Sort)) => {
+                return mkerr(
+                    ErrorBuilder::new("`Sort` has no type")
+                        .kind(TypeErrorKind::Untyped)
+                        .span_err(hir.span(), "`Sort` is used as a value here")
+                        .help(
+                            "`Sort` is at the top of the hierarchy \
+                             `Type : Kind : Sort`, so it can only appear as a \
+                             type annotation, as in `Kind : Sort`",
+                        )
+                        .format(),
+                )
+            }
+            HirKind::Expr(ExprKind::Annot(x, t)) => {
+                let t_ty = annot_to_type(env, t, nodes.as_deref_mut())?;
+                type_with_nodes(
+                    env,
+                    x,
+                    Some((t_ty, t.span())),
+                    nodes.as_deref_mut(),
+                )?
+            }
 
-            let u_annot = annot.ty().as_const().unwrap();
-            let u_body = match body.ty().ty().as_const() {
-                Some(k) => k,
-                _ => return mk_span_err(hir.span(), "Invalid output type"),
-            };
-            let u = function_check(u_annot, u_body).to_universe();
-            let ty_hir = Hir::new(
-                HirKind::Expr(ExprKind::Pi(
-                    binder.clone(),
-                    annot.to_hir(),
-                    body.ty().to_hir(body_env.as_varenv()),
-                )),
-                hir.span(),
-            );
-            let ty = Type::new(ty_hir.eval(env), u);
+            HirKind::Expr(ExprKind::Lam(binder, annot, body)) => {
+                check_binder_annot(annot)?;
+                let annot =
+                    type_with_nodes(env, annot, None, nodes.as_deref_mut())?;
+                let annot_nf = annot.eval_to_type(env)?;
+                let body_env = env.insert_type(binder, annot_nf);
+                let body = type_with_nodes(
+                    &body_env,
+                    body,
+                    None,
+                    nodes.as_deref_mut(),
+                )?;
 
-            Tir::from_hir(hir, ty)
-        }
-        HirKind::Expr(ExprKind::Pi(binder, annot, body)) => {
-            let annot = type_with(env, annot, None)?;
-            let annot_val = annot.eval_to_type(env)?;
-            let body_env = env.insert_type(binder, annot_val);
-            let body = type_with(&body_env, body, None)?;
-            body.ensure_is_type(env)?;
-
-            let ks = annot.ty().as_const().unwrap();
-            let kt = body.ty().as_const().unwrap();
-            let ty = Type::from_const(function_check(ks, kt));
-            Tir::from_hir(hir, ty)
-        }
-        HirKind::Expr(ExprKind::Let(binder, annot, val, body)) => {
-            let val_annot = annot
-                .as_ref()
-                .map(|t| Ok(type_with(env, t, None)?.eval_to_type(env)?))
-                .transpose()?;
-            let val = type_with(env, &val, val_annot)?;
-            let val_nf = val.eval(env);
-            let body_env = env.insert_value(&binder, val_nf, val.ty().clone());
-            let body = type_with(&body_env, body, None)?;
-            let ty = body.ty().clone();
-            Tir::from_hir(hir, ty)
-        }
-        HirKind::Expr(ekind) => {
-            let ekind = ekind.traverse_ref(|e| type_with(env, e, None))?;
-            let ty = type_one_layer(env, ekind, hir.span())?;
-            Tir::from_hir(hir, ty)
+                let u_annot = annot.ty().as_const().unwrap();
+                let u_body =
+                    match body.ty().ty().as_const() {
+                        Some(k) => k,
+                        _ => return mkerr(
+                            ErrorBuilder::new(
+                                "the body of a function can't have type `Sort`",
+                            )
+                            .kind(TypeErrorKind::InvalidOutputType)
+                            .span_err(
+                                body.span(),
+                                format!(
+                                    "this has type: `{}`",
+                                    elide_type(
+                                        &body_env,
+                                        &body.ty().to_expr_tyenv(&body_env)
+                                    )
+                                ),
+                            )
+                            .span_help(hir.span(), "in this function")
+                            .help(
+                                "functions can return terms, types and kinds \
+                                 like `Type`, but not `Kind` or other \
+                                 expressions of type `Sort`",
+                            )
+                            .format(),
+                        ),
+                    };
+                let u = function_check(u_annot, u_body).to_universe();
+                let ty_hir = Hir::new(
+                    HirKind::Expr(ExprKind::Pi(
+                        binder.clone(),
+                        annot.to_hir(),
+                        body.ty().to_hir(body_env.as_varenv()),
+                    )),
+                    hir.span(),
+                );
+                let ty = Type::new(ty_hir.eval(env), u);
+
+                Tir::from_hir(hir, ty)
+            }
+            HirKind::Expr(ExprKind::Pi(binder, annot, body)) => {
+                check_binder_annot(annot)?;
+                let annot =
+                    type_with_nodes(env, annot, None, nodes.as_deref_mut())?;
+                let annot_val = annot.eval_to_type(env)?;
+                let body_env = env.insert_type(binder, annot_val);
+                let body = type_with_nodes(
+                    &body_env,
+                    body,
+                    None,
+                    nodes.as_deref_mut(),
+                )?;
+                body.ensure_is_type(env)?;
+
+                let ks = annot.ty().as_const().unwrap();
+                let kt = body.ty().as_const().unwrap();
+                let ty = Type::from_const(function_check(ks, kt));
+                Tir::from_hir(hir, ty)
+            }
+            HirKind::Expr(ExprKind::Let(binder, annot, val, body)) => {
+                let val_annot = annot
+                    .as_ref()
+                    .map(|t| {
+                        Ok((
+                            annot_to_type(env, t, nodes.as_deref_mut())?,
+                            t.span(),
+                        ))
+                    })
+                    .transpose()?;
+                let val = type_with_nodes(
+                    env,
+                    &val,
+                    val_annot,
+                    nodes.as_deref_mut(),
+                )?;
+                let val_nf = val.eval(env);
+                let body_env =
+                    env.insert_value(&binder, val_nf, val.ty().clone());
+                let body = type_with_nodes(
+                    &body_env,
+                    body,
+                    None,
+                    nodes.as_deref_mut(),
+                )?;
+                let ty = body.ty().clone();
+                Tir::from_hir(hir, ty)
+            }
+            HirKind::Expr(ExprKind::Op(OpKind::Completion(ty, compl))) => {
+                let desugared = desugar_completion(ty, compl, hir.span());
+                let ty = type_with_nodes(
+                    env,
+                    &desugared,
+                    None,
+                    nodes.as_deref_mut(),
+                )?
+                .ty()
+                .clone();
+                Tir::from_hir(hir, ty)
+            }
+            HirKind::Expr(ekind) => {
+                let ekind = ekind.traverse_ref(|e| {
+                    type_with_nodes(env, e, None, nodes.as_deref_mut())
+                })?;
+                let ty = type_one_layer(env, ekind, hir.span())?;
+                Tir::from_hir(hir, ty)
+            }
+        };
+
+        if let Some((annot, annot_span)) = annot {
+            if *tir.ty() != annot {
+                let found = elide_type(env, &tir.ty().to_expr_tyenv(env));
+                let expected = elide_type(env, &annot.to_expr_tyenv(env));
+                return mkerr(
+                    ErrorBuilder::new(format!(
+                        "annot mismatch: {} != {}",
+                        found, expected
+                    ))
+                    .kind(TypeErrorKind::AnnotMismatch)
+                    .span_err(hir.span(), format!("this has type: `{}`", found))
+                    .span_help(
+                        annot_span,
+                        format!(
+                            "expected type `{}` because of this annotation",
+                            expected
+                        ),
+                    )
+                    .format(),
+                );
+            }
         }
-    };
 
-    if let Some(annot) = annot {
-        if *tir.ty() != annot {
-            return mk_span_err(
+        if let (Some(nodes), Some(start)) = (nodes, start) {
+            let children = nodes.split_off(start);
+            nodes.push(TypedNode::new(
                 hir.span(),
-                &format!(
-                    "annot mismatch: {} != {}",
-                    tir.ty().to_expr_tyenv(env),
-                    annot.to_expr_tyenv(env)
-                ),
-            );
+                tir.ty().to_expr_tyenv(env),
+                children,
+            ));
         }
-    }
-
-    Ok(tir)
+        Ok(tir)
+    })
 }
 
 /// Typecheck an expression and return the expression annotated with its type if type-checking
@@ -281,11 +573,102 @@ pub fn typecheck<'hir>(hir: &'hir Hir) -> Result<Tir<'hir>, TypeError> {
     type_with(&TyEnv::new(), hir, None)
 }
 
+/// Like `typecheck`, but prints the types mentioned in errors within the given limits.
+pub fn typecheck_with_display_limits<'hir>(
+    hir: &'hir Hir,
+    limits: TypeDisplayLimits,
+) -> Result<Tir<'hir>, TypeError> {
+    type_with(&TyEnv::new().with_display_limits(limits), hir, None)
+}
+
+/// Like `typecheck`, but returns the tree of the subexpressions of `hir` annotated with their
+/// types, as inferred in a single pass.
+pub fn typecheck_tree(hir: &Hir) -> Result<TypedNode, TypeError> {
+    let mut nodes = Vec::new();
+    type_with_nodes(&TyEnv::new(), hir, None, Some(&mut nodes))?;
+    Ok(nodes.pop().unwrap())
+}
+
+/// Like `typecheck`, but doesn't stop at the first error: on failure, returns all the errors that
+/// could be found. Subexpressions that don't depend on each other, like record fields or list
+/// elements, are checked independently. The body of a `let` whose value fails to typecheck is
+/// still checked if the binding has a type annotation.
+pub fn typecheck_all<'hir>(
+    hir: &'hir Hir,
+) -> Result<Tir<'hir>, Vec<TypeError>> {
+    let env = TyEnv::new();
+    type_with(&env, hir, None).map_err(|_| {
+        let mut errors = Vec::new();
+        collect_errors(&env, hir, &mut errors);
+        errors
+    })
+}
+
+/// Pushes the type errors found in `hir` to `errors`. When an expression fails to typecheck, its
+/// subexpressions are checked first; the error of the expression itself is only reported if
+/// none of them failed, since it is most likely a consequence of theirs otherwise.
+fn collect_errors(env: &TyEnv, hir: &Hir, errors: &mut Vec<TypeError>) {
+    let err = match type_with(env, hir, None) {
+        Ok(_) => return,
+        Err(err) => err,
+    };
+    let as_type = |t: &Hir| type_with(env, t, None)?.eval_to_type(env);
+    let before = errors.len();
+    match hir.kind() {
+        HirKind::Expr(ExprKind::Lam(binder, annot, body))
+        | HirKind::Expr(ExprKind::Pi(binder, annot, body)) => {
+            collect_errors(env, annot, errors);
+            if let Ok(annot) = as_type(annot) {
+                collect_errors(&env.insert_type(binder, annot), body, errors);
+            }
+        }
+        HirKind::Expr(ExprKind::Let(binder, annot, val, body)) => {
+            if let Some(annot) = annot {
+                collect_errors(env, annot, errors);
+            }
+            let before_val = errors.len();
+            collect_errors(env, val, errors);
+            let val_annot = annot
+                .as_ref()
+                .map(|t| Ok::<_, TypeError>((as_type(t)?, t.span())))
+                .transpose();
+            let body_env = match val_annot {
+                Ok(val_annot) => match type_with(env, val, val_annot.clone()) {
+                    Ok(val) => Some(env.insert_value(
+                        binder,
+                        val.eval(env),
+                        val.ty().clone(),
+                    )),
+                    Err(err) => {
+                        // The value doesn't match its annotation.
+                        if errors.len() == before_val {
+                            errors.push(err);
+                        }
+                        val_annot.map(|(t, _)| env.insert_type(binder, t))
+                    }
+                },
+                Err(_) => None,
+            };
+            if let Some(body_env) = body_env {
+                collect_errors(&body_env, body, errors);
+            }
+        }
+        HirKind::Expr(ekind) => {
+            ekind.map_ref(|e| collect_errors(env, e, errors));
+        }
+        _ => {}
+    }
+    if errors.len() == before {
+        errors.push(err);
+    }
+}
+
 /// Like `typecheck`, but additionally checks that the expression's type matches the provided type.
 pub fn typecheck_with<'hir>(
     hir: &'hir Hir,
     ty: &Hir,
 ) -> Result<Tir<'hir>, TypeError> {
     let ty = typecheck(ty)?.eval_to_type(&TyEnv::new())?;
-    type_with(&TyEnv::new(), hir, Some(ty))
+    // The annotation comes from another source, so there is nothing to point at.
+    type_with(&TyEnv::new(), hir, Some((ty, Span::Artificial)))
 }
@@ -0,0 +1,52 @@
+use crate::syntax::{Expr, Span};
+
+/// A subexpression annotated with its inferred type, as returned by `typecheck_tree`. Tools like
+/// editors can use it to show the type of any part of an expression without typechecking it
+/// again.
+#[derive(Debug, Clone)]
+pub struct TypedNode {
+    span: Span,
+    ty: Expr,
+    children: Vec<TypedNode>,
+}
+
+impl TypedNode {
+    pub(crate) fn new(span: Span, ty: Expr, children: Vec<TypedNode>) -> Self {
+        TypedNode { span, ty, children }
+    }
+
+    pub fn span(&self) -> Span {
+        self.span.clone()
+    }
+    /// The type of this subexpression. Variables bound by enclosing functions or `let`s keep
+    /// their names.
+    pub fn ty(&self) -> &Expr {
+        &self.ty
+    }
+    /// The nodes of the immediate subexpressions, in the order they were checked.
+    pub fn children(&self) -> &[TypedNode] {
+        &self.children
+    }
+
+    /// The innermost subexpression whose source text contains the given byte offset, if any.
+    pub fn node_at(&self, offset: usize) -> Option<&TypedNode> {
+        let contains = match &self.span {
+            Span::Parsed(sp) => {
+                let (start, end) = sp.byte_range();
+                start <= offset && offset < end
+            }
+            // Desugared nodes have no location, but their subexpressions might.
+            _ => true,
+        };
+        if !contains {
+            return None;
+        }
+        self.children
+            .iter()
+            .find_map(|child| child.node_at(offset))
+            .or(match self.span {
+                Span::Parsed(_) => Some(self),
+                _ => None,
+            })
+    }
+}
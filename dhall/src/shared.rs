@@ -0,0 +1,34 @@
+//! Helpers used throughout the crate.
+
+/// Reference-counted pointers. By default they are `Rc`s, which are cheaper but can't cross
+/// threads. With the `sync` feature they are `Arc`s instead, so that expressions and values can
+/// be shared between threads.
+#[cfg(not(feature = "sync"))]
+pub use std::rc::{Rc, Weak};
+#[cfg(feature = "sync")]
+pub use std::sync::{Arc as Rc, Weak};
+
+/// Space that must be left on the stack before recursing one more level.
+const STACK_RED_ZONE: usize = 64 * 1024;
+/// Size of the stack segments allocated when we run out.
+const STACK_SEGMENT_SIZE: usize = 2 * 1024 * 1024;
+
+/// Run `f`, first moving to a fresh stack segment if the current one is nearly exhausted.
+/// Expressions and values can be nested arbitrarily deeply, e.g. by a long chain of operators or
+/// by many iterations of a `Natural/fold`, so the passes that recurse through them would otherwise
+/// overflow the stack.
+pub(crate) fn ensure_stack<R>(f: impl FnOnce() -> R) -> R {
+    stacker::maybe_grow(STACK_RED_ZONE, STACK_SEGMENT_SIZE, f)
+}
+
+/// Run `f` with at least `size` bytes of stack, moving to a fresh stack segment if needed. This
+/// is for recursion whose depth is known beforehand but that can't grow the stack as it goes.
+pub(crate) fn with_stack<R>(size: usize, f: impl FnOnce() -> R) -> R {
+    stacker::maybe_grow(size, size, f)
+}
+
+/// Whether recursing further would need a fresh stack segment. A recursive type can check this
+/// when dropped, and only then pay to move its contents out and drop them with `ensure_stack`.
+pub(crate) fn stack_is_low() -> bool {
+    stacker::remaining_stack().is_some_and(|left| left < STACK_RED_ZONE)
+}
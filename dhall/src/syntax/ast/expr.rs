@@ -4,6 +4,7 @@ use crate::builtins::Builtin;
 use crate::error::Error;
 use crate::operations::OpKind;
 use crate::semantics::Universe;
+use crate::shared::{ensure_stack, stack_is_low};
 use crate::syntax::visitor;
 use crate::syntax::*;
 
@@ -38,7 +39,7 @@ impl Const {
 pub struct V(pub Label, pub usize);
 
 // Each node carries an annotation.
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct Expr {
     kind: Box<ExprKind<Expr>>,
     span: Span,
@@ -176,15 +177,94 @@ impl Expr {
             span,
         }
     }
+    pub(crate) fn with_span(mut self, span: Span) -> Self {
+        self.span = span;
+        self
+    }
+
+    /// Returns the canonical form of the expression, suitable for textual comparison: all bound
+    /// variables are renamed to `_` and import hashes are removed. Record and union fields are
+    /// already kept sorted, and the printer uses a fixed layout.
+    pub fn to_canonical(&self) -> Expr {
+        canonicalize(self, &mut Vec::new())
+    }
 
     // Compute the sha256 hash of the binary form of the expression.
     pub fn hash(&self) -> Result<Box<[u8]>, Error> {
-        use sha2::Digest;
         let data = binary::encode(self)?;
-        Ok(sha2::Sha256::digest(&data).as_slice().into())
+        Ok(Box::new(crate::hash::sha256(&data)))
+    }
+}
+
+// Expressions can be nested arbitrarily deeply, e.g. by a long chain of operators, so the
+// traversals implemented here move to a fresh stack segment when the current one runs low.
+impl Clone for Expr {
+    fn clone(&self) -> Self {
+        ensure_stack(|| Expr {
+            kind: self.kind.clone(),
+            span: self.span.clone(),
+        })
     }
 }
 
+impl Drop for Expr {
+    fn drop(&mut self) {
+        if stack_is_low() {
+            let kind = std::mem::replace(
+                &mut self.kind,
+                Box::new(ExprKind::Const(Const::Type)),
+            );
+            ensure_stack(|| drop(kind));
+        }
+    }
+}
+
+fn canonicalize(expr: &Expr, env: &mut Vec<Label>) -> Expr {
+    use ExprKind::*;
+    let kind = match expr.kind() {
+        Var(V(x, n)) => {
+            let mut n = *n;
+            let mut found = None;
+            for (idx, l) in env.iter().rev().enumerate() {
+                if l == x {
+                    if n == 0 {
+                        found = Some(idx);
+                        break;
+                    }
+                    n -= 1;
+                }
+            }
+            match found {
+                Some(idx) => Var(V("_".into(), idx)),
+                // Free variable: skip the binders that were renamed.
+                None => Var(V(x.clone(), n)),
+            }
+        }
+        kind => {
+            let kind = kind.map_ref_maybe_binder(|l, e| match l {
+                None => canonicalize(e, env),
+                Some(l) => {
+                    env.push(l.clone());
+                    let e = canonicalize(e, env);
+                    env.pop();
+                    e
+                }
+            });
+            match kind {
+                Lam(_, t, e) => Lam("_".into(), t, e),
+                Pi(_, t, e) => Pi("_".into(), t, e),
+                Let(_, t, a, e) => Let("_".into(), t, a, e),
+                Import(import) => Import(crate::syntax::Import {
+                    hash: None,
+                    ..import
+                }),
+                kind => kind,
+            }
+        }
+    };
+    Expr::new(kind, expr.span())
+}
+
 // Empty enum to indicate that no error can occur
 pub(crate) enum X {}
 pub(crate) fn trivial_result<T>(x: Result<T, X>) -> T {
@@ -231,7 +311,7 @@ impl From<Label> for V {
 
 impl std::cmp::PartialEq for Expr {
     fn eq(&self, other: &Self) -> bool {
-        self.kind == other.kind
+        ensure_stack(|| self.kind == other.kind)
     }
 }
 
@@ -242,6 +322,6 @@ impl std::hash::Hash for Expr {
     where
         H: std::hash::Hasher,
     {
-        self.kind.hash(state)
+        ensure_stack(|| self.kind.hash(state))
     }
 }
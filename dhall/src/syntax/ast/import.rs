@@ -13,6 +13,8 @@ pub enum FilePrefix {
     Home,
 }
 
+/// The components of a path. For local imports these are the literal names of the directories and
+/// file; for remote imports they are percent-encoded URL path segments.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct FilePath {
     pub file_path: Vec<String>,
@@ -63,6 +65,33 @@ pub struct Import<SubExpr> {
     pub hash: Option<Hash>,
 }
 
+/// Percent-encodes a literal path component (e.g. the contents of a quoted path component) so it
+/// can be used as a URL path segment. Reserved characters and characters that aren't allowed in
+/// URLs get encoded; non-ascii characters are kept as they are.
+pub fn percent_encode_path_component(component: &str) -> String {
+    #[rustfmt::skip]
+    const ENCODED: &percent_encoding::AsciiSet =
+        &percent_encoding::CONTROLS
+        .add(b'=').add(b':').add(b'/').add(b'?')
+        .add(b'#').add(b'[').add(b']').add(b'@')
+        .add(b'!').add(b'$').add(b'&').add(b'\'')
+        .add(b'(').add(b')').add(b'*').add(b'+')
+        .add(b',').add(b';').add(b' ').add(b'"')
+        .add(b'<').add(b'>').add(b'\\').add(b'^')
+        .add(b'`').add(b'{').add(b'|').add(b'}');
+    component
+        .chars()
+        .map(|c| {
+            if c.is_ascii() {
+                percent_encoding::utf8_percent_encode(&c.to_string(), ENCODED)
+                    .to_string()
+            } else {
+                c.to_string()
+            }
+        })
+        .collect()
+}
+
 impl<SE> URL<SE> {
     pub fn traverse_ref<'a, Err, SE2>(
         &'a self,
@@ -1,4 +1,4 @@
-use std::rc::Rc;
+use crate::shared::Rc;
 
 // The type for labels throughout the AST
 // It owns the data because otherwise lifetimes would make recursive imports impossible
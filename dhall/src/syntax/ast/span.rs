@@ -1,4 +1,6 @@
-use std::rc::Rc;
+use crate::error::SourcePosition;
+use crate::shared::Rc;
+use crate::syntax::Label;
 
 /// A location in the source text
 #[derive(Debug, Clone)]
@@ -18,10 +20,10 @@ pub struct ParsedSpan {
 pub enum Span {
     /// A location in the source text
     Parsed(ParsedSpan),
-    /// Desugarings
-    DuplicateRecordFieldsSugar,
+    /// Desugarings. Duplicate fields of a record literal are merged with `∧`; this records the
+    /// name of the field.
+    DuplicateRecordFieldsSugar(Label),
     DottedFieldSugar,
-    WithSugar,
     RecordPunSugar,
     /// For expressions obtained from decoding binary
     Decoded,
@@ -30,9 +32,65 @@ pub enum Span {
 }
 
 impl ParsedSpan {
+    pub fn make(input: Rc<str>, sp: pest::Span) -> Self {
+        ParsedSpan {
+            input,
+            start: sp.start(),
+            end: sp.end(),
+        }
+    }
+    /// The span covering the given byte offsets of the same source text.
+    pub(crate) fn with_range(&self, start: usize, end: usize) -> Self {
+        ParsedSpan {
+            input: self.input.clone(),
+            start,
+            end,
+        }
+    }
+    /// The span covering the whole of another source text.
+    pub(crate) fn with_input(&self, input: Rc<str>) -> Self {
+        ParsedSpan {
+            start: 0,
+            end: input.len(),
+            input,
+        }
+    }
+    pub(crate) fn input(&self) -> &Rc<str> {
+        &self.input
+    }
     pub fn to_input(&self) -> String {
         self.input.to_string()
     }
+    /// Whether the two spans point into the same source text.
+    pub fn same_input(&self, other: &ParsedSpan) -> bool {
+        Rc::ptr_eq(&self.input, &other.input)
+    }
+    /// The source text covered by the span.
+    pub fn as_str(&self) -> &str {
+        &self.input[self.start..self.end]
+    }
+    /// The byte offsets of the span's start and end in the source text.
+    pub fn byte_range(&self) -> (usize, usize) {
+        (self.start, self.end)
+    }
+    /// The 1-based line and column of the span's start and end.
+    pub fn position(&self) -> SourcePosition {
+        let line_col = |idx: usize| {
+            let before = &self.input[..idx];
+            let line = before.matches('\n').count() + 1;
+            let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+            let column = before[line_start..].chars().count() + 1;
+            (line, column)
+        };
+        let (line, column) = line_col(self.start);
+        let (end_line, end_column) = line_col(self.end);
+        SourcePosition {
+            line,
+            column,
+            end_line,
+            end_column,
+        }
+    }
     /// Convert to a char range for consumption by annotate_snippets.
     /// This compensates for  https://github.com/rust-lang/annotate-snippets-rs/issues/24
     pub fn as_char_range(&self) -> (usize, usize) {
@@ -45,11 +103,7 @@ impl ParsedSpan {
 
 impl Span {
     pub fn make(input: Rc<str>, sp: pest::Span) -> Self {
-        Span::Parsed(ParsedSpan {
-            input,
-            start: sp.start(),
-            end: sp.end(),
-        })
+        Span::Parsed(ParsedSpan::make(input, sp))
     }
 
     /// Takes the union of the two spans, i.e. the range of input covered by the two spans plus any
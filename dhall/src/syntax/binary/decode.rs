@@ -203,6 +203,20 @@ fn cbor_value_to_dhall(data: &cbor::Value) -> Result<DecodedExpr, DecodeError> {
                     ));
                 }
             }
+            [U64(29), x, Array(ls), y] => {
+                let x = cbor_value_to_dhall(x)?;
+                let y = cbor_value_to_dhall(y)?;
+                let labels = ls
+                    .iter()
+                    .map(|s| match s {
+                        String(s) => Ok(Label::from(s.as_str())),
+                        _ => Err(DecodeError::WrongFormatError(
+                            "with".to_owned(),
+                        )),
+                    })
+                    .collect::<Result<_, _>>()?;
+                Op(With(x, labels, y))
+            }
             [U64(10), x, rest @ ..] => {
                 let x = cbor_value_to_dhall(&x)?;
                 let labels = rest
@@ -171,6 +171,12 @@ where
         Op(Completion(x, y)) => {
             ser_seq!(ser; tag(3), tag(13), expr(x), expr(y))
         }
+        Op(With(x, ls, y)) => ser_seq!(ser;
+            tag(29),
+            expr(x),
+            ls.iter().map(label).collect::<Vec<_>>(),
+            expr(y)
+        ),
         Import(import) => serialize_import(ser, import),
     }
 }
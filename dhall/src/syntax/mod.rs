@@ -8,7 +8,10 @@
 mod ast;
 pub use crate::syntax::ast::visitor;
 pub use crate::syntax::ast::*;
+pub use crate::syntax::text::cst::*;
+pub use crate::syntax::text::nesting::DEFAULT_MAX_NESTING_DEPTH;
 pub use crate::syntax::text::parser::*;
 pub use crate::syntax::text::printer::*;
+pub use crate::syntax::text::recovery::*;
 pub mod binary;
 pub mod text;
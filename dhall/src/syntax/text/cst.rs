@@ -0,0 +1,456 @@
+//! A lossless concrete syntax tree.
+//!
+//! Unlike the AST, the concrete syntax tree keeps every byte of the source: whitespace, comments,
+//! keywords and punctuation. Its nodes are the (non-silent) rules of the grammar, and its leaves
+//! are tokens that, read in order, give back the source text exactly. This is what tools that
+//! rewrite source files (formatting, linting, freezing imports) need to keep user comments.
+//!
+//! Editors can keep the tree up to date as the text changes with [`SyntaxTree::reparse`], which
+//! only parses the part of the text around each edit again.
+use std::ops::Range;
+
+use pest::iterators::Pair;
+
+use crate::error::ParseError;
+use crate::shared::Rc;
+use crate::syntax::text::parser::{
+    edit_range_error, parse_rule_at, parse_syntax_tree, Rule,
+};
+use crate::syntax::{ParsedSpan, Span};
+
+/// The concrete syntax tree of a whole source text.
+#[derive(Debug, Clone)]
+pub struct SyntaxTree {
+    root: SyntaxNode,
+}
+
+/// A node of the concrete syntax tree, corresponding to a grammar rule.
+#[derive(Debug, Clone)]
+pub struct SyntaxNode {
+    rule: Rule,
+    span: ParsedSpan,
+    children: Vec<SyntaxElement>,
+}
+
+/// A leaf of the concrete syntax tree.
+#[derive(Debug, Clone)]
+pub struct SyntaxToken {
+    kind: TokenKind,
+    span: ParsedSpan,
+}
+
+#[derive(Debug, Clone)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    /// Spaces, tabs and newlines
+    Whitespace,
+    /// `-- ...`, including the newline that ends it
+    LineComment,
+    /// `{- ... -}`, which may be nested
+    BlockComment,
+    /// Anything else: keywords, punctuation, labels, literals, etc.
+    Text,
+}
+
+/// A change to a source text: the bytes in `range` are replaced with `text`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+impl TokenKind {
+    /// Whether tokens of this kind are whitespace or comments.
+    pub fn is_trivia(self) -> bool {
+        self != TokenKind::Text
+    }
+}
+
+impl SyntaxTree {
+    pub(crate) fn new(input: Rc<str>, pair: Pair<'_, Rule>) -> Self {
+        let span = ParsedSpan::make(input, pair.as_span());
+        SyntaxTree {
+            root: SyntaxNode::from_pair(&span, 0, pair),
+        }
+    }
+
+    /// Applies an edit to the source text and returns the syntax tree of the new text.
+    ///
+    /// Only the smallest expression around the edit that parses the same way on its own is
+    /// parsed again; the rest of the tree is reused. If there is no such expression, or the new
+    /// text has a syntax error, the whole text is parsed again.
+    ///
+    /// Fails if the range of the edit is reversed, out of bounds or not on character boundaries.
+    ///
+    /// ```
+    /// # use dhall::syntax::TextEdit;
+    /// let (_, tree) = dhall::Parsed::parse_str_lossless("[ 1, 2 ]").unwrap();
+    /// let edit = TextEdit {
+    ///     range: 5..6,
+    ///     text: "f 3 -- comment\n".to_owned(),
+    /// };
+    /// let tree = tree.reparse(&edit).unwrap();
+    /// assert_eq!(tree.text(), "[ 1, f 3 -- comment\n ]");
+    /// assert_eq!(tree.comments().count(), 1);
+    /// ```
+    pub fn reparse(&self, edit: &TextEdit) -> Result<SyntaxTree, ParseError> {
+        let old_text = self.text();
+        let range = &edit.range;
+        let problem = if range.start > range.end {
+            Some("it ends before it starts")
+        } else if range.end > old_text.len() {
+            Some("it goes past the end of the text")
+        } else if !old_text.is_char_boundary(range.start)
+            || !old_text.is_char_boundary(range.end)
+        {
+            Some("it splits a character")
+        } else {
+            None
+        };
+        if let Some(problem) = problem {
+            return Err(edit_range_error(&old_text.into(), range, problem));
+        }
+        let mut new_text = String::with_capacity(
+            old_text.len() - edit.range.len() + edit.text.len(),
+        );
+        new_text.push_str(&old_text[..edit.range.start]);
+        new_text.push_str(&edit.text);
+        new_text.push_str(&old_text[edit.range.end..]);
+        let input: Rc<str> = new_text.into();
+
+        let span = self.root.span.with_input(input.clone());
+        match self.root.reparse(&span, edit) {
+            Some(root) => Ok(SyntaxTree { root }),
+            None => parse_syntax_tree(&input),
+        }
+    }
+
+    /// The whole source text.
+    pub fn text(&self) -> &str {
+        self.root.text()
+    }
+
+    /// The node for the whole source text.
+    pub fn root(&self) -> &SyntaxNode {
+        &self.root
+    }
+    /// All the tokens of the tree, in source order.
+    pub fn tokens(&self) -> Vec<&SyntaxToken> {
+        self.root.tokens()
+    }
+    /// The comments of the source text, in order.
+    pub fn comments(&self) -> impl Iterator<Item = &SyntaxToken> {
+        self.tokens().into_iter().filter(|tok| {
+            matches!(tok.kind, TokenKind::LineComment | TokenKind::BlockComment)
+        })
+    }
+}
+
+impl SyntaxNode {
+    /// Builds the node for a pair parsed from byte offset `offset` of the input.
+    fn from_pair(
+        input: &ParsedSpan,
+        offset: usize,
+        pair: Pair<'_, Rule>,
+    ) -> Self {
+        let rule = pair.as_rule();
+        let start = offset + pair.as_span().start();
+        let end = offset + pair.as_span().end();
+        // Outside of interpolations, the text between the children of a text literal is part of
+        // the literal, even when it looks like whitespace.
+        let in_text_literal = matches!(
+            rule,
+            Rule::double_quote_literal
+                | Rule::double_quote_chunk
+                | Rule::single_quote_literal
+                | Rule::single_quote_continue
+        );
+
+        let mut children = Vec::new();
+        let mut pos = start;
+        let mut after_expr = false;
+        for child in pair.into_inner() {
+            if child.as_rule() == Rule::EOI {
+                continue;
+            }
+            let child_start = offset + child.as_span().start();
+            let is_expr = child.as_rule() == Rule::expression;
+            let lex = !in_text_literal || after_expr || is_expr;
+            push_gap(input, pos, child_start, lex, &mut children);
+            pos = offset + child.as_span().end();
+            after_expr = is_expr;
+            children.push(SyntaxElement::Node(SyntaxNode::from_pair(
+                input, offset, child,
+            )));
+        }
+        if children.is_empty() {
+            // Leaves only contain whitespace for operators and empty records.
+            let lex = matches!(
+                rule,
+                Rule::import_alt
+                    | Rule::natural_plus
+                    | Rule::empty_record_literal
+                    | Rule::empty_record_type
+            );
+            if lex {
+                lex_trivia(input, start, end, &mut children);
+            } else if start != end {
+                children.push(SyntaxElement::Token(SyntaxToken {
+                    kind: TokenKind::Text,
+                    span: input.with_range(start, end),
+                }));
+            }
+        } else {
+            let lex = !in_text_literal || after_expr;
+            push_gap(input, pos, end, lex, &mut children);
+        }
+
+        SyntaxNode {
+            rule,
+            span: input.with_range(start, end),
+            children,
+        }
+    }
+
+    /// Builds the node for the edited text, reusing what the edit didn't touch. `input` spans the
+    /// whole edited text. Returns `None` if the edit isn't inside a node that can be parsed on its
+    /// own.
+    fn reparse(&self, input: &ParsedSpan, edit: &TextEdit) -> Option<Self> {
+        let (start, end) = self.span.byte_range();
+        let contains = |(start, end): (usize, usize)| {
+            start <= edit.range.start && edit.range.end <= end
+        };
+        if !contains((start, end)) {
+            return None;
+        }
+        let new_end = end - edit.range.len() + edit.text.len();
+
+        // Prefer reparsing the smallest node possible.
+        let child = self.children.iter().position(|child| match child {
+            SyntaxElement::Node(node) => contains(node.span.byte_range()),
+            SyntaxElement::Token(_) => false,
+        });
+        if let Some(i) = child {
+            let new_child = match &self.children[i] {
+                SyntaxElement::Node(node) => node.reparse(input, edit),
+                SyntaxElement::Token(_) => None,
+            };
+            if let Some(new_child) = new_child {
+                let shift =
+                    |pos: usize| pos - edit.range.len() + edit.text.len();
+                let children = self.children[..i]
+                    .iter()
+                    .map(|child| child.moved(input, &|pos| pos))
+                    .chain(Some(SyntaxElement::Node(new_child)))
+                    .chain(
+                        self.children[i + 1..]
+                            .iter()
+                            .map(|child| child.moved(input, &shift)),
+                    )
+                    .collect();
+                return Some(SyntaxNode {
+                    rule: self.rule,
+                    span: input.with_range(start, new_end),
+                    children,
+                });
+            }
+        }
+
+        // Expressions are delimited by the text around them: parsing them again on their own,
+        // up to the same place, gives the same result as parsing them in context.
+        if !matches!(self.rule, Rule::expression | Rule::complete_expression) {
+            return None;
+        }
+        let pair = parse_rule_at(self.rule, input.input(), start, new_end)?;
+        Some(SyntaxNode::from_pair(input, start, pair))
+    }
+
+    /// Copies the node into another source text, moving its offsets with `shift`.
+    fn moved(
+        &self,
+        input: &ParsedSpan,
+        shift: &dyn Fn(usize) -> usize,
+    ) -> Self {
+        let (start, end) = self.span.byte_range();
+        SyntaxNode {
+            rule: self.rule,
+            span: input.with_range(shift(start), shift(end)),
+            children: self
+                .children
+                .iter()
+                .map(|child| child.moved(input, shift))
+                .collect(),
+        }
+    }
+
+    /// The grammar rule this node was parsed with.
+    pub fn rule(&self) -> Rule {
+        self.rule
+    }
+    pub fn span(&self) -> Span {
+        Span::Parsed(self.span.clone())
+    }
+    /// The source text covered by this node, including whitespace and comments.
+    pub fn text(&self) -> &str {
+        self.span.as_str()
+    }
+    pub fn children(&self) -> &[SyntaxElement] {
+        &self.children
+    }
+    /// The child nodes, skipping tokens.
+    pub fn child_nodes(&self) -> impl Iterator<Item = &SyntaxNode> {
+        self.children.iter().filter_map(|child| match child {
+            SyntaxElement::Node(node) => Some(node),
+            SyntaxElement::Token(_) => None,
+        })
+    }
+    /// All the tokens below this node, in source order.
+    pub fn tokens(&self) -> Vec<&SyntaxToken> {
+        let mut tokens = Vec::new();
+        let mut stack = vec![self.children.iter()];
+        while let Some(children) = stack.last_mut() {
+            match children.next() {
+                Some(SyntaxElement::Token(tok)) => tokens.push(tok),
+                Some(SyntaxElement::Node(node)) => {
+                    stack.push(node.children.iter())
+                }
+                None => {
+                    stack.pop();
+                }
+            }
+        }
+        tokens
+    }
+}
+
+impl SyntaxToken {
+    pub fn kind(&self) -> TokenKind {
+        self.kind
+    }
+    pub fn span(&self) -> Span {
+        Span::Parsed(self.span.clone())
+    }
+    pub fn text(&self) -> &str {
+        self.span.as_str()
+    }
+}
+
+impl SyntaxElement {
+    fn moved(
+        &self,
+        input: &ParsedSpan,
+        shift: &dyn Fn(usize) -> usize,
+    ) -> Self {
+        match self {
+            SyntaxElement::Node(node) => {
+                SyntaxElement::Node(node.moved(input, shift))
+            }
+            SyntaxElement::Token(tok) => {
+                let (start, end) = tok.span.byte_range();
+                SyntaxElement::Token(SyntaxToken {
+                    kind: tok.kind,
+                    span: input.with_range(shift(start), shift(end)),
+                })
+            }
+        }
+    }
+    pub fn span(&self) -> Span {
+        match self {
+            SyntaxElement::Node(node) => node.span(),
+            SyntaxElement::Token(tok) => tok.span(),
+        }
+    }
+    pub fn text(&self) -> &str {
+        match self {
+            SyntaxElement::Node(node) => node.text(),
+            SyntaxElement::Token(tok) => tok.text(),
+        }
+    }
+}
+
+/// Adds the text between two children of a node, which only contains silent rules (keywords,
+/// punctuation and whitespace).
+fn push_gap(
+    input: &ParsedSpan,
+    start: usize,
+    end: usize,
+    lex: bool,
+    out: &mut Vec<SyntaxElement>,
+) {
+    if lex {
+        lex_trivia(input, start, end, out);
+    } else if start != end {
+        out.push(SyntaxElement::Token(SyntaxToken {
+            kind: TokenKind::Text,
+            span: input.with_range(start, end),
+        }));
+    }
+}
+
+/// Splits source text into whitespace, comments, and the runs of text between them.
+fn lex_trivia(
+    input: &ParsedSpan,
+    start: usize,
+    end: usize,
+    out: &mut Vec<SyntaxElement>,
+) {
+    let span = input.with_range(start, end);
+    let text = span.as_str();
+    let is_whitespace = |c: char| matches!(c, ' ' | '\t' | '\n' | '\r');
+    let mut pos = 0;
+    while pos < text.len() {
+        let rest = &text[pos..];
+        let (kind, len) = if rest.starts_with(is_whitespace) {
+            let len = rest.find(|c| !is_whitespace(c)).unwrap_or(rest.len());
+            (TokenKind::Whitespace, len)
+        } else if rest.starts_with("--") {
+            let len = rest.find('\n').map(|i| i + 1).unwrap_or(rest.len());
+            (TokenKind::LineComment, len)
+        } else if rest.starts_with("{-") {
+            (TokenKind::BlockComment, block_comment_len(rest))
+        } else {
+            let len = rest
+                .char_indices()
+                .find(|&(i, c)| {
+                    is_whitespace(c)
+                        || rest[i..].starts_with("--")
+                        || rest[i..].starts_with("{-")
+                })
+                .map(|(i, _)| i)
+                .unwrap_or(rest.len());
+            (TokenKind::Text, len)
+        };
+        out.push(SyntaxElement::Token(SyntaxToken {
+            kind,
+            span: input.with_range(start + pos, start + pos + len),
+        }));
+        pos += len;
+    }
+}
+
+/// The length of the (possibly nested) block comment at the start of `text`.
+fn block_comment_len(text: &str) -> usize {
+    let mut depth = 0;
+    let mut pos = 0;
+    while pos < text.len() {
+        let rest = &text[pos..];
+        if rest.starts_with("{-") {
+            depth += 1;
+            pos += 2;
+        } else if rest.starts_with("-}") {
+            depth -= 1;
+            pos += 2;
+            if depth == 0 {
+                return pos;
+            }
+        } else {
+            pos += rest.chars().next().unwrap().len_utf8();
+        }
+    }
+    text.len()
+}
@@ -1,2 +1,5 @@
+pub mod cst;
+pub mod nesting;
 pub mod parser;
 pub mod printer;
+pub mod recovery;
@@ -0,0 +1,209 @@
+//! A limit on how deeply expressions may nest, checked before parsing.
+//!
+//! The parser recurses several times per level of nesting, and can't count its own recursion. So
+//! the text is scanned beforehand: input that nests too deeply is rejected, and otherwise the
+//! parser gets a stack large enough for the depth that was found.
+
+/// How deeply expressions may nest unless the caller chooses otherwise.
+pub const DEFAULT_MAX_NESTING_DEPTH: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Frame {
+    TopLevel,
+    Bracket,
+    Interpolation,
+    DoubleQuoted,
+    SingleQuoted,
+}
+
+impl Frame {
+    fn depth(self) -> usize {
+        match self {
+            Frame::Bracket | Frame::Interpolation => 1,
+            _ => 0,
+        }
+    }
+}
+
+fn is_label_byte(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_' || c == b'-' || c == b'/'
+}
+
+fn is_whitespace_byte(c: u8) -> bool {
+    c == b' ' || c == b'\t' || c == b'\n' || c == b'\r'
+}
+
+/// Measures how deeply `text` nests, and returns the deepest level found, or the byte range of
+/// the token where the nesting first gets deeper than `max_depth`.
+///
+/// Parentheses, `[`, `{`, `<` and text interpolations each count as a level, as does each
+/// expression nested inside another without brackets: the body of a `let`, the type of a
+/// `λ`, `∀` or arrow, an `if` and an annotation. Those last until the end of the enclosing
+/// brackets, or until the next `,` or `|` in them, or for those within the value of a `let`
+/// binding, until the next `let` or `in`. So the depth is sometimes overestimated, e.g. for an
+/// `if` in the middle of an operator chain. Unlike the parser, this doesn't recurse.
+pub(crate) fn nesting_depth(
+    text: &str,
+    max_depth: usize,
+) -> Result<usize, (usize, usize)> {
+    use Frame::*;
+    let b = text.as_bytes();
+    // The open frames, each with the number of expressions nested in it since its last separator,
+    // and that number when the last `let` binding in it started, if the binding is still going.
+    let mut frames = vec![(TopLevel, 0, None)];
+    let mut depth = 0;
+    let mut deepest = 0;
+    let mut i = 0;
+    while i < b.len() {
+        let rest = &b[i..];
+        let top = frames.last().unwrap().0;
+        let mut push = None;
+        let mut pop = false;
+        let mut nest = false;
+        let mut separator = false;
+        let mut binding = None;
+        let end = match top {
+            DoubleQuoted | SingleQuoted if rest.starts_with(b"${") => {
+                push = Some(Interpolation);
+                i + 2
+            }
+            DoubleQuoted => match b[i] {
+                b'\\' => i + 2,
+                b'"' => {
+                    pop = true;
+                    i + 1
+                }
+                _ => i + 1,
+            },
+            SingleQuoted => {
+                if rest.starts_with(b"'''") {
+                    i + 3
+                } else if rest.starts_with(b"''${") {
+                    i + 4
+                } else if rest.starts_with(b"''") {
+                    pop = true;
+                    i + 2
+                } else {
+                    i + 1
+                }
+            }
+            _ if rest.starts_with(b"--") => rest
+                .iter()
+                .position(|&c| c == b'\n')
+                .map_or(b.len(), |n| i + n + 1),
+            _ if rest.starts_with(b"{-") => skip_block_comment(b, i),
+            _ if rest.starts_with(b"''") => {
+                push = Some(SingleQuoted);
+                i + 2
+            }
+            _ if rest.starts_with(b"->") => {
+                nest = true;
+                i + 2
+            }
+            _ if rest.starts_with("→".as_bytes()) => {
+                nest = true;
+                i + "→".len()
+            }
+            // The `::` of record completion and the `||` operator.
+            _ if rest.starts_with(b"::") || rest.starts_with(b"||") => i + 2,
+            // An annotation, unlike the `:` of `env:HOME`, URLs or hashes, is followed by
+            // whitespace or a comment.
+            _ if b[i] == b':' => {
+                let next = &rest[1..];
+                nest = next.first().copied().is_some_and(is_whitespace_byte)
+                    || next.starts_with(b"--")
+                    || next.starts_with(b"{-");
+                i + 1
+            }
+            _ if is_label_byte(b[i]) => {
+                let mut n = 1;
+                while n < rest.len()
+                    && is_label_byte(rest[n])
+                    && !rest[n..].starts_with(b"->")
+                    && !rest[n..].starts_with(b"--")
+                {
+                    n += 1;
+                }
+                match &rest[..n] {
+                    b"let" => binding = Some(true),
+                    b"in" => {
+                        binding = Some(false);
+                        nest = true;
+                    }
+                    b"if" => nest = true,
+                    _ => {}
+                }
+                i + n
+            }
+            _ => {
+                match b[i] {
+                    b'"' => push = Some(DoubleQuoted),
+                    b'(' | b'[' | b'{' | b'<' => push = Some(Bracket),
+                    // Stray closing brackets are left for the parser to report.
+                    b')' | b']' | b'}' | b'>' => pop = top != TopLevel,
+                    b',' | b'|' => separator = true,
+                    _ => {}
+                }
+                if b[i] == b'`' {
+                    rest[1..]
+                        .iter()
+                        .position(|&c| c == b'`')
+                        .map_or(b.len(), |n| i + n + 2)
+                } else {
+                    i + 1
+                }
+            }
+        };
+        if let Some(frame) = push {
+            depth += frame.depth();
+            frames.push((frame, 0, None));
+        }
+        if pop {
+            let (frame, nested, _) = frames.pop().unwrap();
+            depth -= frame.depth() + nested;
+        }
+        let (_, nested, let_start) = frames.last_mut().unwrap();
+        if separator {
+            depth -= *nested;
+            *nested = 0;
+            *let_start = None;
+        }
+        // A `let` or `in` ends the value of the previous binding.
+        if let Some(starts_binding) = binding {
+            if let Some(start) = *let_start {
+                depth -= *nested - start;
+                *nested = start;
+            }
+            *let_start = if starts_binding { Some(*nested) } else { None };
+        }
+        if nest {
+            *nested += 1;
+            depth += 1;
+        }
+        if depth > max_depth {
+            return Err((i, end));
+        }
+        deepest = deepest.max(depth);
+        i = end;
+    }
+    Ok(deepest)
+}
+
+fn skip_block_comment(b: &[u8], mut i: usize) -> usize {
+    let mut depth = 0;
+    while i < b.len() {
+        if b[i..].starts_with(b"{-") {
+            depth += 1;
+            i += 2;
+        } else if b[i..].starts_with(b"-}") {
+            depth -= 1;
+            i += 2;
+            if depth == 0 {
+                return i;
+            }
+        } else {
+            i += 1;
+        }
+    }
+    i
+}
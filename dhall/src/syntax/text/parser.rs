@@ -2,18 +2,23 @@ use itertools::Itertools;
 use pest::prec_climber as pcl;
 use pest::prec_climber::PrecClimber;
 use std::collections::{BTreeMap, BTreeSet};
+use std::io::Read;
 use std::iter::once;
-use std::rc::Rc;
 
 use pest_consume::{match_nodes, Parser};
 
+use crate::error::{Error, ErrorBuilder, ParseError};
 use crate::operations::OpKind::*;
+use crate::shared::{ensure_stack, with_stack, Rc};
+use crate::syntax::text::cst::SyntaxTree;
+use crate::syntax::text::nesting::{nesting_depth, DEFAULT_MAX_NESTING_DEPTH};
 use crate::syntax::ExprKind::*;
 use crate::syntax::NumKind::*;
 use crate::syntax::{
-    Double, Expr, FilePath, FilePrefix, Hash, ImportMode, ImportTarget,
-    Integer, InterpolatedText, InterpolatedTextContents, Label, NaiveDouble,
-    Natural, Scheme, Span, UnspannedExpr, URL, V,
+    percent_encode_path_component, Double, Expr, FilePath, FilePrefix, Hash,
+    ImportMode, ImportTarget, Integer, InterpolatedText,
+    InterpolatedTextContents, Label, NaiveDouble, Natural, ParsedSpan, Scheme,
+    Span, UnspannedExpr, URL, V,
 };
 
 // This file consumes the parse tree generated by pest and turns it into
@@ -25,8 +30,8 @@ type ParsedText = InterpolatedText<Expr>;
 type ParsedTextContents = InterpolatedTextContents<Expr>;
 type ParseInput<'input> = pest_consume::Node<'input, Rule, Rc<str>>;
 
-pub type ParseError = pest::error::Error<Rule>;
-pub type ParseResult<T> = Result<T, ParseError>;
+type PestError = pest::error::Error<Rule>;
+type ParseResult<T> = Result<T, PestError>;
 
 #[derive(Debug)]
 enum Selector {
@@ -45,6 +50,52 @@ fn spanned_union(span1: Span, span2: Span, x: UnspannedExpr) -> Expr {
     Expr::new(x, span1.union(&span2))
 }
 
+/// Most expressions go through a long chain of nodes with a single child in the parse tree
+/// (`expression` > `operator_expression` > ... > `primitive_expression`). This returns the last
+/// node of the chain below `input`, if any, so that it can be parsed directly instead of going
+/// through a nested call for each level. Unlike pest_consume's `shortcut` aliases, this doesn't
+/// build an error for every node that has several children, which takes time proportional to the
+/// size of the input.
+fn skip_single_children<'i>(input: &ParseInput<'i>) -> Option<ParseInput<'i>> {
+    let mut node = None;
+    loop {
+        let current = node.as_ref().unwrap_or(input);
+        // Other rules, like `non_empty_list_literal`, can have a single child that is not the
+        // whole expression.
+        if !is_chain_rule(current.as_rule()) {
+            return node;
+        }
+        let mut children = current.children();
+        match (children.next(), children.next()) {
+            // Parentheses are kept in the span of the expression inside them, so
+            // `primitive_expression` has to see them.
+            (Some(child), None)
+                if child.as_aliased_rule::<DhallParser>()
+                    == AliasedRule::expression
+                    && child.as_span() == current.as_span() =>
+            {
+                node = Some(child)
+            }
+            _ => return node,
+        }
+    }
+}
+
+fn is_chain_rule(rule: Rule) -> bool {
+    matches!(
+        rule,
+        Rule::expression
+            | Rule::operator_expression
+            | Rule::with_expression
+            | Rule::application_expression
+            | Rule::first_application_expression
+            | Rule::completion_expression
+            | Rule::selector_expression
+            | Rule::primitive_expression
+            | Rule::identifier
+    )
+}
+
 // Trim the shared indent off of a vec of lines, as defined by the Dhall semantics of multiline
 // literals.
 fn trim_indent(lines: &mut Vec<ParsedText>) {
@@ -86,6 +137,32 @@ fn trim_indent(lines: &mut Vec<ParsedText>) {
     }
 }
 
+/// Collects the entries of a record type or union type. A duplicate label is an error, which
+/// points at the second entry and tells where the first one is.
+fn collect_unique_entries<'i, T>(
+    entries: impl Iterator<Item = ParseInput<'i>>,
+    entry_kind: &str,
+    container: &str,
+    parse_entry: impl Fn(ParseInput<'i>) -> ParseResult<(Label, T)>,
+) -> ParseResult<BTreeMap<Label, T>> {
+    let mut map = BTreeMap::new();
+    let mut positions = BTreeMap::new();
+    for entry in entries {
+        let position = entry.as_span().start_pos().line_col();
+        let (l, x) = parse_entry(entry.clone())?;
+        if let Some((line, column)) = positions.get(&l) {
+            return Err(entry.error(format!(
+                "Duplicate {} `{}` in {}: it is already defined at line {}, \
+                 column {}",
+                entry_kind, l, container, line, column
+            )));
+        }
+        positions.insert(l.clone(), position);
+        map.insert(l, x);
+    }
+    Ok(map)
+}
+
 /// Insert the expr into the map; in case of collision, create a RecursiveRecordMerge node.
 fn insert_recordlit_entry(map: &mut BTreeMap<Label, Expr>, l: Label, e: Expr) {
     use crate::operations::BinOp::RecursiveRecordMerge;
@@ -97,39 +174,21 @@ fn insert_recordlit_entry(map: &mut BTreeMap<Label, Expr>, l: Label, e: Expr) {
         Entry::Occupied(mut entry) => {
             let dummy = Expr::new(Num(Bool(false)), Span::Artificial);
             let other = entry.insert(dummy);
+            let label = entry.key().clone();
             entry.insert(Expr::new(
                 Op(BinOp(RecursiveRecordMerge, other, e)),
-                Span::DuplicateRecordFieldsSugar,
+                Span::DuplicateRecordFieldsSugar(label),
             ));
         }
     }
 }
 
-fn desugar_with_expr(x: Expr, labels: &[Label], y: Expr) -> Expr {
-    use crate::operations::BinOp::RightBiasedRecordMerge;
-    let expr = |k| Expr::new(k, Span::WithSugar);
-    match labels {
-        [] => y,
-        [l, rest @ ..] => {
-            let res = desugar_with_expr(
-                expr(Op(Field(x.clone(), l.clone()))),
-                rest,
-                y,
-            );
-            expr(Op(BinOp(
-                RightBiasedRecordMerge,
-                x,
-                expr(RecordLit(once((l.clone(), res)).collect())),
-            )))
-        }
-    }
-}
-
 lazy_static::lazy_static! {
     static ref PRECCLIMBER: PrecClimber<Rule> = {
         use Rule::*;
         // In order of precedence
         let operators = vec![
+            equivalent,
             import_alt,
             bool_or,
             natural_plus,
@@ -142,7 +201,6 @@ lazy_static::lazy_static! {
             natural_times,
             bool_eq,
             bool_ne,
-            equivalent,
         ];
         PrecClimber::new(
             operators
@@ -224,27 +282,24 @@ impl DhallParser {
 
                 let s = &s[1..];
                 let s = if &s[0..1] == "{" {
-                    &s[1..s.len() - 1]
+                    // Braced escapes may be padded with any number of zeroes
+                    match s[1..s.len() - 1].trim_start_matches('0') {
+                        "" => "0",
+                        s => s,
+                    }
                 } else {
                     s
                 };
 
-                if s.len() > 8 {
-                    return Err(input.error(
-                        "Escape sequences can't have more than 8 chars"
-                            .to_string(),
-                    ));
-                }
-
-                // pad with zeroes
-                let s: String = std::iter::repeat('0')
-                    .take(8 - s.len())
-                    .chain(s.chars())
-                    .collect();
-
-                // `s` has length 8, so `bytes` has length 4
-                let bytes: &[u8] = &hex::decode(s).unwrap();
-                let i = u32::from_be_bytes(bytes.try_into().unwrap());
+                let i = match u32::from_str_radix(s, 16) {
+                    Ok(i) if i <= 0x10_FFFF => i,
+                    _ => {
+                        return Err(input.error(
+                            "Escape sequences can't be above U+10FFFF"
+                                .to_string(),
+                        ))
+                    }
+                };
                 match i {
                     0xD800..=0xDFFF => {
                         return Err(input.error(
@@ -396,30 +451,51 @@ impl DhallParser {
 
     fn natural_literal(input: ParseInput) -> ParseResult<Natural> {
         let s = input.as_str().trim();
-        if s.starts_with("0x") {
+        let n = if s.starts_with("0x") {
             let without_prefix = s.trim_start_matches("0x");
             usize::from_str_radix(without_prefix, 16)
-                .map_err(|e| input.error(format!("{}", e)))
         } else {
-            s.parse().map_err(|e| input.error(format!("{}", e)))
-        }
+            s.parse()
+        };
+        // The grammar only lets digits through, so this can only fail on overflow.
+        n.map_err(|_| {
+            input.error(format!(
+                "Natural literal too large for this implementation, max is {}",
+                Natural::MAX
+            ))
+        })
     }
 
     fn integer_literal(input: ParseInput) -> ParseResult<Integer> {
         let s = input.as_str().trim();
         let (sign, rest) = (&s[0..1], &s[1..]);
-        if rest.starts_with("0x") {
+        let n = if rest.starts_with("0x") {
             let without_prefix =
                 sign.to_owned() + rest.trim_start_matches("0x");
             isize::from_str_radix(&without_prefix, 16)
-                .map_err(|e| input.error(format!("{}", e)))
         } else {
-            s.parse().map_err(|e| input.error(format!("{}", e)))
-        }
+            s.parse()
+        };
+        n.map_err(|_| {
+            input.error(if sign == "-" {
+                format!(
+                    "Integer literal too small for this implementation, min is {}",
+                    Integer::MIN
+                )
+            } else {
+                format!(
+                    "Integer literal too large for this implementation, max is +{}",
+                    Integer::MAX
+                )
+            })
+        })
     }
 
-    #[alias(expression, shortcut = true)]
+    #[alias(expression)]
     fn identifier(input: ParseInput) -> ParseResult<Expr> {
+        if let Some(child) = skip_single_children(&input) {
+            return Self::expression(child);
+        }
         Ok(match_nodes!(input.children();
             [variable(v)] => spanned(input, Var(v)),
             [expression(e)] => e,
@@ -439,30 +515,7 @@ impl DhallParser {
     }
     #[alias(path_component)]
     fn quoted_path_component(input: ParseInput) -> ParseResult<String> {
-        #[rustfmt::skip]
-        const RESERVED: &percent_encoding::AsciiSet =
-            &percent_encoding::CONTROLS
-            .add(b'=').add(b':').add(b'/').add(b'?')
-            .add(b'#').add(b'[').add(b']').add(b'@')
-            .add(b'!').add(b'$').add(b'&').add(b'\'')
-            .add(b'(').add(b')').add(b'*').add(b'+')
-            .add(b',').add(b';');
-        Ok(input
-            .as_str()
-            .chars()
-            .map(|c| {
-                // Percent-encode ascii chars
-                if c.is_ascii() {
-                    percent_encoding::utf8_percent_encode(
-                        &c.to_string(),
-                        RESERVED,
-                    )
-                    .to_string()
-                } else {
-                    c.to_string()
-                }
-            })
-            .collect())
+        Ok(input.as_str().to_string())
     }
     fn path(input: ParseInput) -> ParseResult<FilePath> {
         Ok(match_nodes!(input.into_children();
@@ -532,16 +585,21 @@ impl DhallParser {
     }
 
     fn url_path(input: ParseInput) -> ParseResult<FilePath> {
-        Ok(match_nodes!(input.into_children();
-            [path_component(components)..] => {
-                let mut file_path: Vec<_> = components.collect();
-                // An empty path normalizes to "/"
-                if file_path.is_empty() {
-                    file_path = vec!["".to_owned()];
+        let mut file_path = input
+            .into_children()
+            .map(|c| match c.as_rule() {
+                // Quoted components are literal text and must be encoded to be valid in a URL.
+                Rule::quoted_path_component => {
+                    percent_encode_path_component(c.as_str())
                 }
-                FilePath { file_path }
-            }
-        ))
+                _ => c.as_str().to_owned(),
+            })
+            .collect::<Vec<_>>();
+        // An empty path normalizes to "/"
+        if file_path.is_empty() {
+            file_path = vec!["".to_owned()];
+        }
+        Ok(FilePath { file_path })
     }
 
     fn authority(input: ParseInput) -> ParseResult<String> {
@@ -681,48 +739,11 @@ impl DhallParser {
     }
 
     fn expression(input: ParseInput) -> ParseResult<Expr> {
-        Ok(match_nodes!(input.children();
-            [lambda(()), label(l), expression(typ),
-                    arrow(()), expression(body)] => {
-                spanned(input, Lam(l, typ, body))
-            },
-            [if_(()), expression(cond), expression(left),
-                    expression(right)] => {
-                spanned(input, Op(BoolIf(cond, left, right)))
-            },
-            [let_binding(bindings).., expression(final_expr)] => {
-                bindings.rev().fold(
-                    final_expr,
-                    |acc, x| {
-                        spanned_union(
-                            acc.span(),
-                            x.3,
-                            Let(x.0, x.1, x.2, acc)
-                        )
-                    }
-                )
-            },
-            [forall(()), label(l), expression(typ),
-                    arrow(()), expression(body)] => {
-                spanned(input, Pi(l, typ, body))
-            },
-            [expression(typ), arrow(()), expression(body)] => {
-                spanned(input, Pi("_".into(), typ, body))
-            },
-            [merge(()), expression(x), expression(y), expression(z)] => {
-                spanned(input, Op(Merge(x, y, Some(z))))
-            },
-            [assert(()), expression(x)] => {
-                spanned(input, Assert(x))
-            },
-            [toMap(()), expression(x), expression(y)] => {
-                spanned(input, Op(ToMap(x, Some(y))))
-            },
-            [expression(e), expression(annot)] => {
-                spanned(input, Annot(e, annot))
-            },
-            [expression(e)] => e,
-        ))
+        // Each nested expression recurses, and they can be nested arbitrarily deeply.
+        ensure_stack(|| match skip_single_children(&input) {
+            Some(child) => Self::expression(child),
+            None => parse_expression(input),
+        })
     }
 
     fn let_binding(
@@ -736,7 +757,7 @@ impl DhallParser {
         ))
     }
 
-    #[alias(expression, shortcut = true)]
+    #[alias(expression)]
     #[prec_climb(expression, PRECCLIMBER)]
     fn operator_expression(
         l: Expr,
@@ -771,15 +792,22 @@ impl DhallParser {
         Ok(())
     }
 
-    #[alias(expression, shortcut = true)]
+    #[alias(expression)]
     fn with_expression(input: ParseInput) -> ParseResult<Expr> {
+        if let Some(child) = skip_single_children(&input) {
+            return Self::expression(child);
+        }
         Ok(match_nodes!(input.children();
             [expression(e)] => e,
             [expression(first), with_clause(clauses)..] => {
                 clauses.fold(
                     first,
                     |acc, (labels, e)| {
-                        desugar_with_expr(acc, &labels, e)
+                        spanned_union(
+                            acc.span(),
+                            e.span(),
+                            Op(With(acc, labels, e)),
+                        )
                     }
                 )
             },
@@ -792,8 +820,11 @@ impl DhallParser {
         ))
     }
 
-    #[alias(expression, shortcut = true)]
+    #[alias(expression)]
     fn application_expression(input: ParseInput) -> ParseResult<Expr> {
+        if let Some(child) = skip_single_children(&input) {
+            return Self::expression(child);
+        }
         Ok(match_nodes!(input.children();
             [expression(e)] => e,
             [expression(first), expression(rest)..] => {
@@ -811,8 +842,11 @@ impl DhallParser {
         ))
     }
 
-    #[alias(expression, shortcut = true)]
+    #[alias(expression)]
     fn first_application_expression(input: ParseInput) -> ParseResult<Expr> {
+        if let Some(child) = skip_single_children(&input) {
+            return Self::expression(child);
+        }
         Ok(match_nodes!(input.children();
             [Some_(()), expression(e)] => {
                 spanned(input, SomeLit(e))
@@ -827,8 +861,11 @@ impl DhallParser {
         ))
     }
 
-    #[alias(expression, shortcut = true)]
+    #[alias(expression)]
     fn completion_expression(input: ParseInput) -> ParseResult<Expr> {
+        if let Some(child) = skip_single_children(&input) {
+            return Self::expression(child);
+        }
         Ok(match_nodes!(input.children();
             [expression(e)] => e,
             [expression(first), expression(rest)..] => {
@@ -846,8 +883,11 @@ impl DhallParser {
         ))
     }
 
-    #[alias(expression, shortcut = true)]
+    #[alias(expression)]
     fn selector_expression(input: ParseInput) -> ParseResult<Expr> {
+        if let Some(child) = skip_single_children(&input) {
+            return Self::expression(child);
+        }
         Ok(match_nodes!(input.children();
             [expression(e)] => e,
             [expression(first), selector(rest)..] => {
@@ -895,8 +935,11 @@ impl DhallParser {
         ))
     }
 
-    #[alias(expression, shortcut = true)]
+    #[alias(expression)]
     fn primitive_expression(input: ParseInput) -> ParseResult<Expr> {
+        if let Some(child) = skip_single_children(&input) {
+            return Self::expression(child);
+        }
         Ok(match_nodes!(input.children();
             [double_literal(n)] => spanned(input, Num(Double(n))),
             [natural_literal(n)] => spanned(input, Num(Natural(n))),
@@ -905,7 +948,7 @@ impl DhallParser {
             [single_quote_literal(s)] => spanned(input, TextLit(s)),
             [record_type_or_literal(e)] => spanned(input, e),
             [union_type(e)] => spanned(input, e),
-            [expression(e)] => e,
+            [expression(e)] => e.with_span(input_to_span(input)),
         ))
     }
 
@@ -932,26 +975,12 @@ impl DhallParser {
     fn non_empty_record_type(
         input: ParseInput,
     ) -> ParseResult<BTreeMap<Label, Expr>> {
-        Ok(match_nodes!(input.children();
-            [record_type_entry(entries)..] => {
-                let mut map = BTreeMap::default();
-                for (l, t) in entries {
-                    use std::collections::btree_map::Entry;
-                    match map.entry(l) {
-                        Entry::Occupied(_) => {
-                            return Err(input.error(
-                                "Duplicate field in record type"
-                                    .to_string(),
-                            ));
-                        }
-                        Entry::Vacant(e) => {
-                            e.insert(t);
-                        }
-                    }
-                }
-                map
-            },
-        ))
+        collect_unique_entries(
+            input.into_children(),
+            "field",
+            "record type",
+            Self::record_type_entry,
+        )
     }
 
     fn record_type_entry(input: ParseInput) -> ParseResult<(Label, Expr)> {
@@ -997,27 +1026,15 @@ impl DhallParser {
     }
 
     fn union_type(input: ParseInput) -> ParseResult<UnspannedExpr> {
-        let map = match_nodes!(input.children();
-            [empty_union_type(_)] => Default::default(),
-            [union_type_entry(entries)..] => {
-                let mut map = BTreeMap::default();
-                for (l, t) in entries {
-                    use std::collections::btree_map::Entry;
-                    match map.entry(l) {
-                        Entry::Occupied(_) => {
-                            return Err(input.error(
-                                "Duplicate variant in union type"
-                                    .to_string(),
-                            ));
-                        }
-                        Entry::Vacant(e) => {
-                            e.insert(t);
-                        }
-                    }
-                }
-                map
-            },
-        );
+        let entries = input
+            .into_children()
+            .filter(|c| c.as_rule() != Rule::empty_union_type);
+        let map = collect_unique_entries(
+            entries,
+            "variant",
+            "union type",
+            Self::union_type_entry,
+        )?;
         Ok(UnionType(map))
     }
 
@@ -1052,14 +1069,438 @@ impl DhallParser {
     }
 }
 
-pub fn parse_expr(input_str: &str) -> ParseResult<Expr> {
-    let rc_input_str = input_str.to_string().into();
-    let inputs = DhallParser::parse_with_userdata(
-        Rule::final_expression,
-        input_str,
-        rc_input_str,
-    )?;
-    Ok(match_nodes!(<DhallParser>; inputs;
+/// The body of `DhallParser::expression`, kept out of it so that the recursion through
+/// `DhallParser::expression` uses little stack.
+fn parse_expression(input: ParseInput) -> ParseResult<Expr> {
+    Ok(match_nodes!(<DhallParser>; input.children();
+        [lambda(()), label(l), expression(typ),
+                arrow(()), expression(body)] => {
+            spanned(input, Lam(l, typ, body))
+        },
+        [if_(()), expression(cond), expression(left),
+                expression(right)] => {
+            spanned(input, Op(BoolIf(cond, left, right)))
+        },
+        [let_binding(bindings).., expression(final_expr)] => {
+            bindings.rev().fold(
+                final_expr,
+                |acc, x| {
+                    spanned_union(
+                        acc.span(),
+                        x.3,
+                        Let(x.0, x.1, x.2, acc)
+                    )
+                }
+            )
+        },
+        [forall(()), label(l), expression(typ),
+                arrow(()), expression(body)] => {
+            spanned(input, Pi(l, typ, body))
+        },
+        [expression(typ), arrow(()), expression(body)] => {
+            spanned(input, Pi("_".into(), typ, body))
+        },
+        [merge(()), expression(x), expression(y), expression(z)] => {
+            spanned(input, Op(Merge(x, y, Some(z))))
+        },
+        [assert(()), expression(x)] => {
+            spanned(input, Assert(x))
+        },
+        [toMap(()), expression(x), expression(y)] => {
+            spanned(input, Op(ToMap(x, Some(y))))
+        },
+        [expression(e), expression(annot)] => {
+            spanned(input, Annot(e, annot))
+        },
         [expression(e)] => e,
     ))
 }
+
+/// Parses an expression, also returning its concrete syntax tree.
+pub fn parse_expr_lossless(
+    input_str: &str,
+) -> Result<(Expr, SyntaxTree), ParseError> {
+    let rc_input_str: Rc<str> = input_str.to_string().into();
+    let parse = || -> ParseResult<_> {
+        let depth = check_nesting(input_str, Some(DEFAULT_MAX_NESTING_DEPTH))?;
+        let inputs = parse_nested(
+            Rule::final_expression,
+            input_str,
+            &rc_input_str,
+            depth,
+        )?;
+        let root = inputs.as_pairs().clone().next().unwrap();
+        let tree = SyntaxTree::new(rc_input_str.clone(), root);
+        let expr = match_nodes!(<DhallParser>; inputs;
+            [expression(e)] => e,
+        );
+        Ok((expr, tree))
+    };
+    parse().map_err(|err| make_parse_error(&rc_input_str, err))
+}
+
+/// Parses only the concrete syntax tree of a source text.
+pub(crate) fn parse_syntax_tree(
+    input: &Rc<str>,
+) -> Result<SyntaxTree, ParseError> {
+    let parse = || -> ParseResult<_> {
+        let depth = check_nesting(input, Some(DEFAULT_MAX_NESTING_DEPTH))?;
+        let inputs = parse_nested(Rule::final_expression, input, input, depth)?;
+        let root = inputs.as_pairs().clone().next().unwrap();
+        Ok(SyntaxTree::new(input.clone(), root))
+    };
+    parse().map_err(|err| make_parse_error(input, err))
+}
+
+/// Parses `rule` at byte offset `start` of `input`, succeeding only if it stops exactly at `end`.
+/// The positions of the returned pair are relative to `start`.
+pub(crate) fn parse_rule_at(
+    rule: Rule,
+    input: &Rc<str>,
+    start: usize,
+    end: usize,
+) -> Option<pest::iterators::Pair<'_, Rule>> {
+    let depth =
+        check_nesting(&input[start..end], Some(DEFAULT_MAX_NESTING_DEPTH))
+            .ok()?;
+    let inputs = parse_nested(rule, &input[start..], input, depth).ok()?;
+    let pair = inputs.as_pairs().clone().next()?;
+    if start + pair.as_span().end() == end {
+        Some(pair)
+    } else {
+        None
+    }
+}
+
+pub fn parse_expr(input_str: &str) -> Result<Expr, ParseError> {
+    parse_expr_with_nesting_limit(input_str, Some(DEFAULT_MAX_NESTING_DEPTH))
+}
+
+/// Like `parse_expr`, but allows expressions to nest `max_depth` levels deep instead of
+/// `DEFAULT_MAX_NESTING_DEPTH`. Pass `None` to remove the limit, e.g. for trusted input; the
+/// parser then uses as much stack as the input needs.
+///
+/// ```
+/// use dhall::syntax::parse_expr_with_nesting_limit;
+///
+/// let src = format!("{}1{}", "[ ".repeat(50), " ]".repeat(50));
+/// let err = parse_expr_with_nesting_limit(&src, Some(20)).unwrap_err();
+/// assert!(err.to_string().contains("too deeply nested"));
+/// assert!(parse_expr_with_nesting_limit(&src, None).is_ok());
+/// ```
+pub fn parse_expr_with_nesting_limit(
+    input_str: &str,
+    max_depth: Option<usize>,
+) -> Result<Expr, ParseError> {
+    let rc_input_str: Rc<str> = input_str.to_string().into();
+    parse_expr_as(input_str, &rc_input_str, max_depth).map_err(|(err, _)| err)
+}
+
+/// Parses an expression read from `reader`.
+///
+/// The source is read into a single buffer, which the spans of the expression then share; unlike
+/// `parse_expr`, the text isn't copied again. This doesn't parse incrementally, and so needs as
+/// much memory as reading the source into a `String` first: the pest grammar takes the whole
+/// input as one `&str` since it may backtrack arbitrarily far, and spans point into the source
+/// text.
+pub fn parse_expr_from_reader(mut reader: impl Read) -> Result<Expr, Error> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+    let input: Rc<str> = text.into();
+    Ok(
+        parse_expr_as(&input, &input, Some(DEFAULT_MAX_NESTING_DEPTH))
+            .map_err(|(err, _)| err)?,
+    )
+}
+
+/// Parses `text` but attributes spans and errors to `input`, which must have the same length. On
+/// failure, also returns the byte offset where parsing failed.
+pub(crate) fn parse_expr_as(
+    text: &str,
+    input: &Rc<str>,
+    max_depth: Option<usize>,
+) -> Result<Expr, (ParseError, usize)> {
+    let parse = || -> ParseResult<_> {
+        let depth = check_nesting(text, max_depth)?;
+        let inputs = parse_nested(Rule::final_expression, text, input, depth)?;
+        Ok(match_nodes!(<DhallParser>; inputs;
+            [expression(e)] => e,
+        ))
+    };
+    parse().map_err(|err| {
+        let pos = match err.location {
+            pest::error::InputLocation::Pos(pos) => pos,
+            pest::error::InputLocation::Span((start, _)) => start,
+        };
+        (make_parse_error(input, err), pos)
+    })
+}
+
+/// Stack used by the grammar for each level of nesting. This is generous: debug builds use several
+/// times more stack than release builds, and brackets more than the other kinds of nesting.
+const STACK_PER_NESTING_LEVEL: usize = 16 * 1024;
+
+/// Returns how deeply `text` nests, or fails if that is deeper than `max_depth`.
+fn check_nesting(text: &str, max_depth: Option<usize>) -> ParseResult<usize> {
+    let max_depth = max_depth.unwrap_or(usize::MAX);
+    match nesting_depth(text, max_depth) {
+        Ok(depth) => Ok(depth),
+        Err((start, end)) => Err(PestError::new_from_span(
+            pest::error::ErrorVariant::CustomError {
+                message: format!(
+                    "Expression too deeply nested: more than {} levels",
+                    max_depth
+                ),
+            },
+            pest::Span::new(text, start, end).unwrap(),
+        )),
+    }
+}
+
+/// The error for an edit of `input` whose `range` can't be applied, pointing at the closest
+/// position to the start of the range.
+pub(crate) fn edit_range_error(
+    input: &Rc<str>,
+    range: &std::ops::Range<usize>,
+    problem: &str,
+) -> ParseError {
+    let mut pos = range.start.min(input.len());
+    while !input.is_char_boundary(pos) {
+        pos -= 1;
+    }
+    let err = PestError::new_from_span(
+        pest::error::ErrorVariant::CustomError {
+            message: format!(
+                "Invalid edit range {}..{}: {}",
+                range.start, range.end, problem
+            ),
+        },
+        pest::Span::new(input, pos, pos).unwrap(),
+    );
+    make_parse_error(input, err)
+}
+
+/// Runs the grammar from `rule` on `text`, with enough stack for the `depth` that
+/// `check_nesting` found.
+fn parse_nested<'i>(
+    rule: Rule,
+    text: &'i str,
+    input: &Rc<str>,
+    depth: usize,
+) -> ParseResult<pest_consume::Nodes<'i, Rule, Rc<str>>> {
+    with_stack((depth + 1) * STACK_PER_NESTING_LEVEL, || {
+        DhallParser::parse_with_userdata(rule, text, input.clone())
+    })
+}
+
+/// Turns a pest error into a diagnostic pointing at the offending input, naming what was
+/// expected there in terms a user would recognize rather than grammar rules.
+fn make_parse_error(input: &Rc<str>, err: PestError) -> ParseError {
+    use pest::error::{ErrorVariant, InputLocation};
+    let (start, end) = match err.location {
+        // Point at the first unexpected character.
+        InputLocation::Pos(pos) => {
+            let len = input[pos..].chars().next().map_or(0, char::len_utf8);
+            (pos, pos + len)
+        }
+        InputLocation::Span(span) => span,
+    };
+    let span = ParsedSpan::make(
+        input.clone(),
+        pest::Span::new(input, start, end).unwrap(),
+    );
+
+    let (title, label, expected) = match err.variant {
+        ErrorVariant::ParsingError {
+            positives,
+            negatives,
+        } => {
+            let found = match input[start..end].chars().next() {
+                None => "unexpected end of input".to_owned(),
+                Some('\n') => "unexpected end of line".to_owned(),
+                Some(c) => format!("unexpected `{}`", c),
+            };
+            let expected = describe_rules(&positives);
+            let title = if !expected.is_empty() {
+                format!("expected {}", list_alternatives(&expected))
+            } else if !negatives.is_empty() {
+                format!(
+                    "unexpected {}",
+                    list_alternatives(&describe_rules(&negatives))
+                )
+            } else {
+                "syntax error".to_owned()
+            };
+            (title, found, expected)
+        }
+        // Recent versions of pest check that there is enough stack left before recursing.
+        ErrorVariant::CustomError { message }
+            if message == "stack limit reached" =>
+        {
+            let title = "Expression too deeply nested".to_owned();
+            (
+                title,
+                "the parser ran out of stack here".to_owned(),
+                Vec::new(),
+            )
+        }
+        ErrorVariant::CustomError { message } => {
+            (message.clone(), message, Vec::new())
+        }
+    };
+    let diagnostic = ErrorBuilder::new(title)
+        .span_err(Span::Parsed(span.clone()), label)
+        .format();
+    ParseError::new(expected, diagnostic, &span)
+}
+
+/// Describes each rule, removing duplicates. When an expression is expected, the kinds of
+/// expressions the parser tried are not listed separately.
+fn describe_rules(rules: &[Rule]) -> Vec<String> {
+    let expects_expr = rules.iter().any(|&rule| is_expression(rule));
+    rules
+        .iter()
+        .filter(|&&rule| !(expects_expr && starts_expression(rule)))
+        .map(|&rule| describe_rule(rule))
+        .unique()
+        .collect()
+}
+
+fn is_expression(rule: Rule) -> bool {
+    use Rule::*;
+    matches!(
+        rule,
+        complete_expression
+            | expression
+            | operator_expression
+            | import_alt_expression
+            | or_expression
+            | plus_expression
+            | text_append_expression
+            | list_append_expression
+            | and_expression
+            | combine_expression
+            | prefer_expression
+            | combine_types_expression
+            | times_expression
+            | equal_expression
+            | not_equal_expression
+            | equivalent_expression
+            | with_expression
+            | application_expression
+            | first_application_expression
+            | import_expression
+            | completion_expression
+            | selector_expression
+            | primitive_expression
+            | import_hashed
+    )
+}
+
+/// Rules that match the start of some kind of expression.
+fn starts_expression(rule: Rule) -> bool {
+    use Rule::*;
+    matches!(
+        rule,
+        natural_literal
+            | integer_literal
+            | double_literal
+            | numeric_double_literal
+            | minus_infinity_literal
+            | plus_infinity_literal
+            | text_literal
+            | double_quote_literal
+            | single_quote_literal
+            | empty_list_literal
+            | non_empty_list_literal
+            | record_type_or_literal
+            | empty_record_literal
+            | empty_record_type
+            | non_empty_record_type_or_literal
+            | union_type
+            | builtin
+            | import
+            | import_type
+            | local
+            | http
+            | env
+            | missing
+            | variable
+            | identifier
+            | if_
+            | merge
+            | Some_
+            | toMap
+            | assert
+            | NaN
+            | Infinity
+            | lambda
+            | forall
+    )
+}
+
+/// What a rule matches, as shown in "expected ..." messages.
+fn describe_rule(rule: Rule) -> String {
+    use Rule::*;
+    let s = match rule {
+        _ if is_expression(rule) => "an expression",
+        EOI => "end of input",
+        import_alt | bool_or | natural_plus | text_append | list_append
+        | bool_and | natural_times | bool_eq | bool_ne | combine
+        | combine_types | equivalent | prefer => "an operator",
+        arrow => "`→`",
+        lambda => "`λ`",
+        forall => "`∀`",
+        simple_label | quoted_label | label | any_label | any_label_or_some
+        | nonreserved_label => "a label",
+        variable | identifier => "a variable",
+        natural_literal => "a natural number",
+        integer_literal => "an integer",
+        double_literal
+        | numeric_double_literal
+        | minus_infinity_literal
+        | plus_infinity_literal => "a double",
+        double_quote_literal | single_quote_literal | text_literal => {
+            "a text literal"
+        }
+        double_quote_chunk
+        | double_quote_char
+        | double_quote_escaped
+        | single_quote_char
+        | single_quote_continue
+        | escaped_quote_pair
+        | escaped_interpolation => "text",
+        empty_list_literal | non_empty_list_literal => "a list",
+        record_type_or_literal | non_empty_record_type_or_literal => "a record",
+        union_type => "a union type",
+        builtin => "a builtin",
+        hash => "an integrity hash",
+        import | import_type | local | http | http_raw | env => "an import",
+        record_type_entry | record_literal_entry => "a record field",
+        union_type_entry => "a union alternative",
+        empty_record_literal => "`{=}`",
+        empty_record_type => "`{}`",
+        selector => "a field selection",
+        let_binding => "a `let` binding",
+        with_clause => "a `with` update",
+        // Keywords are named after themselves, e.g. `Some_` is `Some`.
+        if_ | using | merge | missing | Infinity | NaN | Some_ | toMap
+        | assert | Text | Location => {
+            return format!(
+                "`{}`",
+                format!("{:?}", rule).trim_end_matches('_')
+            );
+        }
+        _ => return format!("{:?}", rule).replace('_', " "),
+    };
+    s.to_owned()
+}
+
+/// Formats a list as "a, b or c".
+fn list_alternatives(items: &[String]) -> String {
+    match items.split_last() {
+        None => String::new(),
+        Some((last, [])) => last.clone(),
+        Some((last, init)) => format!("{} or {}", init.join(", "), last),
+    }
+}
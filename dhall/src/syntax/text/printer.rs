@@ -1,5 +1,6 @@
 use crate::builtins::Builtin;
 use crate::operations::{BinOp, OpKind};
+use crate::shared::ensure_stack;
 use crate::syntax::*;
 use itertools::Itertools;
 use std::fmt::{self, Display};
@@ -15,8 +16,11 @@ enum PrintPhase {
     Base,
     // `operator-expression`
     Operator,
-    // All the `<operator>-expression`s
-    BinOp(self::BinOp),
+    // All the `<operator>-expression`s. Operators associate to the left, so the flag is set for
+    // the right operand, where the same operator needs parentheses.
+    BinOp(self::BinOp, bool),
+    // `with-expression`
+    With,
     // `application-expression`
     App,
     // `import-expression`
@@ -63,8 +67,8 @@ impl UnspannedExpr {
             Annot(a, b) => Annot(a.phase(Operator), b),
             Op(OpKind::BinOp(op, a, b)) => Op(OpKind::BinOp(
                 op,
-                a.phase(PrintPhase::BinOp(op)),
-                b.phase(PrintPhase::BinOp(op)),
+                a.phase(PrintPhase::BinOp(op, false)),
+                b.phase(PrintPhase::BinOp(op, true)),
             )),
             SomeLit(e) => SomeLit(e.phase(PrintPhase::Import)),
             Op(OpKind::App(f, a)) => Op(OpKind::App(
@@ -79,6 +83,11 @@ impl UnspannedExpr {
             Op(Completion(a, b)) => {
                 Op(Completion(a.phase(Primitive), b.phase(Primitive)))
             }
+            Op(OpKind::With(a, ls, b)) => Op(OpKind::With(
+                a.phase(PrintPhase::With),
+                ls,
+                b.phase(PrintPhase::App),
+            )),
             ExprKind::Import(a) => {
                 ExprKind::Import(a.map_ref(|x| x.phase(PrintPhase::Import)))
             }
@@ -91,10 +100,27 @@ impl UnspannedExpr {
         f: &mut fmt::Formatter,
         phase: PrintPhase,
     ) -> Result<(), fmt::Error> {
+        // Printing recurses once per level of the expression, which can be arbitrarily deep.
+        ensure_stack(|| {
+            let needs_paren = self.needs_paren(phase);
+            if needs_paren {
+                f.write_str("(")?;
+            }
+            self.annotate_with_phases().fmt(f)?;
+            if needs_paren {
+                f.write_str(")")?;
+            }
+
+            Ok(())
+        })
+    }
+
+    // Whether the expression must be parenthesized when printed at the given phase.
+    fn needs_paren(&self, phase: PrintPhase) -> bool {
         use ExprKind::*;
         use OpKind::*;
 
-        let needs_paren = match self {
+        match self {
             Lam(_, _, _)
             | Pi(_, _, _)
             | Let(_, _, _, _)
@@ -106,22 +132,256 @@ impl UnspannedExpr {
             | Annot(_, _) => phase > PrintPhase::Base,
             // Precedence is magically handled by the ordering of BinOps. This is reverse Pratt
             // parsing.
-            Op(BinOp(op, _, _)) => phase > PrintPhase::BinOp(*op),
+            Op(BinOp(op, _, _)) => phase > PrintPhase::BinOp(*op, false),
+            Op(With(_, _, _)) => phase > PrintPhase::With,
             Op(App(_, _)) => phase > PrintPhase::App,
             Op(Completion(_, _)) => phase > PrintPhase::Import,
             _ => false,
+        }
+    }
+}
+
+/// An expression printed with elisions, as returned by `Expr::elide`.
+#[derive(Copy, Clone)]
+pub struct Elided<'a> {
+    expr: PhasedExpr<'a>,
+    depth: usize,
+    width: usize,
+}
+
+impl Expr {
+    /// Prints the expression with `…` in place of the subexpressions nested more than `depth`
+    /// levels deep, and of the entries of records, unions and lists beyond the first `width`.
+    /// This keeps huge types readable in error messages.
+    ///
+    /// ```
+    /// use dhall::syntax::parse_expr;
+    ///
+    /// let ty = parse_expr("{ a : Bool, b : { c : List Natural }, d : Text }").unwrap();
+    /// assert_eq!(ty.elide(2, 2).to_string(), "{ a : Bool, b : { c : … }, … }");
+    /// ```
+    pub fn elide(&self, depth: usize, width: usize) -> Elided<'_> {
+        Elided {
+            expr: PhasedExpr(self, PrintPhase::Base),
+            depth,
+            width: width.max(1),
+        }
+    }
+}
+
+impl<'a> Display for Elided<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        ensure_stack(|| self.fmt_layer(f))
+    }
+}
+
+impl<'a> Elided<'a> {
+    fn fmt_layer(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        use ExprKind::*;
+        let PhasedExpr(expr, phase) = self.expr;
+        let kind = expr.as_ref();
+        let mut is_leaf = true;
+        kind.map_ref(|_| is_leaf = false);
+        if self.depth == 0 && !is_leaf {
+            return f.write_str("…");
+        }
+
+        let width = self.width;
+        let kind = kind.annotate_with_phases().map_ref(|e| Elided {
+            expr: *e,
+            depth: self.depth - 1,
+            width,
+        });
+        let needs_paren = expr.as_ref().needs_paren(phase);
+        if needs_paren {
+            f.write_str("(")?;
+        }
+        match &kind {
+            NEListLit(es) if es.len() > width => {
+                fmt_list("[", ", ", ", … ]", &es[..width], f, Display::fmt)?
+            }
+            RecordLit(a) if a.len() > width => fmt_list(
+                "{ ",
+                ", ",
+                ", … }",
+                a.iter().take(width),
+                f,
+                |(k, v), f| {
+                    fmt_label(k, f)?;
+                    write!(f, " = {}", v)
+                },
+            )?,
+            RecordType(a) if a.len() > width => fmt_list(
+                "{ ",
+                ", ",
+                ", … }",
+                a.iter().take(width),
+                f,
+                |(k, t), f| {
+                    fmt_label(k, f)?;
+                    write!(f, " : {}", t)
+                },
+            )?,
+            UnionType(a) if a.len() > width => fmt_list(
+                "< ",
+                " | ",
+                " | … >",
+                a.iter().take(width),
+                f,
+                |(k, v), f| {
+                    fmt_label(k, f)?;
+                    if let Some(v) = v {
+                        write!(f, ": {}", v)?;
+                    }
+                    Ok(())
+                },
+            )?,
+            _ => kind.fmt(f)?,
+        }
+        if needs_paren {
+            f.write_str(")")?;
+        }
+        Ok(())
+    }
+}
+
+/// An expression printed so as to keep the source text of its unchanged parts, as returned by
+/// `Expr::print_preserving`.
+#[derive(Copy, Clone)]
+pub struct Preserving<'a> {
+    expr: PhasedExpr<'a>,
+    original: Option<&'a Expr>,
+    /// Whether this is the whole expression, so that the whitespace and comments around it in
+    /// the source are kept too.
+    is_root: bool,
+}
+
+impl Expr {
+    /// Prints the expression, copying the source text of `original` for the subexpressions that
+    /// are the same in both. `original` is meant to be a freshly parsed expression, and `self` a
+    /// rewrite of it: the untouched parts keep their layout and comments, and only the rewritten
+    /// ones are printed anew. If nothing was changed, this gives back the source byte for byte.
+    ///
+    /// ```
+    /// use dhall::syntax::parse_expr;
+    ///
+    /// let src = "-- settings\n{ port = 80, hosts = [ \"a\",   \"b\" ] }\n";
+    /// let parsed = parse_expr(src).unwrap();
+    /// assert_eq!(parsed.print_preserving(&parsed).to_string(), src);
+    ///
+    /// let edited = parse_expr("{ port = 8080, hosts = [ \"a\", \"b\" ] }").unwrap();
+    /// assert_eq!(
+    ///     edited.print_preserving(&parsed).to_string(),
+    ///     "-- settings\n{ hosts = [ \"a\",   \"b\" ], port = 8080 }\n",
+    /// );
+    /// ```
+    pub fn print_preserving<'a>(
+        &'a self,
+        original: &'a Expr,
+    ) -> Preserving<'a> {
+        Preserving {
+            expr: PhasedExpr(self, PrintPhase::Base),
+            original: Some(original),
+            is_root: true,
+        }
+    }
+}
+
+impl<'a> Display for Preserving<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        ensure_stack(|| self.fmt_layer(f))
+    }
+}
+
+impl<'a> Preserving<'a> {
+    fn fmt_layer(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let PhasedExpr(expr, phase) = self.expr;
+        let original = match self.original {
+            Some(original) => original,
+            None => return self.expr.fmt(f),
+        };
+        let span = match original.span() {
+            Span::Parsed(span) => Some(span),
+            _ => None,
+        };
+        let (before, after) = match &span {
+            Some(span) if self.is_root => {
+                let (start, end) = span.byte_range();
+                (&span.input()[..start], &span.input()[end..])
+            }
+            _ => ("", ""),
         };
 
+        f.write_str(before)?;
+        let verbatim = match &span {
+            Some(span) if expr == original => Some(span),
+            _ => None,
+        };
+        // Parentheses in the source are copied along with the rest.
+        let needs_paren = expr.as_ref().needs_paren(phase)
+            && !verbatim.is_some_and(|span| is_parenthesized(original, span));
         if needs_paren {
             f.write_str("(")?;
         }
-        self.annotate_with_phases().fmt(f)?;
+        match verbatim {
+            Some(span) => f.write_str(span.as_str())?,
+            None => {
+                let mut originals =
+                    matching_children(expr, original).into_iter();
+                expr.as_ref()
+                    .annotate_with_phases()
+                    .map_ref(|e| Preserving {
+                        expr: *e,
+                        original: originals.next().flatten(),
+                        is_root: false,
+                    })
+                    .fmt(f)?
+            }
+        }
         if needs_paren {
             f.write_str(")")?;
         }
+        f.write_str(after)
+    }
+}
 
-        Ok(())
+/// Whether the source text of the expression is wrapped in parentheses. Only parenthesized
+/// expressions start with one, apart from those whose first subexpression does.
+fn is_parenthesized(expr: &Expr, span: &ParsedSpan) -> bool {
+    let start = span.byte_range().0;
+    let mut child_at_start = false;
+    expr.kind().map_ref(|e| {
+        if let Span::Parsed(sp) = e.span() {
+            child_at_start |= sp.byte_range().0 == start;
+        }
+    });
+    span.as_str().starts_with('(') && !child_at_start
+}
+
+/// Pairs the subexpressions of `expr`, in the order of `map_ref`, with the subexpressions of
+/// `original` they were rewritten from. Entries of records and unions are paired by label, so
+/// that adding or removing one keeps the others; otherwise both nodes must have the same shape.
+fn matching_children<'a>(
+    expr: &Expr,
+    original: &'a Expr,
+) -> Vec<Option<&'a Expr>> {
+    use ExprKind::*;
+    let mut children = Vec::new();
+    match (expr.kind(), original.kind()) {
+        (RecordLit(a), RecordLit(b)) | (RecordType(a), RecordType(b)) => {
+            children.extend(a.keys().map(|k| b.get(k)))
+        }
+        (UnionType(a), UnionType(b)) => children.extend(
+            a.iter()
+                .filter(|(_, v)| v.is_some())
+                .map(|(k, _)| b.get(k).and_then(Option::as_ref)),
+        ),
+        (a, b) if a.map_ref(|_| ()) == b.map_ref(|_| ()) => {
+            b.map_ref(|e| children.push(Some(e)));
+        }
+        _ => {}
     }
+    children
 }
 
 fn fmt_list<T, I, F>(
@@ -150,11 +410,21 @@ fn fmt_label(label: &Label, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
     // TODO: distinguish between reserved and nonreserved locations for quoting builtins
     let s = String::from(label);
     let is_reserved = match s.as_str() {
-        "let" | "in" | "if" | "then" | "else" | "Type" | "Kind" | "Sort"
-        | "True" | "False" | "Some" => true,
+        "if" | "then" | "else" | "let" | "in" | "using" | "missing"
+        | "assert" | "as" | "Infinity" | "NaN" | "merge" | "Some" | "toMap"
+        | "forall" | "with" | "Type" | "Kind" | "Sort" | "True" | "False" => {
+            true
+        }
         _ => Builtin::parse(&s).is_some(),
     };
-    if !is_reserved && s.chars().all(|c| c.is_ascii_alphanumeric()) {
+    let is_simple = s
+        .chars()
+        .next()
+        .filter(|c| c.is_ascii_alphabetic() || *c == '_')
+        .is_some()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if !is_reserved && is_simple {
         write!(f, "{}", s)
     } else {
         write!(f, "`{}`", s)
@@ -273,6 +543,11 @@ impl<SE: Display + Clone> Display for OpKind<SE> {
             Completion(a, b) => {
                 write!(f, "{}::{}", a, b)?;
             }
+            With(a, ls, b) => {
+                write!(f, "{} with ", a)?;
+                fmt_list("", ".", "", ls, f, fmt_label)?;
+                write!(f, " = {}", b)?;
+            }
         }
         Ok(())
     }
@@ -324,16 +599,11 @@ impl<SubExpr: Display> Display for InterpolatedText<SubExpr> {
                             '\n' => f.write_str("\\n"),
                             '\r' => f.write_str("\\r"),
                             '\t' => f.write_str("\\t"),
+                            // Other control characters are printed as "\uXXXX", as
+                            // `Text/show` requires. Everything else, including non-ASCII
+                            // characters, is printed as is.
                             '\u{0000}'..='\u{001F}' => {
-                                // Escape to an explicit "\u{XXXX}" form
-                                let escaped: String =
-                                    c.escape_default().collect();
-                                // Print as "\uXXXX"
-                                write!(
-                                    f,
-                                    "\\u{:0>4}",
-                                    &escaped[3..escaped.len() - 1]
-                                )
+                                write!(f, "\\u{:04x}", c as u32)
                             }
                             c => write!(f, "{}", c),
                         }?;
@@ -387,15 +657,23 @@ impl Display for NaiveDouble {
             f.write_str("-Infinity")
         } else if v.is_nan() {
             f.write_str("NaN")
-        } else if v == 0.0 && v.is_sign_negative() {
-            f.write_str("-0.0")
-        } else {
-            let s = format!("{}", v);
-            if s.contains('e') || s.contains('.') {
+        } else if v == 0.0 || (0.1..1e7).contains(&v.abs()) {
+            // Like Haskell's `show`, which `Double/show` follows: plain decimal notation for
+            // moderate magnitudes, scientific notation otherwise.
+            let s = format!("{:?}", v);
+            if s.contains('.') {
                 f.write_str(&s)
             } else {
                 write!(f, "{}.0", s)
             }
+        } else {
+            let s = format!("{:e}", v);
+            match s.find('e') {
+                Some(i) if !s[..i].contains('.') => {
+                    write!(f, "{}.0{}", &s[..i], &s[i..])
+                }
+                _ => f.write_str(&s),
+            }
         }
     }
 }
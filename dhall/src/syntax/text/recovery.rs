@@ -0,0 +1,287 @@
+//! Recovery from syntax errors, so that one error doesn't hide the ones after it.
+//!
+//! The grammar has no error productions, so recovery works on the source text instead: when
+//! parsing fails, the list element, record field, union alternative or top-level `let` binding
+//! around the error is blanked out, and parsing starts over. Blanking keeps byte offsets
+//! unchanged, so the spans of the partial AST still point into the original text.
+use std::cmp::Reverse;
+
+use crate::error::ParseError;
+use crate::shared::Rc;
+use crate::syntax::text::nesting::DEFAULT_MAX_NESTING_DEPTH;
+use crate::syntax::text::parser::parse_expr_as;
+use crate::syntax::Expr;
+
+/// Parses an expression, recovering from syntax errors. Returns what could be parsed once the
+/// erroneous parts are left out, if anything, along with all the errors found.
+pub fn parse_expr_with_recovery(
+    input_str: &str,
+) -> (Option<Expr>, Vec<ParseError>) {
+    let input: Rc<str> = input_str.to_string().into();
+    let mut text = input_str.to_owned();
+    let mut errors = Vec::new();
+    let mut blanked: Vec<(usize, usize)> = Vec::new();
+    loop {
+        match parse_expr_as(&text, &input, Some(DEFAULT_MAX_NESTING_DEPTH)) {
+            Ok(expr) => return (Some(expr), errors),
+            Err((err, pos)) => {
+                // An error where something was left out is caused by the recovery itself.
+                if !blanked
+                    .iter()
+                    .any(|&(start, end)| start <= pos && pos <= end)
+                {
+                    errors.push(err);
+                }
+                // Each step blanks out some non-whitespace, so this terminates.
+                match recovery_region(&text, pos) {
+                    Some((start, end)) => {
+                        blank_out(&mut text, start, end);
+                        blanked.push((start, end));
+                    }
+                    None => return (None, errors),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TokenKind {
+    Open,
+    Close,
+    /// `,` or `|`
+    Separator,
+    Let,
+    In,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Token {
+    kind: TokenKind,
+    start: usize,
+    end: usize,
+}
+
+/// Finds the brackets, separators and `let`/`in` keywords of the text, skipping comments, text
+/// literals and quoted labels. All of these are ASCII, so this works on bytes.
+fn structure(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    lex(text.as_bytes(), 0, Some(&mut tokens));
+    tokens
+}
+
+/// Lexes from `i`. With `out`, lexes to the end of the input; without it, lexes an
+/// interpolation and returns the index after its closing brace.
+fn lex(b: &[u8], mut i: usize, mut out: Option<&mut Vec<Token>>) -> usize {
+    let is_label_byte =
+        |c: u8| c.is_ascii_alphanumeric() || matches!(c, b'_' | b'-' | b'/');
+    let is_keyword = |i: usize, kw: &[u8]| {
+        b[i..].starts_with(kw)
+            && (i == 0 || !is_label_byte(b[i - 1]))
+            && b.get(i + kw.len()).is_none_or(|&c| !is_label_byte(c))
+    };
+    let mut depth = 0;
+    while i < b.len() {
+        let (kind, len) = if b[i..].starts_with(b"--") {
+            i = b[i..]
+                .iter()
+                .position(|&c| c == b'\n')
+                .map_or(b.len(), |n| i + n + 1);
+            continue;
+        } else if b[i..].starts_with(b"{-") {
+            i = skip_block_comment(b, i);
+            continue;
+        } else if b[i] == b'"' {
+            i = skip_double_quoted(b, i + 1);
+            continue;
+        } else if b[i..].starts_with(b"''") {
+            i = skip_single_quoted(b, i + 2);
+            continue;
+        } else if b[i] == b'`' {
+            i = b[i + 1..]
+                .iter()
+                .position(|&c| c == b'`')
+                .map_or(b.len(), |n| i + n + 2);
+            continue;
+        } else if b[i..].starts_with(b"->") || b[i..].starts_with(b"||") {
+            i += 2;
+            continue;
+        } else if matches!(b[i], b'(' | b'[' | b'{' | b'<') {
+            depth += 1;
+            (TokenKind::Open, 1)
+        } else if matches!(b[i], b')' | b']' | b'}' | b'>') {
+            if out.is_none() && b[i] == b'}' && depth == 0 {
+                return i + 1;
+            }
+            depth -= 1;
+            (TokenKind::Close, 1)
+        } else if matches!(b[i], b',' | b'|') {
+            (TokenKind::Separator, 1)
+        } else if is_keyword(i, b"let") {
+            (TokenKind::Let, 3)
+        } else if is_keyword(i, b"in") {
+            (TokenKind::In, 2)
+        } else {
+            i += 1;
+            continue;
+        };
+        if let Some(out) = out.as_deref_mut() {
+            out.push(Token {
+                kind,
+                start: i,
+                end: i + len,
+            });
+        }
+        i += len;
+    }
+    i
+}
+
+fn skip_block_comment(b: &[u8], mut i: usize) -> usize {
+    let mut depth = 0;
+    while i < b.len() {
+        if b[i..].starts_with(b"{-") {
+            depth += 1;
+            i += 2;
+        } else if b[i..].starts_with(b"-}") {
+            depth -= 1;
+            i += 2;
+            if depth == 0 {
+                return i;
+            }
+        } else {
+            i += 1;
+        }
+    }
+    i
+}
+
+fn skip_double_quoted(b: &[u8], mut i: usize) -> usize {
+    while i < b.len() {
+        if b[i] == b'\\' {
+            i += 2;
+        } else if b[i] == b'"' {
+            return i + 1;
+        } else if b[i..].starts_with(b"${") {
+            i = lex(b, i + 2, None);
+        } else {
+            i += 1;
+        }
+    }
+    b.len()
+}
+
+fn skip_single_quoted(b: &[u8], mut i: usize) -> usize {
+    while i < b.len() {
+        if b[i..].starts_with(b"'''") {
+            i += 3;
+        } else if b[i..].starts_with(b"''${") {
+            i += 4;
+        } else if b[i..].starts_with(b"''") {
+            return i + 2;
+        } else if b[i..].starts_with(b"${") {
+            i = lex(b, i + 2, None);
+        } else {
+            i += 1;
+        }
+    }
+    b.len()
+}
+
+/// Chooses the part of the text to blank out to get past an error at `pos`: the innermost
+/// element of a list, record or union around the error, along with a separator so that the
+/// remaining elements stay well-formed. If that element is already blank, the element around
+/// the enclosing brackets is tried instead, and so on up to the `let` bindings at the top level.
+fn recovery_region(text: &str, pos: usize) -> Option<(usize, usize)> {
+    use TokenKind::*;
+    let tokens = structure(text);
+
+    // Pairs of matching brackets; unclosed ones extend to the end of the text.
+    let mut groups = Vec::new();
+    let mut stack = Vec::new();
+    for (i, tok) in tokens.iter().enumerate() {
+        match tok.kind {
+            Open => stack.push(i),
+            Close => {
+                if let Some(open) = stack.pop() {
+                    groups.push((open, Some(i)));
+                }
+            }
+            _ => {}
+        }
+    }
+    groups.extend(stack.into_iter().map(|open| (open, None)));
+    groups.retain(|&(open, close)| {
+        tokens[open].start < pos
+            && close.is_none_or(|close| pos <= tokens[close].start)
+    });
+    groups.sort_by_key(|&(open, _)| Reverse(tokens[open].start));
+
+    let mut target = pos;
+    for (open, close) in groups {
+        let inner = &tokens[open + 1..close.unwrap_or(tokens.len())];
+        let mut depth = 0usize;
+        let mut separators = Vec::new();
+        for tok in inner {
+            match tok.kind {
+                Open => depth += 1,
+                Close => depth = depth.saturating_sub(1),
+                Separator if depth == 0 => separators.push(*tok),
+                _ => {}
+            }
+        }
+        let prev = separators.iter().rev().find(|sep| sep.start < target);
+        let next = separators.iter().find(|sep| sep.start >= target);
+        let region = match (prev, next) {
+            (Some(prev), Some(next)) => (prev.end, next.end),
+            (None, Some(next)) => (tokens[open].end, next.end),
+            (Some(prev), None) => {
+                (prev.start, close.map_or(text.len(), |c| tokens[c].start))
+            }
+            (None, None) => (
+                tokens[open].end,
+                close.map_or(text.len(), |c| tokens[c].start),
+            ),
+        };
+        if !text[region.0..region.1].trim().is_empty() {
+            return Some(region);
+        }
+        target = tokens[open].start;
+    }
+
+    // Only whole `let` bindings can be left out at the top level.
+    let mut depth = 0usize;
+    let mut keywords = Vec::new();
+    for tok in &tokens {
+        match tok.kind {
+            Open => depth += 1,
+            Close => depth = depth.saturating_sub(1),
+            Let | In if depth == 0 => keywords.push(*tok),
+            _ => {}
+        }
+    }
+    // An error at a keyword is about the binding before it.
+    let i = keywords.iter().rposition(|kw| kw.start < target)?;
+    let next = keywords.get(i + 1)?;
+    if keywords[i].kind != Let {
+        return None;
+    }
+    // In `let a = x in let b = y in c`, leaving out `b` also leaves out the `in` before it.
+    let start = match i.checked_sub(1).map(|j| keywords[j]) {
+        Some(prev) if prev.kind == In => prev.start,
+        _ => keywords[i].start,
+    };
+    Some((start, next.start))
+}
+
+/// Replaces the given range of the text with whitespace of the same length, keeping line breaks.
+fn blank_out(text: &mut String, start: usize, end: usize) {
+    let blank: String = text[start..end]
+        .chars()
+        .map(|c| match c {
+            '\n' | '\r' => c.to_string(),
+            _ => " ".repeat(c.len_utf8()),
+        })
+        .collect();
+    text.replace_range(start..end, &blank);
+}
@@ -7,9 +7,11 @@ use std::env;
 use std::fmt::Display;
 use std::fs::{create_dir_all, read_to_string, File};
 use std::io::{Read, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use crate::error::{ErrorKind, Result};
+use crate::semantics::{Cache, ImportEnv};
 use crate::syntax::{binary, Expr};
 use crate::{Normalized, Parsed, Resolved, Typed};
 
@@ -96,7 +98,13 @@ impl TestFile {
     }
     /// Parse and resolve the target file
     pub fn resolve(&self) -> Result<Resolved> {
-        Ok(self.parse()?.resolve()?)
+        let parsed = self.parse()?;
+        let cache_dir = test_cache_dir()?;
+        let mut env = ImportEnv::new()
+            .semantic_cache(Some(Cache::with_dir(cache_dir.clone())?));
+        let resolved = parsed.resolve_with(&mut env);
+        std::fs::remove_dir_all(cache_dir)?;
+        resolved
     }
     /// Parse, resolve and tck the target file
     pub fn typecheck(&self) -> Result<Typed> {
@@ -232,6 +240,27 @@ impl TestFile {
     }
 }
 
+/// Creates a fresh cache directory holding the entries that import tests expect to find in the
+/// cache, e.g. for `hashFromCache`. Each resolution gets its own, so that tests neither see each
+/// other's entries nor write to the test suite.
+fn test_cache_dir() -> Result<PathBuf> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let dir = env::temp_dir().join(format!(
+        "dhall-test-cache-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    create_dir_all(&dir)?;
+    let shipped = Path::new("dhall-lang/tests/import/cache/dhall");
+    if let Ok(entries) = std::fs::read_dir(shipped) {
+        for entry in entries {
+            let entry = entry?;
+            std::fs::copy(entry.path(), dir.join(entry.file_name()))?;
+        }
+    }
+    Ok(dir)
+}
+
 #[allow(dead_code)]
 fn run_test_or_panic(test: Test) {
     let res = if env::var("CI_GRCOV").is_ok() {
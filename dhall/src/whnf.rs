@@ -0,0 +1,104 @@
+//! Lazy inspection of values. Normalization only evaluates a value up to its outermost
+//! constructor, its weak head normal form (WHNF); subvalues are evaluated when they are looked
+//! at. This allows walking a small part of an enormous configuration without evaluating the rest.
+use std::collections::BTreeMap;
+
+use crate::semantics::NirKind;
+use crate::syntax::NumKind;
+use crate::Normalized;
+
+/// The outermost constructor of a value, with its subvalues left unevaluated. See
+/// `Normalized::head`.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum Head {
+    Bool(bool),
+    Natural(usize),
+    Integer(isize),
+    Double(f64),
+    /// A text literal without interpolations.
+    Text(String),
+    Optional(Option<Normalized>),
+    List(Vec<Normalized>),
+    Record(BTreeMap<String, Normalized>),
+    /// A union alternative, with its argument if it has one.
+    Union(String, Option<Normalized>),
+    /// A function, including partially applied builtins.
+    Lambda,
+    /// A type, a kind or a sort.
+    Type,
+    /// Anything else, e.g. an operation stuck on a variable.
+    Other,
+}
+
+impl Normalized {
+    /// Evaluates the value up to its outermost constructor and returns it, leaving the subvalues
+    /// unevaluated. Calling `head` on those evaluates them in turn, so only the parts of the value
+    /// that are looked at get evaluated.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), dhall::error::Error> {
+    /// use dhall::whnf::Head;
+    ///
+    /// let config = dhall::Parsed::parse_str(
+    ///     "{ hosts = [ \"a\", \"b\" ], retries = Natural/fold 1000000 Natural (λ(x : Natural) → x + 1) 0 }",
+    /// )?
+    /// .resolve()?
+    /// .typecheck()?
+    /// .normalize();
+    /// // `retries` doesn't get evaluated.
+    /// let hosts = match config.head() {
+    ///     Head::Record(fields) => fields["hosts"].clone(),
+    ///     _ => unreachable!(),
+    /// };
+    /// match hosts.head() {
+    ///     Head::List(hosts) => match hosts[0].head() {
+    ///         Head::Text(host) => assert_eq!(host, "a"),
+    ///         _ => unreachable!(),
+    ///     },
+    ///     _ => unreachable!(),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn head(&self) -> Head {
+        let wrap = |v: &crate::semantics::Nir| Normalized(v.clone());
+        match self.0.kind() {
+            NirKind::Num(NumKind::Bool(b)) => Head::Bool(*b),
+            NirKind::Num(NumKind::Natural(n)) => Head::Natural(*n),
+            NirKind::Num(NumKind::Integer(n)) => Head::Integer(*n),
+            NirKind::Num(NumKind::Double(d)) => Head::Double((*d).into()),
+            NirKind::TextLit(t) => match t.as_text() {
+                Some(s) => Head::Text(s),
+                None => Head::Other,
+            },
+            NirKind::EmptyOptionalLit(_) => Head::Optional(None),
+            NirKind::NEOptionalLit(v) => Head::Optional(Some(wrap(v))),
+            NirKind::EmptyListLit(_) => Head::List(Vec::new()),
+            NirKind::NEListLit(xs) => Head::List(xs.iter().map(wrap).collect()),
+            NirKind::RecordLit(kvs) => Head::Record(
+                kvs.iter().map(|(k, v)| (k.into(), wrap(v))).collect(),
+            ),
+            NirKind::UnionLit(l, v, _) => Head::Union(l.into(), Some(wrap(v))),
+            NirKind::UnionConstructor(l, kts) if kts.get(l) == Some(&None) => {
+                Head::Union(l.into(), None)
+            }
+            NirKind::LamClosure { .. }
+            | NirKind::UnionConstructor(..)
+            | NirKind::AppliedBuiltin(..)
+            | NirKind::Foreign(..) => Head::Lambda,
+            NirKind::Const(_)
+            | NirKind::BuiltinType(_)
+            | NirKind::PiClosure { .. }
+            | NirKind::OptionalType(_)
+            | NirKind::ListType(_)
+            | NirKind::RecordType(_)
+            | NirKind::UnionType(_)
+            | NirKind::Equivalence(..) => Head::Type,
+            NirKind::Var(_)
+            | NirKind::Assert(_)
+            | NirKind::Op(_)
+            | NirKind::Aborted => Head::Other,
+        }
+    }
+}
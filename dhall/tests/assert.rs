@@ -0,0 +1,34 @@
+mod common;
+
+use common::{print, typecheck};
+
+#[test]
+fn test_equivalence_binds_loosest() {
+    assert_eq!(typecheck("assert : 1 + 1 ≡ 2"), Ok(()));
+    assert_eq!(typecheck("assert : [ 1 ] # [ 2 ] ≡ [ 1, 2 ]"), Ok(()));
+    assert_eq!(typecheck("λ(n : Natural) → assert : n * 1 ≡ n"), Ok(()));
+    assert_eq!(print("(1 ≡ 1) + 1 ≡ 2"), "(1 ≡ 1) + 1 ≡ 2");
+}
+
+#[test]
+fn test_assert_is_up_to_normalization_and_alpha() {
+    assert_eq!(
+        typecheck("assert : (λ(x : Natural) → x) ≡ (λ(y : Natural) → y)"),
+        Ok(())
+    );
+    assert_eq!(typecheck("assert : NaN ≡ NaN"), Ok(()));
+    assert!(typecheck("assert : +0.0 ≡ -0.0").is_err());
+    // There is no eta-equivalence
+    assert!(typecheck(
+        "λ(f : Natural → Natural) → assert : (λ(x : Natural) → f x) ≡ f"
+    )
+    .is_err());
+}
+
+#[test]
+fn test_assert_reflexivity_skips_normalization() {
+    let expensive =
+        "Natural/fold 1000000000 Natural (λ(x : Natural) → x + 1) 0";
+    let src = format!("assert : {} ≡ {}", expensive, expensive);
+    assert_eq!(typecheck(&src), Ok(()));
+}
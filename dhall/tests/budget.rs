@@ -0,0 +1,52 @@
+use dhall::error::{ErrorKind, EvalError};
+use dhall::semantics::with_eval_budget;
+use dhall::{Parsed, Typed};
+
+fn typed(src: &str) -> Typed {
+    Parsed::parse_str(src)
+        .unwrap()
+        .resolve()
+        .unwrap()
+        .typecheck()
+        .unwrap()
+}
+
+const SLOW: &str =
+    "{ a = Natural/fold 100000 Natural (λ(x : Natural) → x + 1) 0, b = True }";
+
+#[test]
+fn test_budget_exceeded() {
+    let err = typed(SLOW).normalize_with_budget(1000).unwrap_err();
+    match err.kind() {
+        ErrorKind::Eval(EvalError::BudgetExceeded(err)) => {
+            assert_eq!(err.steps, 1000)
+        }
+        _ => panic!("unexpected error: {}", err),
+    }
+    assert_eq!(
+        typed(SLOW)
+            .normalize_with_budget(1_000_000)
+            .unwrap()
+            .to_string(),
+        "{ a = 100000, b = True }"
+    );
+}
+
+#[test]
+fn test_aborted_values_stay_usable() {
+    // `normalize` is lazy, so the fold is only evaluated under the budget, which gives up on it.
+    let nf = typed(SLOW).normalize();
+    assert!(with_eval_budget(1000, || nf.to_string()).is_err());
+    assert_eq!(nf.to_string(), "{ a = 100000, b = True }");
+}
+
+#[test]
+fn test_nested_budgets() {
+    let nf = typed(SLOW).normalize();
+    let ret = with_eval_budget(1_000_000, || {
+        // The inner budget running out doesn't abort the outer evaluation.
+        assert!(with_eval_budget(10, || nf.to_string()).is_err());
+        nf.to_string()
+    });
+    assert_eq!(ret.unwrap(), "{ a = 100000, b = True }");
+}
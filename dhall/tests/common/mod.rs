@@ -0,0 +1,38 @@
+//! Helpers shared by the integration tests. Each test crate uses only some of them.
+#![allow(dead_code)]
+use dhall::syntax::Expr;
+use dhall::{Parsed, Typed};
+
+/// Parses an expression and prints it back.
+pub fn print(src: &str) -> String {
+    Parsed::parse_str(src).unwrap().to_string()
+}
+
+fn typed(src: &str) -> Result<Typed, String> {
+    Parsed::parse_str(src)
+        .unwrap()
+        .resolve()
+        .unwrap()
+        .typecheck()
+        .map_err(|e| e.to_string())
+}
+
+/// Parses, resolves and typechecks an expression, returning the type error if there is one.
+pub fn typecheck(src: &str) -> Result<(), String> {
+    typed(src).map(|_| ())
+}
+
+/// Prints the type of a well-typed expression.
+pub fn type_of(src: &str) -> String {
+    typed(src).unwrap().get_type().unwrap().to_string()
+}
+
+/// Prints the normal form of a well-typed expression.
+pub fn normalize(src: &str) -> String {
+    typed(src).unwrap().normalize().to_string()
+}
+
+/// Returns the normal form of a well-typed expression.
+pub fn normal_form(src: &str) -> Expr {
+    typed(src).unwrap().normalize().to_expr()
+}
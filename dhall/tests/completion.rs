@@ -0,0 +1,22 @@
+mod common;
+
+use common::normalize;
+use dhall::Parsed;
+
+const SCHEMA: &str =
+    "let T = { Type = { a : Natural, b : Bool }, default = { a = 1 } } in ";
+
+#[test]
+fn test_completion_round_trips() {
+    let src = format!("{}λ(r : {{ b : Bool }}) → T::r", SCHEMA);
+    let resolved = Parsed::parse_str(&src).unwrap().resolve().unwrap();
+    assert!(resolved.to_string().ends_with("→ T::r"));
+    let nf = resolved.typecheck().unwrap().normalize();
+    assert_eq!(nf.to_string(), "λ(r : { b : Bool }) → { a = 1 } ⫽ r");
+}
+
+#[test]
+fn test_completion_normalizes() {
+    let src = format!("{}(T::{{ b = True }}).a", SCHEMA);
+    assert_eq!(normalize(&src), "1");
+}
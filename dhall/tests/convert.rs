@@ -0,0 +1,49 @@
+mod common;
+
+use common::normal_form;
+use dhall::convert::{
+    flatten_record, to_env, unflatten_record, FlattenOptions,
+};
+
+#[test]
+fn test_flatten_reports_colliding_keys() {
+    let expr = normal_form("{ `a.b` = 1, a = { b = 2 } }");
+    assert_eq!(
+        flatten_record(&expr, &FlattenOptions::new()),
+        Err("fields a.b and `a.b` both flatten to the key `a.b`".to_owned())
+    );
+    // With another separator, the keys don't collide.
+    let options = FlattenOptions::new().separator("/");
+    let flat = flatten_record(&expr, &options).unwrap();
+    let keys: Vec<_> = flat.keys().cloned().collect();
+    assert_eq!(keys, vec!["a.b", "a/b"]);
+}
+
+#[test]
+fn test_flatten_indexed_lists_round_trip() {
+    let options = FlattenOptions::new().index_lists(true);
+    let expr = normal_form(
+        "{ xs = [ { a = 1 }, { a = 2 } ], ys = [] : List Bool, `0` = 3 }",
+    );
+    let flat = flatten_record(&expr, &options).unwrap();
+    let keys: Vec<_> = flat.keys().cloned().collect();
+    assert_eq!(keys, vec!["0", "xs.0.a", "xs.1.a", "ys"]);
+    assert_eq!(unflatten_record(&flat, &options), Ok(expr));
+
+    let expr = normal_form("{ xs = [ 1 ], `xs.0` = 2 }");
+    assert_eq!(
+        flatten_record(&expr, &options),
+        Err("fields xs.0 and `xs.0` both flatten to the key `xs.0`".to_owned())
+    );
+}
+
+#[test]
+fn test_env_rejects_colliding_names() {
+    let expr = normal_form("{ a_b = 1, a = { b = 2 } }");
+    assert_eq!(
+        to_env(&expr),
+        Err("fields a.b and a_b both flatten to the key `a_b`".to_owned())
+    );
+    let expr = normal_form("{ a_b = 1, a = { c = 2 } }");
+    assert_eq!(to_env(&expr), Ok("a_b=1\na_c=2\n".to_owned()));
+}
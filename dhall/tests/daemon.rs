@@ -0,0 +1,26 @@
+use std::env;
+
+use dhall::daemon::Daemon;
+
+#[test]
+fn test_local_imports_are_read_again() {
+    let dir =
+        env::temp_dir().join(format!("dhall-daemon-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("value.dhall");
+    let request =
+        format!(r#"{{"command": "eval", "expr": "{} + 1"}}"#, file.display());
+    let mut daemon = Daemon::new();
+
+    std::fs::write(&file, "1").unwrap();
+    assert_eq!(
+        daemon.handle(&request),
+        r#"{"id":null,"ok":true,"result":"2"}"#
+    );
+    std::fs::write(&file, "2").unwrap();
+    assert_eq!(
+        daemon.handle(&request),
+        r#"{"id":null,"ok":true,"result":"3"}"#
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,40 @@
+mod common;
+
+use common::normalize;
+
+#[test]
+fn test_deep_natural_fold() {
+    let n = normalize("Natural/fold 100000 Natural (λ(x : Natural) → x + 1) 0");
+    assert_eq!(n, "100000");
+}
+
+#[test]
+fn test_long_text_concatenation() {
+    let n =
+        normalize("Natural/fold 100000 Text (λ(t : Text) → t ++ \"ab\") \"\"");
+    assert_eq!(n, format!("\"{}\"", "ab".repeat(100000)));
+}
+
+#[test]
+fn test_natural_fold_stops_early() {
+    let n = normalize("Natural/fold 1000000000 Natural (λ(_ : Natural) → 5) 0");
+    assert_eq!(n, "5");
+}
+
+#[test]
+fn test_fold_build_fusion() {
+    // Without fusion, this would build a list of a billion elements.
+    let n = normalize(
+        "List/fold Bool (List/build Bool (λ(list : Type) → λ(cons : Bool → list → list) → λ(nil : list) → Natural/fold 1000000000 list (cons True) nil)) Bool (λ(x : Bool) → λ(_ : Bool) → x) False",
+    );
+    assert_eq!(n, "True");
+}
+
+#[test]
+fn test_fold_of_stuck_build_is_not_fused() {
+    // The build doesn't reduce to a literal, so neither does the fold.
+    let n = normalize(
+        "λ(f : ∀(list : Type) → (Bool → list → list) → list → list) → List/fold Bool (List/build Bool f) Natural (λ(_ : Bool) → λ(n : Natural) → n + 1) 0",
+    );
+    assert!(n.contains("List/fold"), "{}", n);
+}
@@ -0,0 +1,37 @@
+mod common;
+
+use common::{print, typecheck};
+use dhall::Parsed;
+
+#[test]
+fn test_double_literals() {
+    assert_eq!(
+        print("[ NaN, Infinity, -Infinity ]"),
+        "[NaN, Infinity, -Infinity]"
+    );
+    assert_eq!(print("+2.50"), "2.5");
+    assert_eq!(print("-0.0"), "-0.0");
+    assert_eq!(print("1E10"), "1.0e10");
+    assert_eq!(print("-1.5e-3"), "-1.5e-3");
+    assert_eq!(print("123.456e2"), "12345.6");
+    assert!(Parsed::parse_str("1e400").is_err());
+}
+
+#[test]
+fn test_double_show_is_canonical() {
+    assert_eq!(
+        typecheck("assert : Double/show 1e300 ≡ \"1.0e300\""),
+        Ok(())
+    );
+    assert_eq!(typecheck("assert : Double/show 0.05 ≡ \"5.0e-2\""), Ok(()));
+    assert_eq!(
+        typecheck("assert : Double/show 1234567.0 ≡ \"1234567.0\""),
+        Ok(())
+    );
+}
+
+#[test]
+fn test_double_equivalence() {
+    assert_eq!(typecheck("assert : NaN ≡ NaN"), Ok(()));
+    assert!(typecheck("assert : 0.0 ≡ -0.0").is_err());
+}
@@ -0,0 +1,111 @@
+use std::env;
+use std::path::Path;
+
+use dhall::context::Context;
+use dhall::semantics::{Cache, ImportEnv};
+use dhall::Parsed;
+
+fn resolve_to_string(src: &str) -> String {
+    let mut env = ImportEnv::new();
+    Parsed::parse_str(src)
+        .unwrap()
+        .resolve_with(&mut env)
+        .unwrap()
+        .typecheck()
+        .unwrap()
+        .normalize()
+        .to_string()
+}
+
+#[test]
+fn test_protected_imports_are_cached() {
+    let dir = env::temp_dir()
+        .join(format!("dhall-import-cache-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("answer.dhall"), "40 + 2").unwrap();
+    let cache_home = dir.join("cache");
+    env::remove_var("DHALL_CACHE");
+    env::set_var("XDG_CACHE_HOME", &cache_home);
+
+    // The cache directory is only created when an entry is written.
+    let _ = ImportEnv::new();
+    assert!(!cache_home.exists());
+
+    let hash =
+        "c39cde2e11e3d5a57cccbc06f6599256ece67b3d16d1bc1df1d0cfa79d9be605";
+    let file = dir.join("answer.dhall");
+    let import = format!("{} sha256:{}", file.display(), hash);
+    assert_eq!(resolve_to_string(&import), "42");
+    let entry = Path::new(&cache_home)
+        .join("dhall")
+        .join(format!("1220{}", hash));
+    assert!(entry.exists());
+
+    assert_eq!(resolve_to_string(&format!("missing sha256:{}", hash)), "42");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_host_builtins_bypass_the_normal_form_cache() {
+    let dir = env::temp_dir()
+        .join(format!("dhall-normal-form-cache-{}", std::process::id()));
+    let cache = Cache::with_dir(dir.clone()).unwrap();
+    let eval = |src: &str| {
+        Parsed::parse_str(src)
+            .unwrap()
+            .resolve()
+            .unwrap()
+            .typecheck()
+            .unwrap()
+            .normalize()
+    };
+    let normalize_with_secret = |secret: &'static str| {
+        let ctx = Context::new()
+            .builtin("lookupSecret", eval("Text → Text"), move |_| {
+                Some(eval(secret))
+            })
+            .unwrap();
+        let config = Parsed::parse_str(r#"lookupSecret "db""#).unwrap();
+        ctx.resolve(config)
+            .unwrap()
+            .typecheck()
+            .unwrap()
+            .normalize_cached(&cache)
+            .to_string()
+    };
+    assert_eq!(normalize_with_secret(r#""one""#), r#""one""#);
+    assert_eq!(normalize_with_secret(r#""two""#), r#""two""#);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_offline_imports_check_cors() {
+    let dir = env::temp_dir()
+        .join(format!("dhall-offline-cors-{}", std::process::id()));
+    let cache = Cache::with_dir(dir.clone()).unwrap();
+    let parent = "https://a.example/parent.dhall".parse().unwrap();
+    let child = "https://b.example/child.dhall".parse().unwrap();
+    cache
+        .insert_remote(&parent, "https://b.example/child.dhall", None)
+        .unwrap();
+    let resolve = || {
+        let mut env = ImportEnv::new()
+            .semantic_cache(Some(cache.clone()))
+            .offline(true);
+        Parsed::parse_str("https://a.example/parent.dhall")
+            .unwrap()
+            .resolve_with(&mut env)
+            .map(|resolved| resolved.typecheck().unwrap().to_string())
+            .map_err(|err| err.to_string())
+    };
+
+    cache.insert_remote(&child, "1", None).unwrap();
+    let err = resolve().unwrap_err();
+    assert!(err.contains("Access-Control-Allow-Origin"), "{}", err);
+
+    cache
+        .insert_remote(&child, "1", Some("https://a.example"))
+        .unwrap();
+    assert_eq!(resolve(), Ok("1".to_owned()));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,20 @@
+mod common;
+
+use common::print;
+
+fn round_trip(src: &str) {
+    assert_eq!(print(src), src);
+}
+
+#[test]
+fn test_quoted_labels_round_trip() {
+    round_trip("λ(`x y` : Natural) → `x y`");
+    round_trip("{ `a.b` = 1, c = 2, `merge` = 3, `with` = 4 }.`a.b`");
+    round_trip("< `Infinity` | `if` | `toMap`: Bool >.`Infinity`");
+    round_trip("let `assert` = 1 in `assert`");
+}
+
+#[test]
+fn test_unneeded_quotes_are_dropped() {
+    assert_eq!(print("{ `a` = `b-c` }"), "{ a = b-c }");
+}
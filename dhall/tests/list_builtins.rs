@@ -0,0 +1,14 @@
+mod common;
+
+use common::normalize;
+
+#[test]
+fn test_reversed_list() {
+    let n = normalize(
+        "let xs = List/reverse Natural [1, 2, 3] in { head = List/head Natural xs, last = List/last Natural xs, length = List/length Natural xs, indexed = List/indexed Natural xs, back = List/reverse Natural xs, append = xs # [4], fold = List/fold Natural xs Text (λ(x : Natural) → λ(t : Text) → Natural/show x ++ t) \"\" }",
+    );
+    assert_eq!(
+        n,
+        "{ append = [3, 2, 1, 4], back = [1, 2, 3], fold = \"321\", head = Some 3, indexed = [{ index = 0, value = 3 }, { index = 1, value = 2 }, { index = 2, value = 1 }], last = Some 1, length = 3 }"
+    );
+}
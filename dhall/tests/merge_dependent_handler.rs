@@ -0,0 +1,12 @@
+mod common;
+
+use common::typecheck;
+
+#[test]
+fn test_merge_return_type_is_dependent() {
+    let err = typecheck(
+        "merge { x = λ(y : Type) → λ(z : y) → z } (< x : Type >.x Bool)",
+    )
+    .unwrap_err();
+    assert!(err.contains("MergeReturnTypeIsDependent"));
+}
@@ -0,0 +1,92 @@
+use dhall::error::{ErrorKind, ParseError, SourcePosition};
+use dhall::Parsed;
+
+fn parse_error(src: &str) -> ParseError {
+    match Parsed::parse_str(src).unwrap_err().into_kind() {
+        ErrorKind::Parse(err) => err,
+        err => panic!("expected a parse error, got {:?}", err),
+    }
+}
+
+#[test]
+fn test_parse_error_expected_tokens() {
+    let err = parse_error("merge x");
+    assert_eq!(err.diagnostic().title(), "expected an expression");
+    assert_eq!(
+        err.diagnostic().position(),
+        Some(SourcePosition {
+            line: 1,
+            column: 7,
+            end_line: 1,
+            end_column: 8
+        })
+    );
+
+    let err = parse_error("let x = 1");
+    assert_eq!(err.expected(), ["an operator", "`→`", "a `let` binding"]);
+    assert!(err.to_string().contains("unexpected end of input"));
+}
+
+#[test]
+fn test_parse_error_custom_message() {
+    let err = parse_error("\"\\uD800\"");
+    assert!(err.expected().is_empty());
+    assert_eq!(
+        err.diagnostic().title(),
+        "Escape sequences can't contain surrogate pairs"
+    );
+    let position = err.diagnostic().position().unwrap();
+    assert_eq!((position.column, position.end_column), (3, 8));
+}
+
+#[test]
+fn test_parse_unicode_escapes() {
+    let parsed = Parsed::parse_str("\"\\u{0000000041}\\u{1F600}\\u00E9\"");
+    assert_eq!(parsed.unwrap().to_string(), "\"A😀é\"");
+
+    let err = parse_error("\"\\u{110000}\"");
+    assert_eq!(
+        err.diagnostic().title(),
+        "Escape sequences can't be above U+10FFFF"
+    );
+}
+
+#[test]
+fn test_parse_error_location() {
+    let err = parse_error("let x = 1\nin x é");
+    assert_eq!((err.line(), err.column()), (2, 6));
+    assert_eq!(err.byte_span(), 15..17);
+    assert_eq!(err.snippet(), "é");
+
+    let err = parse_error("[ 1,");
+    assert_eq!((err.line(), err.column()), (1, 5));
+    assert_eq!(err.byte_span(), 4..4);
+    assert_eq!(err.snippet(), "");
+}
+
+#[test]
+fn test_parse_numeric_overflow() {
+    let err = parse_error("[ 1, 99999999999999999999999 ]");
+    assert_eq!(
+        err.diagnostic().title(),
+        format!(
+            "Natural literal too large for this implementation, max is {}",
+            usize::MAX
+        )
+    );
+    assert_eq!(err.byte_span(), 5..28);
+
+    let err = parse_error("-0xFFFFFFFFFFFFFFFFFFFF");
+    assert_eq!(
+        err.diagnostic().title(),
+        format!(
+            "Integer literal too small for this implementation, min is {}",
+            isize::MIN
+        )
+    );
+    let err = parse_error("+99999999999999999999999");
+    assert!(err
+        .diagnostic()
+        .title()
+        .starts_with("Integer literal too large"));
+}
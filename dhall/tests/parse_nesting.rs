@@ -0,0 +1,100 @@
+use dhall::Parsed;
+
+// Each level of nesting used to make parsing twice as slow.
+#[test]
+fn test_parse_deeply_nested() {
+    let depth = 40;
+    let sources = [
+        format!("{}1{}", "(".repeat(depth), ")".repeat(depth)),
+        format!("{}1{}", "[ ".repeat(depth), " ]".repeat(depth)),
+        format!("{}1{}", "{ a = f ".repeat(depth), " }".repeat(depth)),
+        format!("{}x{}", "λ(x : T) → (".repeat(depth), ")".repeat(depth)),
+    ];
+    for src in sources.iter() {
+        let printed = Parsed::parse_str(src).unwrap().to_string();
+        let reparsed = Parsed::parse_str(&printed).unwrap().to_string();
+        assert_eq!(reparsed, printed);
+    }
+}
+
+#[test]
+fn test_operators_associate_left() {
+    let parse = |src: &str| Parsed::parse_str(src).unwrap();
+    assert!(parse("2 * 3 * 4") == parse("(2 * 3) * 4"));
+    assert!(parse("2 * 3 * 4") != parse("2 * (3 * 4)"));
+    assert_eq!(parse("2 * (3 * 4)").to_string(), "2 * (3 * 4)");
+    assert_eq!(parse("a ? (b ? c)").to_string(), "a ? (b ? c)");
+    assert_eq!(parse("(a + b) * c + d").to_string(), "(a + b) * c + d");
+}
+
+#[test]
+fn test_parse_long_operator_chain() {
+    let src = vec!["1"; 2000].join(" + ");
+    assert_eq!(Parsed::parse_str(&src).unwrap().to_string(), src);
+    let resolved = Parsed::parse_str(&src).unwrap().resolve().unwrap();
+    assert_eq!(
+        resolved.typecheck().unwrap().type_expr().to_string(),
+        "Natural"
+    );
+}
+
+#[test]
+fn test_nesting_limit() {
+    let depth = 100_000;
+    let sources = [
+        format!("{}1{}", "(".repeat(depth), ")".repeat(depth)),
+        format!("{}x", "λ(x : T) → ".repeat(depth)),
+        format!("{}1", "let x = 1 in ".repeat(depth)),
+        format!("{}x{}", "\"${".repeat(depth), "}\"".repeat(depth)),
+    ];
+    for src in sources.iter() {
+        let err = Parsed::parse_str(src).unwrap_err().to_string();
+        assert!(err.contains("Expression too deeply nested"), "{}", err);
+    }
+    let err = Parsed::parse_str_lossless(&sources[0]).unwrap_err();
+    assert!(err.to_string().contains("more than 1000 levels"), "{}", err);
+
+    // Consecutive bindings don't nest in the grammar.
+    let lets = format!(
+        "{}in x",
+        "let x : Natural = if b then 1 else 2\n".repeat(1000)
+    );
+    assert!(Parsed::parse_str(&lets).is_ok());
+}
+
+#[test]
+fn test_nesting_below_the_limit() {
+    let depth = 900;
+    let sources = [
+        format!("{}1{}", "(".repeat(depth), ")".repeat(depth)),
+        format!("{}x", "λ(x : T) → ".repeat(depth)),
+        format!("{}1", "let x = 1 in ".repeat(depth)),
+        format!("{}T", "T → ".repeat(depth)),
+    ];
+    // The parser needs more stack than this for any of these, and gets it.
+    let small_stack = 256 * 1024;
+    std::thread::Builder::new()
+        .stack_size(small_stack)
+        .spawn(move || {
+            for src in sources.iter() {
+                assert!(Parsed::parse_str(src).is_ok());
+            }
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+#[test]
+fn test_long_binding_chains() {
+    let depth = 150;
+    let lets: String = (0..depth)
+        .map(|i| format!("let x{} = {} in ", i, i))
+        .collect();
+    let arrows = format!("λ(f : {}Natural) → f", "Natural → ".repeat(depth));
+    for src in [format!("{}x0", lets), arrows].iter() {
+        let parsed = Parsed::parse_str(src).unwrap();
+        assert_eq!(parsed.to_string(), *src);
+        assert!(parsed.resolve().unwrap().typecheck().is_ok());
+    }
+}
@@ -0,0 +1,63 @@
+use dhall::error::ParseError;
+use dhall::Parsed;
+
+fn recover(src: &str) -> (Option<String>, Vec<(usize, usize)>) {
+    let (parsed, errors) = Parsed::parse_str_with_recovery(src);
+    let positions = errors
+        .iter()
+        .map(ParseError::diagnostic)
+        .map(|diagnostic| {
+            let position = diagnostic.position().unwrap();
+            (position.line, position.column)
+        })
+        .collect();
+    (parsed.map(|parsed| parsed.to_string()), positions)
+}
+
+#[test]
+fn test_recover_elements() {
+    assert_eq!(
+        recover("{ a = 1, b = , c = }"),
+        (Some("{ a = 1 }".to_owned()), vec![(1, 14), (1, 20)])
+    );
+    assert_eq!(
+        recover("[ 1, 2 +, 3, (4 +) ]"),
+        (Some("[1, 3]".to_owned()), vec![(1, 9), (1, 18)])
+    );
+    assert_eq!(
+        recover("< A : | B >"),
+        (Some("< B >".to_owned()), vec![(1, 7)])
+    );
+    // Separators in comments don't count.
+    assert_eq!(
+        recover("{ a = [ 1, , 2 ], b = -- x, y\n , c = 3 }"),
+        (
+            Some("{ a = [1, 2], c = 3 }".to_owned()),
+            vec![(1, 12), (2, 2)]
+        )
+    );
+}
+
+#[test]
+fn test_recover_let_bindings() {
+    assert_eq!(
+        recover("let a = 1 in let b = ] in let c = 2 in c"),
+        (
+            Some("let a = 1 in let c = 2 in c".to_owned()),
+            vec![(1, 22)]
+        )
+    );
+    assert_eq!(
+        recover("let a = 1 +\nlet b = { x = }\nlet c = 3\nin c"),
+        (
+            Some("let b = {} in let c = 3 in c".to_owned()),
+            vec![(2, 1), (2, 15)]
+        )
+    );
+}
+
+#[test]
+fn test_recover_nothing() {
+    assert_eq!(recover("{ a = 1"), (None, vec![(1, 8)]));
+    assert_eq!(recover("1 + 1"), (Some("1 + 1".to_owned()), vec![]));
+}
@@ -0,0 +1,101 @@
+use dhall::syntax::{parse_expr, Expr, ExprKind, NumKind, Span};
+
+const CORPUS: &[&str] = &[
+    "let x : Natural = 1 + 2 * 3 in x",
+    "let a = 1 let b = 2 in a + b",
+    "λ(x : Natural) → ∀(y : Type) → x",
+    "{ a = 1, b.c = True, d } ⫽ { a = 2 } ∧ {=} ⩓ {}",
+    "[ 1, 2 ] # ([] : List Natural)",
+    "\"abc${ 1 + 1 }def\" ++ ''\nfoo${ x }bar\n''",
+    "merge { A = 1 } (< A >.A) : Natural",
+    "./foo/bar as Text ? env:HOME ? missing",
+    "r with a.b = 1 with c = 2",
+    "x@1 `a-b`.c.{ d, e }.(f)",
+    "if (a || b) then Some (f x) else None Natural",
+    "(λ(x : Bool) → x) True",
+    "(2 * (3 + 4)) * 5",
+    "toMap { a = 1 } : List { mapKey : Text, mapValue : Natural }",
+];
+
+/// Puts comments and odd spacing wherever there is whitespace.
+fn scramble(src: &str) -> String {
+    src.replace(' ', "  {- c -}\n-- line\n\t")
+}
+
+/// Checks that the source text of each subexpression parses back to it, which is what
+/// `print_preserving` relies on.
+fn check_spans(expr: &Expr) {
+    if let Span::Parsed(span) = expr.span() {
+        let reparsed = parse_expr(span.as_str()).unwrap();
+        assert_eq!(&reparsed, expr, "{}", span.as_str());
+    }
+    expr.kind().map_ref(check_spans);
+}
+
+/// Adds 1 to every `Natural` literal.
+fn bump(expr: &Expr) -> Expr {
+    let kind = match expr.kind() {
+        ExprKind::Num(NumKind::Natural(n)) => {
+            ExprKind::Num(NumKind::Natural(n + 1))
+        }
+        kind => kind.map_ref(bump),
+    };
+    Expr::new(kind, expr.span())
+}
+
+#[test]
+fn test_unmodified_round_trips() {
+    for src in CORPUS {
+        let src =
+            format!("-- header\n\n  {}  {{- trailer -}}\n", scramble(src));
+        let parsed = parse_expr(&src).unwrap();
+        assert_eq!(parsed.print_preserving(&parsed).to_string(), src);
+        check_spans(&parsed);
+    }
+}
+
+#[test]
+fn test_rewrite_keeps_untouched_parts() {
+    let src =
+        "-- config\n{ port =   80\n, name = \"x\" ++ {- who -} \"y\"\n}\n";
+    let parsed = parse_expr(src).unwrap();
+    assert_eq!(
+        bump(&parsed).print_preserving(&parsed).to_string(),
+        "-- config\n{ name = \"x\" ++ {- who -} \"y\", port = 81 }\n",
+    );
+
+    let src = "let f = λ(x : Natural) →  {- id -}\n  x\nin  f 1";
+    let parsed = parse_expr(src).unwrap();
+    assert_eq!(
+        bump(&parsed).print_preserving(&parsed).to_string(),
+        "let f = λ(x : Natural) →  {- id -}\n  x in f 2",
+    );
+}
+
+#[test]
+fn test_rewrites_are_parenthesized() {
+    // The rewritten node is printed anew, and the parts it is made of need parentheses.
+    let original = parse_expr("f   (1 + 1)").unwrap();
+    let rewritten = parse_expr("(λ(x : Natural) → x) (1 + 1)").unwrap();
+    assert_eq!(
+        rewritten.print_preserving(&original).to_string(),
+        "(λ(x : Natural) → x) (1 + 1)",
+    );
+    for src in CORPUS {
+        let parsed = parse_expr(&scramble(src)).unwrap();
+        let printed = bump(&parsed).print_preserving(&parsed).to_string();
+        assert_eq!(parse_expr(&printed).unwrap(), bump(&parsed), "{}", printed);
+    }
+}
+
+#[test]
+fn test_format_check() {
+    let is_formatted = |src: &str| {
+        let parsed = parse_expr(src).unwrap();
+        parsed.to_string() == parsed.print_preserving(&parsed).to_string()
+    };
+    assert!(is_formatted("{ a = 1, b = [2, 3] }"));
+    assert!(!is_formatted("{ a = 1, b = [ 2, 3 ] }"));
+    assert!(!is_formatted("{ b = [2, 3], a = 1 }"));
+    assert!(!is_formatted("{ a = 1 } -- comment\n"));
+}
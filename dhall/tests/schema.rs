@@ -0,0 +1,47 @@
+mod common;
+
+use common::{normal_form, typecheck};
+use dhall::schema::diff_types;
+
+/// Checks that the diff between two schemas is breaking exactly when values of the old one
+/// don't typecheck against the new one. `uses` are expressions that use a value of the old
+/// schema where the new one is expected; in them, `Old` and `New` stand for the two schemas.
+fn check(old: &str, new: &str, uses: &[&str]) {
+    let diff = diff_types(&normal_form(old), &normal_form(new));
+    for body in uses {
+        let src = format!("let Old = {} let New = {} in {}", old, new, body);
+        assert_eq!(typecheck(&src).is_ok(), diff.is_compatible(), "{}", src);
+    }
+}
+
+#[test]
+fn test_added_optional_field_is_breaking() {
+    let (old, new) = ("{ a : Natural }", "{ a : Natural, b : Optional Bool }");
+    check(old, new, &["{ a = 1 } : New", "λ(x : Old) → x : New"]);
+    assert!(!diff_types(&normal_form(old), &normal_form(new)).is_compatible());
+}
+
+#[test]
+fn test_added_alternative_is_breaking() {
+    let (old, new) = ("< A | B >", "< A | B | C >");
+    check(
+        old,
+        new,
+        &["Old.A : New", "λ(x : New) → merge { A = 1, B = 2 } x"],
+    );
+    assert!(!diff_types(&normal_form(old), &normal_form(new)).is_compatible());
+}
+
+#[test]
+fn test_unchanged_schema_is_compatible() {
+    let (old, new) = ("{ a : List < A | B > }", "{ a : List < B | A > }");
+    check(
+        old,
+        new,
+        &[
+            "{ a = [ < A | B >.A ] } : New",
+            "λ(x : New) → List/length < A | B > x.a",
+        ],
+    );
+    assert!(diff_types(&normal_form(old), &normal_form(new)).is_compatible());
+}
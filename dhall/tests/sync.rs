@@ -0,0 +1,39 @@
+#![cfg(feature = "sync")]
+use std::sync::Arc;
+use std::thread;
+
+use dhall::syntax::Expr;
+use dhall::{Normalized, Parsed, Typed};
+
+#[test]
+fn test_send_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Expr>();
+    assert_send_sync::<Typed>();
+    assert_send_sync::<Normalized>();
+}
+
+#[test]
+fn test_share_value_between_threads() {
+    let nf =
+        Parsed::parse_str("λ(x : Natural) → { a = x + 1, b = [ x, 2 + 2 ] }")
+            .unwrap()
+            .resolve()
+            .unwrap()
+            .typecheck()
+            .unwrap()
+            .normalize();
+    let nf = Arc::new(nf);
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            let nf = nf.clone();
+            thread::spawn(move || nf.to_string())
+        })
+        .collect();
+    for t in threads {
+        assert_eq!(
+            t.join().unwrap(),
+            "λ(x : Natural) → { a = x + 1, b = [x, 4] }"
+        );
+    }
+}
@@ -0,0 +1,148 @@
+use dhall::syntax::{
+    Rule, Span, SyntaxElement, SyntaxNode, SyntaxTree, TextEdit, TokenKind,
+};
+use dhall::Parsed;
+
+const CORPUS: &[&str] = &[
+    "let x : Natural = 1 + 2 * 3 in x",
+    "λ(x : Natural) → ∀(y : Type) → x",
+    "{ a = 1, b.c = True, d } ⫽ { a = 2 } ∧ {=} ⩓ {}",
+    "[ 1, 2 ] # ([] : List Natural)",
+    "\"abc${ 1 + 1 }def\" ++ ''\nfoo${ x }bar\n''",
+    "merge { A = 1 } (< A >.A) : Natural",
+    "./foo/bar as Text ? env:HOME ? missing",
+    "r with a.b = 1 with c = 2",
+    "x@1 `a-b`.c.{ d, e }.(f)",
+];
+
+#[test]
+fn test_syntax_tree_is_lossless() {
+    for src in CORPUS {
+        // Put comments wherever there is whitespace
+        let commented = src.replace(' ', " {- c {- nested -} -}\n-- line\n ");
+        let (parsed, tree) = Parsed::parse_str_lossless(&commented).unwrap();
+        assert_eq!(
+            parsed.to_string(),
+            Parsed::parse_str(src).unwrap().to_string()
+        );
+
+        let tokens = tree.tokens();
+        let text: String = tokens.iter().map(|tok| tok.text()).collect();
+        assert_eq!(text, commented);
+        let count =
+            |kind| tokens.iter().filter(|tok| tok.kind() == kind).count();
+        let spaces = src.matches(' ').count();
+        assert_eq!(count(TokenKind::BlockComment), spaces, "{}", commented);
+        assert_eq!(count(TokenKind::LineComment), spaces, "{}", commented);
+    }
+}
+
+#[test]
+fn test_syntax_tree_text_literals() {
+    let (_, tree) =
+        Parsed::parse_str_lossless("\" {- not a comment -} \" -- comment\n")
+            .unwrap();
+    let comments: Vec<_> = tree.comments().map(|tok| tok.text()).collect();
+    assert_eq!(comments, vec!["-- comment\n"]);
+}
+
+#[test]
+fn test_syntax_tree_nodes() {
+    let (_, tree) = Parsed::parse_str_lossless("{- header -}\nf x").unwrap();
+    let root = tree.root();
+    assert_eq!(root.rule(), Rule::final_expression);
+    assert_eq!(root.text(), "{- header -}\nf x");
+    let kinds: Vec<_> = root
+        .children()
+        .iter()
+        .map(|child| match child {
+            SyntaxElement::Token(tok) => Some(tok.kind()),
+            SyntaxElement::Node(_) => None,
+        })
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            Some(TokenKind::BlockComment),
+            Some(TokenKind::Whitespace),
+            None
+        ]
+    );
+    assert_eq!(root.child_nodes().next().unwrap().text(), "f x");
+}
+
+/// The rules and byte ranges of all the nodes and tokens of a tree, in order.
+fn shape(tree: &SyntaxTree) -> Vec<(Option<Rule>, usize, usize)> {
+    fn range(span: Span) -> (usize, usize) {
+        match span {
+            Span::Parsed(span) => span.byte_range(),
+            _ => unreachable!(),
+        }
+    }
+    fn go(node: &SyntaxNode, out: &mut Vec<(Option<Rule>, usize, usize)>) {
+        let (start, end) = range(node.span());
+        out.push((Some(node.rule()), start, end));
+        for child in node.children() {
+            match child {
+                SyntaxElement::Node(node) => go(node, out),
+                SyntaxElement::Token(tok) => {
+                    let (start, end) = range(tok.span());
+                    out.push((None, start, end));
+                }
+            }
+        }
+    }
+    let mut out = Vec::new();
+    go(tree.root(), &mut out);
+    out
+}
+
+#[test]
+fn test_syntax_tree_reparse() {
+    let src = "let a = [ 1, 2 + 3 ]\nlet b = { x = \"s${ a }\", y = (f a) } -- c\nin b";
+    let (_, tree) = Parsed::parse_str_lossless(src).unwrap();
+    let edits = [
+        ("2 + 3", "4"),
+        ("1", "1 + 10"),
+        ("a }", "a ++ a }"),
+        ("f a", "g"),
+        ("-- c", "{- c -}"),
+        (", y", " , z = 1, y"),
+        ("[ 1", "[ 0, 1"),
+        ("in b", "in b.x"),
+        ("let a", "let z = 0 let a"),
+        ("{ x", "{ w = 1, x"),
+    ];
+    for (old, new) in edits.iter() {
+        let start = src.find(old).unwrap();
+        let edit = TextEdit {
+            range: start..start + old.len(),
+            text: new.to_string(),
+        };
+        let new_src = src.replacen(old, new, 1);
+        let reparsed = tree.reparse(&edit).unwrap();
+        let (_, expected) = Parsed::parse_str_lossless(&new_src).unwrap();
+        assert_eq!(reparsed.text(), new_src);
+        assert_eq!(shape(&reparsed), shape(&expected), "{}", new_src);
+    }
+
+    let edit = TextEdit {
+        range: 8..9,
+        text: ",".to_owned(),
+    };
+    assert!(tree.reparse(&edit).is_err());
+}
+
+#[test]
+fn test_syntax_tree_reparse_invalid_range() {
+    let (_, tree) = Parsed::parse_str_lossless("\"λ\" ++ x").unwrap();
+    // A reversed range, ranges out of bounds, and one that splits `λ`.
+    for &(start, end) in &[(5, 4), (3, 20), (20, 21), (2, 3)] {
+        let edit = TextEdit {
+            range: start..end,
+            text: "a".to_owned(),
+        };
+        let err = tree.reparse(&edit).unwrap_err().to_string();
+        assert!(err.contains("Invalid edit range"), "{}", err);
+    }
+}
@@ -0,0 +1,37 @@
+mod common;
+
+use common::{normalize, type_of, typecheck};
+use dhall::Parsed;
+
+#[test]
+fn test_nested_with_on_literal() {
+    assert_eq!(
+        normalize(
+            "{ a = { b = 1 } } with a.b = 2 with a.c = True with d.e = 3"
+        ),
+        "{ a = { b = 2, c = True }, d = { e = 3 } }"
+    );
+}
+
+#[test]
+fn test_with_type() {
+    assert_eq!(
+        type_of("{ a = { b = 1 } } with a.b = True with c = 2"),
+        "{ a : { b : Bool }, c : Natural }"
+    );
+    assert!(typecheck("{ a = 1 } with a.b = 2").is_err());
+}
+
+#[test]
+fn test_with_after_completion() {
+    let src = "let T = { Type = { a : Natural }, default = { a = 1 } } \
+               in T::{=} with a = 3";
+    assert_eq!(normalize(src), "{ a = 3 }");
+}
+
+#[test]
+fn test_with_round_trips() {
+    let src = "λ(r : { a : { b : Natural } }) → r with a.b = 1 with c = 2";
+    let resolved = Parsed::parse_str(src).unwrap().resolve().unwrap();
+    assert_eq!(resolved.to_string(), src);
+}
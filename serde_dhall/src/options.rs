@@ -1,5 +1,6 @@
 use std::path::{Path, PathBuf};
 
+use dhall::semantics::ImportEnv;
 use dhall::Parsed;
 
 use crate::SimpleType;
@@ -85,7 +86,8 @@ pub struct Deserializer<'a, A> {
     source: Source<'a>,
     annot: A,
     allow_imports: bool,
-    // allow_remote_imports: bool,
+    allow_remote_imports: bool,
+    eval_budget: Option<usize>,
     // use_cache: bool,
 }
 
@@ -95,7 +97,8 @@ impl<'a> Deserializer<'a, NoAnnot> {
             source,
             annot: NoAnnot,
             allow_imports: true,
-            // allow_remote_imports: true,
+            allow_remote_imports: true,
+            eval_budget: None,
             // use_cache: true,
         }
     }
@@ -155,6 +158,8 @@ impl<'a> Deserializer<'a, NoAnnot> {
             annot: ManualAnnot(ty),
             source: self.source,
             allow_imports: self.allow_imports,
+            allow_remote_imports: self.allow_remote_imports,
+            eval_budget: self.eval_budget,
         }
     }
 
@@ -205,6 +210,8 @@ impl<'a> Deserializer<'a, NoAnnot> {
             annot: StaticAnnot,
             source: self.source,
             allow_imports: self.allow_imports,
+            allow_remote_imports: self.allow_remote_imports,
+            eval_budget: self.eval_budget,
         }
     }
 }
@@ -241,14 +248,57 @@ impl<'a, A> Deserializer<'a, A> {
         }
     }
 
-    // /// TODO
-    // pub fn remote_imports(&mut self, imports: bool) -> &mut Self {
-    //     self.allow_remote_imports = imports;
-    //     if imports {
-    //         self.allow_imports = true;
-    //     }
-    //     self
-    // }
+    /// Sets whether to allow fetching remote imports. Remote imports can still be resolved if they
+    /// are present in the cache, either by hash or because they were downloaded before.
+    ///
+    /// By default, remote imports are enabled.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// let data = "https://example.com/answer.dhall ? 42";
+    /// let answer = serde_dhall::from_str(data)
+    ///     .remote_imports(false)
+    ///     .parse::<u64>()?;
+    /// assert_eq!(answer, 42);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn remote_imports(self, imports: bool) -> Self {
+        Deserializer {
+            allow_remote_imports: imports,
+            ..self
+        }
+    }
+
+    /// Sets the maximum number of steps normalization may take. A step is the evaluation of one
+    /// subexpression or one function application. Going over fails with an "evaluation budget
+    /// exceeded" error. Use this when parsing untrusted input, which could otherwise keep the
+    /// normalizer busy for a very long time.
+    ///
+    /// By default, normalization is unbounded.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// let data = "Natural/fold 1000000000 Natural (λ(x : Natural) → x + 1) 0";
+    /// assert!(
+    ///     serde_dhall::from_str(data)
+    ///         .eval_budget(10_000)
+    ///         .parse::<u64>()
+    ///         .is_err()
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn eval_budget(self, steps: usize) -> Self {
+        Deserializer {
+            eval_budget: Some(steps),
+            ..self
+        }
+    }
 
     fn _parse<T>(&self) -> dhall::error::Result<Value>
     where
@@ -259,7 +309,8 @@ impl<'a, A> Deserializer<'a, A> {
             Source::File(p) => Parsed::parse_file(p.as_ref())?,
         };
         let resolved = if self.allow_imports {
-            parsed.resolve()?
+            let offline = !self.allow_remote_imports;
+            parsed.resolve_with(&mut ImportEnv::new().offline(offline))?
         } else {
             parsed.skip_resolve()?
         };
@@ -267,7 +318,11 @@ impl<'a, A> Deserializer<'a, A> {
             None => resolved.typecheck()?,
             Some(ty) => resolved.typecheck_with(ty.to_value().as_hir())?,
         };
-        Ok(Value::from_nir(typed.normalize().as_nir()))
+        let normalized = match self.eval_budget {
+            None => typed.normalize(),
+            Some(steps) => typed.normalize_with_budget(steps)?,
+        };
+        Ok(Value::from_nir(normalized.as_nir()))
     }
 
     /// Parses the chosen dhall value with the options provided.
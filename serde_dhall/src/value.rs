@@ -158,7 +158,7 @@ impl SimpleValue {
             }
             NirKind::NEListLit(xs) => {
                 // Detect and handle the special records that make assoc maps
-                if let NirKind::RecordLit(kvs) = xs[0].kind() {
+                if let NirKind::RecordLit(kvs) = xs.head().kind() {
                     if kvs.len() == 2
                         && kvs.contains_key("mapKey")
                         && kvs.contains_key("mapValue")
@@ -240,12 +240,11 @@ impl SimpleType {
             NirKind::UnionType(kts) => SimpleType::Union(
                 kts.iter()
                     .map(|(k, v)| {
-                        Some((
-                            k.into(),
-                            v.as_ref()
-                                .map(|v| Ok(Self::from_nir(v)?))
-                                .transpose()?,
-                        ))
+                        let v = match v {
+                            Some(v) => Some(Self::from_nir(v)?),
+                            None => None,
+                        };
+                        Some((k.into(), v))
                     })
                     .collect::<Option<_>>()?,
             ),